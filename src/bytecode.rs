@@ -0,0 +1,321 @@
+use std::fmt;
+
+pub mod compile;
+pub use compile::compile_program;
+
+pub mod vm;
+
+/// What a saved program starts with
+const MAGIC: &[u8; 4] = b"ATBC";
+/// Changes whenever the format does, older files have to be compiled again
+const VERSION: u8 = 1;
+/// The most locals a function can have, far more than a program uses. A frame is made
+/// with a slot for every one, so a file can't ask the vm for gigabytes
+const MAX_LOCALS: u32 = 65_536;
+
+/// An instruction of the stack machine. Operands are pushed from left to right, and an
+/// instruction pops its operands and pushes its result, like the native code does with
+/// `push` and `pop`
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Op {
+    Const(i64),
+    LoadGlobal(u32),
+    StoreGlobal(u32),
+    /// Parameters are the first locals of a function
+    LoadLocal(u32),
+    StoreLocal(u32),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Neg,
+    /// Pops the arguments, the last one on top, and pushes the return value
+    Call(u32),
+    /// Calls a function in place of the current one, and returns what it returns
+    TailCall(u32),
+    Return,
+    Exit,
+    PutChar,
+    /// Drops the value on top, what a call used as a statement returned
+    Pop,
+}
+
+/// The code of one function, top level code included
+#[derive(Debug)]
+pub struct FunctionCode {
+    pub name: String,
+    pub params: u32,
+    /// The parameters and variables, every one of them has a slot in the frame
+    pub locals: u32,
+    pub code: Vec<Op>,
+    /// The source line of each op, for runtime errors
+    pub lines: Vec<u32>,
+}
+
+/// A compiled program, the top level code is the first function
+#[derive(Debug)]
+pub struct Program {
+    /// Only the names, for the disassembly, the values start at 0
+    pub globals: Vec<String>,
+    pub functions: Vec<FunctionCode>,
+}
+
+impl Op {
+    /// How many values the op pops and pushes, calls need the function they call
+    fn stack_effect(&self, program: &Program) -> (usize, usize) {
+        match self {
+            Op::Const(_) | Op::LoadGlobal(_) | Op::LoadLocal(_) => (0, 1),
+            Op::StoreGlobal(_) | Op::StoreLocal(_) => (1, 0),
+            Op::Add | Op::Sub | Op::Mul | Op::Div => (2, 1),
+            Op::Neg => (1, 1),
+            Op::Call(function) => (program.functions[*function as usize].params as usize, 1),
+            Op::TailCall(function) => (program.functions[*function as usize].params as usize, 0),
+            Op::Return | Op::Exit | Op::PutChar | Op::Pop => (1, 0),
+        }
+    }
+}
+
+impl Program {
+    /// The file format is the magic number and version followed by the globals and the
+    /// functions, every number is little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+
+        push_u32(&mut bytes, self.globals.len() as u32);
+        for global in &self.globals {
+            push_str(&mut bytes, global);
+        }
+
+        push_u32(&mut bytes, self.functions.len() as u32);
+        for function in &self.functions {
+            push_str(&mut bytes, &function.name);
+            push_u32(&mut bytes, function.params);
+            push_u32(&mut bytes, function.locals);
+            push_u32(&mut bytes, function.code.len() as u32);
+
+            for (op, line) in function.code.iter().zip(&function.lines) {
+                let (opcode, operand) = match op {
+                    Op::Const(value) => (0, Some(*value)),
+                    Op::LoadGlobal(index) => (1, Some(*index as i64)),
+                    Op::StoreGlobal(index) => (2, Some(*index as i64)),
+                    Op::LoadLocal(index) => (3, Some(*index as i64)),
+                    Op::StoreLocal(index) => (4, Some(*index as i64)),
+                    Op::Add => (5, None),
+                    Op::Sub => (6, None),
+                    Op::Mul => (7, None),
+                    Op::Div => (8, None),
+                    Op::Neg => (9, None),
+                    Op::Call(function) => (10, Some(*function as i64)),
+                    Op::TailCall(function) => (11, Some(*function as i64)),
+                    Op::Return => (12, None),
+                    Op::Exit => (13, None),
+                    Op::PutChar => (14, None),
+                    Op::Pop => (15, None),
+                };
+
+                bytes.push(opcode);
+                match (op, operand) {
+                    (Op::Const(_), Some(value)) => bytes.extend_from_slice(&value.to_le_bytes()),
+                    (_, Some(index)) => push_u32(&mut bytes, index as u32),
+                    (_, None) => (),
+                }
+                push_u32(&mut bytes, *line);
+            }
+        }
+
+        return bytes;
+    }
+
+    /// Reads a program written by `to_bytes`, and checks that it can't make the vm read
+    /// outside of its stack, frames or globals
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, String> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(4)? != MAGIC {
+            return Err("Not an atomic bytecode file".to_string());
+        }
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(format!("Bytecode version {} isn't supported, compile the program again", version));
+        }
+
+        let mut globals = vec!();
+        for _ in 0..reader.u32()? {
+            globals.push(reader.str()?);
+        }
+
+        let mut functions = vec!();
+        for _ in 0..reader.u32()? {
+            let name = reader.str()?;
+            let params = reader.u32()?;
+            let locals = reader.u32()?;
+
+            let mut code = vec!();
+            let mut lines = vec!();
+            for _ in 0..reader.u32()? {
+                let op = match reader.take(1)?[0] {
+                    0 => Op::Const(i64::from_le_bytes(reader.take(8)?.try_into().unwrap())),
+                    1 => Op::LoadGlobal(reader.u32()?),
+                    2 => Op::StoreGlobal(reader.u32()?),
+                    3 => Op::LoadLocal(reader.u32()?),
+                    4 => Op::StoreLocal(reader.u32()?),
+                    5 => Op::Add,
+                    6 => Op::Sub,
+                    7 => Op::Mul,
+                    8 => Op::Div,
+                    9 => Op::Neg,
+                    10 => Op::Call(reader.u32()?),
+                    11 => Op::TailCall(reader.u32()?),
+                    12 => Op::Return,
+                    13 => Op::Exit,
+                    14 => Op::PutChar,
+                    15 => Op::Pop,
+                    opcode => return Err(format!("Unknown opcode {}", opcode)),
+                };
+
+                code.push(op);
+                lines.push(reader.u32()?);
+            }
+
+            functions.push(FunctionCode { name, params, locals, code, lines });
+        }
+
+        if reader.position != bytes.len() {
+            return Err("Unexpected bytes after the program".to_string());
+        }
+
+        let program = Program { globals, functions };
+        program.verify()?;
+
+        return Ok(program);
+    }
+
+    fn verify(&self) -> Result<(), String> {
+        if self.functions.is_empty() {
+            return Err("The program has no top level code".to_string());
+        }
+
+        // nothing passes the top level code any arguments
+        if self.functions[0].params != 0 {
+            return Err("The top level code has parameters".to_string());
+        }
+
+        for function in &self.functions {
+            if function.params > function.locals {
+                return Err(format!("Function {} has more parameters than locals", function.name));
+            }
+            if function.locals > MAX_LOCALS {
+                return Err(format!("Function {} has more than {} locals", function.name, MAX_LOCALS));
+            }
+
+            let mut depth = 0;
+
+            for op in &function.code {
+                let index_ok = match op {
+                    Op::LoadGlobal(index) | Op::StoreGlobal(index) => (*index as usize) < self.globals.len(),
+                    Op::LoadLocal(index) | Op::StoreLocal(index) => *index < function.locals,
+                    Op::Call(index) | Op::TailCall(index) => (*index as usize) < self.functions.len(),
+                    _ => true,
+                };
+                if !index_ok {
+                    return Err(format!("{:?} in function {} is out of range", op, function.name));
+                }
+
+                let (pops, pushes) = op.stack_effect(self);
+                if depth < pops {
+                    return Err(format!("{:?} in function {} pops more values than there are", op, function.name));
+                }
+                depth = depth - pops + pushes;
+            }
+
+            // the end can't be fallen off
+            if !matches!(function.code.last(), Some(Op::Return | Op::Exit | Op::TailCall(_))) {
+                return Err(format!("Function {} doesn't end with a return or exit", function.name));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let Some(taken) = self.bytes.get(self.position..self.position + count) else {
+            return Err("The bytecode file ends too early".to_string());
+        };
+        self.position += count;
+
+        return Ok(taken);
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        return Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let length = self.u32()? as usize;
+        let bytes = self.take(length)?;
+
+        return String::from_utf8(bytes.to_vec()).map_err(|_| "A name isn't valid UTF-8".to_string());
+    }
+}
+
+fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_str(bytes: &mut Vec<u8>, string: &str) {
+    push_u32(bytes, string.len() as u32);
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+/// Whether the bytes are a saved program, instead of source code
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// The disassembly, `--emit=bytecode`
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, global) in self.globals.iter().enumerate() {
+            writeln!(f, "global {} @{}", i, global)?;
+        }
+
+        for (i, function) in self.functions.iter().enumerate() {
+            writeln!(f, "\nfn {} {} (params {}, locals {})", i, function.name, function.params, function.locals)?;
+
+            for (offset, (op, line)) in function.code.iter().zip(&function.lines).enumerate() {
+                let text = match op {
+                    Op::Const(value) => format!("const {}", value),
+                    Op::LoadGlobal(index) => format!("load_global {} ; @{}", index, self.globals[*index as usize]),
+                    Op::StoreGlobal(index) => format!("store_global {} ; @{}", index, self.globals[*index as usize]),
+                    Op::LoadLocal(index) => format!("load_local {}", index),
+                    Op::StoreLocal(index) => format!("store_local {}", index),
+                    Op::Add => "add".to_string(),
+                    Op::Sub => "sub".to_string(),
+                    Op::Mul => "mul".to_string(),
+                    Op::Div => "div".to_string(),
+                    Op::Neg => "neg".to_string(),
+                    Op::Call(index) => format!("call {} ; {}", index, self.functions[*index as usize].name),
+                    Op::TailCall(index) => format!("tail_call {} ; {}", index, self.functions[*index as usize].name),
+                    Op::Return => "return".to_string(),
+                    Op::Exit => "exit".to_string(),
+                    Op::PutChar => "putchar".to_string(),
+                    Op::Pop => "pop".to_string(),
+                };
+
+                writeln!(f, "    {:4}  line {:<4} {}", offset, line, text)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use crate::{
+    opt::fold::first_token,
+    parser::{*, math::OperationType},
+};
+
+use super::{FunctionCode, Op, Program};
+
+/// Compiles the ast into bytecode. Every function, nested or not, becomes its own
+/// `FunctionCode`, after the top level code. The program must already have been checked
+/// by lowering it into the ir
+pub fn compile_program(program: &NodeProgram) -> Program {
+    // the indices are needed before the calls to functions that are defined later
+    let mut names = vec!("main".to_string());
+    collect_functions(program, &mut names);
+
+    let mut compiler = Compiler { globals: vec!(), function_indices: HashMap::new(), functions: vec!() };
+    for (index, name) in names.iter().enumerate().skip(1) {
+        compiler.function_indices.insert(name.clone(), index as u32);
+    }
+    compiler.functions.resize_with(names.len(), || None);
+
+    let mut main = FunctionBuilder::new("main".to_string(), true);
+    compiler.compile_scope(&mut main, program);
+
+    // falling off the end of the program exits with 0
+    main.emit(Op::Const(0));
+    main.emit(Op::Exit);
+    compiler.functions[0] = Some(main.finish());
+
+    let functions = compiler.functions.into_iter().map(|function| function.expect("Function wasn't compiled")).collect();
+
+    Program { globals: compiler.globals, functions }
+}
+
+fn collect_functions(program: &NodeProgram, names: &mut Vec<String>) {
+    for stmt in &program.statements {
+        if let NodeStatements::Function(func_stmt) = stmt {
+            names.push(func_stmt.identifier.info.clone());
+            collect_functions(&func_stmt.scope, names);
+        }
+    }
+}
+
+struct Compiler {
+    globals: Vec<String>,
+    function_indices: HashMap<String, u32>,
+    /// In the order the functions are defined in, after the top level code
+    functions: Vec<Option<FunctionCode>>,
+}
+
+/// The function that is being compiled
+struct FunctionBuilder {
+    name: String,
+    /// Whether this is the top level code, whose variables are globals
+    top_level: bool,
+    params: u32,
+    /// The slot of each parameter and variable
+    locals: HashMap<String, u32>,
+    code: Vec<Op>,
+    lines: Vec<u32>,
+    /// The line of the last token compiled, where the next op comes from
+    line: u32,
+}
+
+impl FunctionBuilder {
+    fn new(name: String, top_level: bool) -> FunctionBuilder {
+        FunctionBuilder { name, top_level, params: 0, locals: HashMap::new(), code: vec!(), lines: vec!(), line: 0 }
+    }
+
+    fn emit(&mut self, op: Op) {
+        self.code.push(op);
+        self.lines.push(self.line);
+    }
+
+    fn finish(self) -> FunctionCode {
+        FunctionCode {
+            name: self.name,
+            params: self.params,
+            locals: self.locals.len() as u32,
+            code: self.code,
+            lines: self.lines,
+        }
+    }
+}
+
+impl Compiler {
+    fn compile_scope(&mut self, builder: &mut FunctionBuilder, program: &NodeProgram) {
+        for stmt in &program.statements {
+            match stmt {
+                NodeStatements::Declare(declare_stmt) => {
+                    // compiled before the variable exists, so it can't refer to itself
+                    match &declare_stmt.expression {
+                        Some(expression) => self.compile_expression(builder, expression),
+                        None => builder.emit(Op::Const(0)),
+                    }
+
+                    let name = &declare_stmt.identifier.info;
+                    builder.line = declare_stmt.identifier.line as u32;
+
+                    if builder.top_level {
+                        self.globals.push(name.clone());
+                        builder.emit(Op::StoreGlobal(self.globals.len() as u32 - 1));
+                    } else {
+                        let slot = builder.locals.len() as u32;
+                        builder.locals.insert(name.clone(), slot);
+                        builder.emit(Op::StoreLocal(slot));
+                    }
+                },
+                NodeStatements::Set(set_stmt) => {
+                    self.compile_expression(builder, &set_stmt.expression);
+
+                    builder.line = set_stmt.identifier.line as u32;
+                    let store = self.variable(builder, &set_stmt.identifier.info, Op::StoreLocal, Op::StoreGlobal);
+                    builder.emit(store);
+                },
+                NodeStatements::Exit(exit_stmt) => {
+                    self.compile_expression(builder, &exit_stmt.expression);
+                    builder.emit(Op::Exit);
+                },
+                NodeStatements::PutChar(putchar_stmt) => {
+                    self.compile_expression(builder, &putchar_stmt.expression);
+                    builder.emit(Op::PutChar);
+                },
+                NodeStatements::Function(func_stmt) => self.compile_function(func_stmt),
+                // a program with extern functions is turned away by `check_no_externs`
+                NodeStatements::Extern(_) => (),
+                NodeStatements::FunctionCall(func_call_stmt) => {
+                    self.compile_call(builder, func_call_stmt, Op::Call);
+                    builder.emit(Op::Pop);
+                },
+                NodeStatements::Return(return_stmt) => {
+                    builder.line = return_stmt.token.line as u32;
+
+                    match &return_stmt.expression {
+                        Some(MathValue::Call(call)) => self.compile_call(builder, call, Op::TailCall),
+                        Some(expression) => {
+                            self.compile_expression(builder, expression);
+                            builder.emit(Op::Return);
+                        },
+                        None => {
+                            builder.emit(Op::Const(0));
+                            builder.emit(Op::Return);
+                        },
+                    }
+                },
+            }
+        }
+    }
+
+    /// Compiles the function on its own, nested functions don't end up inside the
+    /// enclosing one
+    fn compile_function(&mut self, func_stmt: &NodeStmtFunction) {
+        let mut builder = FunctionBuilder::new(func_stmt.identifier.info.clone(), false);
+        builder.line = func_stmt.identifier.line as u32;
+
+        for (slot, arg) in func_stmt.args.iter().enumerate() {
+            builder.locals.insert(arg.identifier.info.clone(), slot as u32);
+        }
+        builder.params = func_stmt.args.len() as u32;
+
+        self.compile_scope(&mut builder, &func_stmt.scope);
+
+        // falling off the end returns 0
+        builder.emit(Op::Const(0));
+        builder.emit(Op::Return);
+
+        let index = self.function_indices[&func_stmt.identifier.info] as usize;
+        self.functions[index] = Some(builder.finish());
+    }
+
+    /// Leaves the value of the expression on the stack
+    fn compile_expression(&mut self, builder: &mut FunctionBuilder, expr: &MathValue) {
+        match expr {
+            MathValue::Integer(integer) => {
+                builder.line = integer.line as u32;
+                builder.emit(Op::Const(integer.value.expect("Integer literal without a value")));
+            },
+
+            MathValue::Identifier(ident) => {
+                builder.line = ident.line as u32;
+                let load = self.variable(builder, &ident.info, Op::LoadLocal, Op::LoadGlobal);
+                builder.emit(load);
+            },
+
+            // the operands are pushed from left to right
+            MathValue::Operation(oper) => {
+                for operand in oper.operands() {
+                    self.compile_expression(builder, operand);
+                }
+
+                let op = match oper.as_ref() {
+                    OperationType::Add(_) => Op::Add,
+                    OperationType::Sub(_) => Op::Sub,
+                    OperationType::Mult(_) => Op::Mul,
+                    OperationType::Div(div) => {
+                        // dividing by zero is reported where the divisor is
+                        builder.line = first_token(&div.value_2).line as u32;
+                        Op::Div
+                    },
+                    OperationType::Negate(_) => Op::Neg,
+                };
+                builder.emit(op);
+            },
+
+            MathValue::Call(call) => self.compile_call(builder, call, Op::Call),
+        }
+    }
+
+    /// Pushes the arguments and calls the function with `call`, `Op::Call` or `Op::TailCall`
+    fn compile_call(&mut self, builder: &mut FunctionBuilder, call: &NodeStmtFunctionCall, op: fn(u32) -> Op) {
+        for arg in &call.args {
+            self.compile_expression(builder, arg);
+        }
+
+        builder.line = call.identifier.line as u32;
+        builder.emit(op(self.function_indices[&call.identifier.info]));
+    }
+
+    /// The load or store of a variable, a local if the function has one with the name,
+    /// otherwise a global
+    fn variable(&self, builder: &FunctionBuilder, name: &str, local: fn(u32) -> Op, global: fn(u32) -> Op) -> Op {
+        if let Some(slot) = builder.locals.get(name) {
+            return local(*slot);
+        }
+
+        let index = self.globals.iter().position(|global| global == name).expect("Unknown variable");
+        return global(index as u32);
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::{BufWriter, Write};
+
+use crate::errors::Error;
+
+use super::{Op, Program};
+
+/// How deeply calls can nest, the same limit as the tree-walking interpreter
+const MAX_DEPTH: usize = 100_000;
+
+/// A call that hasn't returned yet
+struct Frame {
+    function: usize,
+    /// The next op to run
+    pc: usize,
+    /// Where the locals start on the stack, the operands are above them
+    base: usize,
+}
+
+/// Runs the program and returns its exit code, `putchar` writes to stdout. The program
+/// has been verified, so the stack, locals and globals are never read out of range
+pub fn run(program: &Program) -> Result<i32, Error> {
+    let mut out = BufWriter::new(std::io::stdout());
+    let result = execute(program, &mut out);
+
+    out.flush().expect("Could not write to stdout");
+
+    return result;
+}
+
+fn execute(program: &Program, out: &mut impl Write) -> Result<i32, Error> {
+    let mut globals = vec!(0; program.globals.len());
+    let mut stack: Vec<i64> = vec!();
+    let mut frames = vec!();
+
+    enter(program, &mut stack, &mut frames, 0);
+
+    loop {
+        let frame = frames.last_mut().unwrap();
+        let function = &program.functions[frame.function];
+        let op = function.code[frame.pc];
+        let line = function.lines[frame.pc] as usize;
+        frame.pc += 1;
+
+        let base = frame.base;
+
+        match op {
+            Op::Const(value) => stack.push(value),
+            Op::LoadGlobal(index) => stack.push(globals[index as usize]),
+            Op::StoreGlobal(index) => globals[index as usize] = stack.pop().unwrap(),
+            Op::LoadLocal(slot) => stack.push(stack[base + slot as usize]),
+            Op::StoreLocal(slot) => {
+                let value = stack.pop().unwrap();
+                stack[base + slot as usize] = value;
+            },
+
+            Op::Add | Op::Sub | Op::Mul | Op::Div => {
+                let rhs = stack.pop().unwrap();
+                let lhs = stack.pop().unwrap();
+
+                let value = match op {
+                    Op::Add => lhs.wrapping_add(rhs),
+                    Op::Sub => lhs.wrapping_sub(rhs),
+                    Op::Mul => lhs.wrapping_mul(rhs),
+                    _ => {
+                        if rhs == 0 {
+                            return Err(Error { line, msg: "Division by zero".to_string() });
+                        }
+
+                        lhs.wrapping_div(rhs)
+                    },
+                };
+                stack.push(value);
+            },
+            Op::Neg => {
+                let value = stack.pop().unwrap();
+                stack.push(value.wrapping_neg());
+            },
+
+            Op::Call(index) => {
+                if frames.len() > MAX_DEPTH {
+                    let msg = format!("Calls are nested more than {} deep, the stack would overflow", MAX_DEPTH);
+                    return Err(Error { line, msg });
+                }
+
+                enter(program, &mut stack, &mut frames, index as usize);
+            },
+            Op::TailCall(index) => {
+                // the arguments replace the frame of the current call
+                let params = program.functions[index as usize].params as usize;
+                let args = stack.len() - params;
+                stack.drain(base..args);
+
+                frames.pop();
+                enter(program, &mut stack, &mut frames, index as usize);
+            },
+            Op::Return => {
+                let value = stack.pop().unwrap();
+                stack.truncate(base);
+                stack.push(value);
+
+                frames.pop();
+                if frames.is_empty() {
+                    return Ok(value as i32);
+                }
+            },
+
+            // only the lowest byte of the exit code is kept, like the exit syscall does
+            Op::Exit => return Ok(stack.pop().unwrap() as i32),
+            Op::PutChar => {
+                let value = stack.pop().unwrap();
+                out.write_all(&[value as u8]).expect("Could not write to stdout");
+            },
+            Op::Pop => {
+                stack.pop();
+            },
+        }
+    }
+}
+
+/// Starts a call, the arguments are on top of the stack and become the first locals
+fn enter(program: &Program, stack: &mut Vec<i64>, frames: &mut Vec<Frame>, function: usize) {
+    let code = &program.functions[function];
+    let base = stack.len() - code.params as usize;
+
+    stack.resize(base + code.locals as usize, 0);
+    frames.push(Frame { function, pc: 0, base });
+}
@@ -1,279 +1,695 @@
-use crate::code_gen::math::OperationType;
+use std::collections::HashMap;
 
-use crate::parser::MathValue;
+use crate::ir::{self, BinaryOp, Function, Instr, Module, Temp, Terminator, UnaryOp, Var};
 
-use std::{borrow::Borrow, collections::HashMap};
+pub mod asm;
+use asm::{Inst, Mem, Operand, Reg};
 
-use crate::{
-    exit_message,
-    parser::*,
-    tokenise::Token,
-};
+pub mod peephole;
 
+pub mod encode;
+pub mod elf;
+pub mod jit;
+
+pub mod c;
+pub mod llvm;
+pub mod aarch64;
+pub mod wasm;
+
+pub mod target;
+use target::Syscalls;
+
+mod regalloc;
+use regalloc::{Allocation, Value};
+
+/// Generates x86-64 nasm assembly from the ir.
+///
+/// Every function gets a frame based on `rbp`. Arguments are pushed by the caller, the
+/// first one first, so they sit above the return address. Temps and local variables are
+/// kept in registers where possible, the rest get a slot each below `rbp`. The top level
+/// variables live in the `.bss` section, or in `.data` in an object file when they start
+/// with a constant.
+///
+/// Extern functions are called the way C calls them. A program that declares any is
+/// linked with the C library, so it starts at `main` instead of `_start`, and `exit` and
+/// `putchar` go through the C library as well, to keep its buffered output in order
 pub struct CodeGen {
-    stack_ptr: isize,
-    pub asm: String,
-    pub post_asm: String,
+    pub asm: Vec<Inst>,
+    pub post_asm: Vec<Inst>,
+
+    output: Output,
+
+    /// Without it every value gets a stack slot
+    allocate_registers: bool,
+    /// Whether the program is linked with the C library, because it has extern functions
+    libc: bool,
+    /// The registers of the current function
+    allocation: Allocation,
+    /// The offset from `rbp` of every value of the current function that isn't in a register
+    slots: HashMap<Value, i32>,
+    /// The callee saved registers the current function uses, and where they are saved
+    saved: Vec<(Reg, i32)>,
+}
 
-    variables: HashMap<String, isize>,
-    functions: Vec<String>,
+/// What the generated code ends up in
+pub enum Output {
+    /// A program that runs the top level code from `_start`
+    Executable,
+    /// An object file to link into a C program. There is no `_start`, the top level code
+    /// is exported as `prefix` followed by `init` instead, and returns when it reaches
+    /// the end. Top level variables declared with a constant start with it without
+    /// `init` running. Every function is exported as `prefix` followed by its name,
+    /// through a wrapper that takes the arguments in registers the way C passes them
+    Object { prefix: String },
+    /// Code that is loaded into memory by `jit` and called from Rust. `putchar` and
+    /// `exit` call back into the host instead of making system calls
+    Jit,
 }
 
 impl CodeGen {
-    pub fn new() -> CodeGen {
+    pub fn new(allocate_registers: bool, output: Output) -> CodeGen {
         CodeGen {
-            stack_ptr: -1,
+            asm: vec!(),
+            post_asm: vec!(),
 
-            asm: String::from("global _start\n_start:\n    push rsp\n"),
-            post_asm: String::from("    mov rax, 60\n    mov rdi, 0\n    syscall\n"),
+            output,
 
-            variables: HashMap::new(),
-            functions: vec!(),
+            allocate_registers,
+            libc: false,
+            allocation: Allocation { registers: HashMap::new() },
+            slots: HashMap::new(),
+            saved: vec!(),
         }
     }
 
-    pub fn gen_output(&mut self, ast: &NodeProgram) -> String {
-        self.generate(ast);
-        let mut output = String::new();
-        output.push_str(&self.asm);
-        output.push_str(&self.post_asm);
+    /// The whole listing, the text followed by the bss section
+    pub fn gen_instructions(&mut self, module: &Module) -> Vec<Inst> {
+        self.generate(module);
+        let mut output = std::mem::take(&mut self.asm);
+        output.append(&mut self.post_asm);
 
         return output;
     }
 
-    pub fn generate(&mut self, ast: &NodeProgram) {
-        for stmt in &ast.statements {
-            match stmt {
-                NodeStatements::Declare(declare_stmt) => {
-                    self.gen_declare(declare_stmt);
-                },
-                NodeStatements::Exit(exit_stmt) => {
-                    self.gen_exit(exit_stmt);
-                },
-                NodeStatements::PutChar(putchar_stmt) => {
-                    self.gen_putchar(putchar_stmt);
-                }
-                NodeStatements::Set(set_stmt) => {
-                    self.gen_set(set_stmt);
-                },
-                NodeStatements::Function(func_stmt) => {
-                    self.gen_function(func_stmt);
-                }
-                NodeStatements::FunctionCall(func_call_stmt) => {
-                    self.gen_func_call(func_call_stmt);
-                }
+    pub fn generate(&mut self, module: &Module) {
+        self.libc = !module.externs.is_empty() && !matches!(self.output, Output::Jit);
+
+        if self.libc {
+            for name in ["exit", "putchar"] {
+                self.asm.push(Inst::Extern(name.to_string()));
+            }
+        }
+        if !matches!(self.output, Output::Jit) {
+            for external in &module.externs {
+                self.asm.push(Inst::Extern(external.name.clone()));
             }
         }
-    }
 
+        match &self.output {
+            Output::Executable => {
+                // the C library has its own `_start`, which calls `main`
+                let entry = if self.libc { "main" } else { "_start" };
 
-    fn gen_func_call(&mut self, func_call_stmt: &NodeStmtFunctionCall) {
-        self.asm.push_str(&format!("    call fn_{}\n", func_call_stmt.identifier.info));
-    }
+                self.asm.push(Inst::Global(entry.to_string()));
+                self.asm.push(Inst::Section(".text".to_string()));
 
+                self.asm.push(Inst::Label(entry.to_string()));
+                self.gen_function(module, &module.main, true);
+            },
+            Output::Object { prefix } => {
+                let prefix = prefix.clone();
+
+                // the end of the top level code returns to C instead of exiting
+                let mut init = module.main.clone();
+                init.name = "init".to_string();
+                if let Some(last) = init.blocks.last_mut() {
+                    last.terminator = Terminator::Return(ir::Operand::Const(0));
+                }
+
+                for function in std::iter::once(&init).chain(&module.functions) {
+                    self.asm.push(Inst::Global(format!("{}{}", prefix, function.name)));
+                }
+                self.asm.push(Inst::Section(".text".to_string()));
+
+                for function in std::iter::once(&init).chain(&module.functions) {
+                    self.gen_export(function, &prefix);
+                }
 
-    fn gen_function(&mut self, func_stmt: &NodeStmtFunction) {
-        // when the function gets called return address is pushed onto the stack
-        self.stack_ptr += 1;
+                self.asm.push(Inst::Comment("top level code".to_string()));
+                self.asm.push(Inst::Label("fn_init".to_string()));
+                self.gen_function(module, &init, false);
+            },
+            Output::Jit => {
+                self.asm.push(Inst::Section(".text".to_string()));
+                self.gen_jit_runtime();
 
-        self.functions.push(func_stmt.identifier.info.clone());
+                self.asm.push(Inst::Label(jit::MAIN.to_string()));
+                self.gen_jit_enter();
+                self.gen_function(module, &module.main, true);
 
-        let identifier: &str = &func_stmt.identifier.info;
-        let mut assembly = format!("; function definition\nfn_{}:\n", identifier);
+                for function in &module.functions {
+                    self.gen_jit_export(function);
+                }
+            },
+        }
 
-        let scope_asm = self.gen_scope(&func_stmt.scope, "    ret\n");
+        for function in &module.functions {
+            self.asm.push(Inst::Comment("function definition".to_string()));
+            self.asm.push(Inst::Label(format!("fn_{}", function.name)));
+            self.gen_function(module, function, false);
+        }
 
-        assembly.push_str(&scope_asm);
+        let initial = match self.output {
+            Output::Object { .. } => initial_values(&module.main),
+            _ => HashMap::new(),
+        };
 
+        if !initial.is_empty() {
+            self.post_asm.push(Inst::Section(".data".to_string()));
+        }
+        for (i, global) in module.globals.iter().enumerate() {
+            if let Some(value) = initial.get(&i) {
+                self.post_asm.push(Inst::Dq(format!("global_{}", global), *value));
+            }
+        }
 
-        self.post_asm.push_str(&assembly);
+        if initial.len() < module.globals.len() || matches!(self.output, Output::Jit) {
+            self.post_asm.push(Inst::Section(".bss".to_string()));
+        }
 
-        // the return then resets the stack
-        self.stack_ptr -= 1;
+        if let Output::Jit = self.output {
+            for slot in [jit::HOST_PUTCHAR, jit::HOST_EXIT, jit::HOST_CONTEXT, jit::SAVED_RSP] {
+                self.post_asm.push(Inst::Resq(slot.to_string(), 1));
+            }
+
+            // the addresses of the extern functions, filled in when the program is loaded
+            for external in &module.externs {
+                self.post_asm.push(Inst::Resq(format!("{}{}", jit::EXTERN_PREFIX, external.name), 1));
+            }
+        }
+
+        for (i, global) in module.globals.iter().enumerate() {
+            if !initial.contains_key(&i) {
+                self.post_asm.push(Inst::Resq(format!("global_{}", global), 1));
+            }
+        }
     }
 
-    fn gen_scope(&mut self, program: &NodeProgram, end_str: &str) -> String {
-        let mut new_generator = CodeGen {
-            stack_ptr: self.stack_ptr,
-            asm: String::new(),
-            post_asm: String::new(),
-            variables: self.variables.clone(),
-            functions: self.functions.clone(),
+    fn gen_function(&mut self, module: &Module, function: &Function, entry: bool) {
+        self.allocation = if self.allocate_registers {
+            regalloc::allocate(function)
+        } else {
+            Allocation { registers: HashMap::new() }
         };
+        self.slots.clear();
+        self.saved.clear();
 
-        new_generator.generate(program);
+        // parameters are above the return address and the saved rbp
+        for i in 0..function.params {
+            self.slots.insert(Value::Local(i), 16 + (function.params - 1 - i) as i32 * 8);
+        }
 
-        new_generator.asm.push_str("    ; fix stack pointer\n");
-        new_generator.add_stack_pointer(new_generator.stack_ptr - self.stack_ptr);
-        new_generator.asm.push_str(end_str);
+        // everything else that didn't get a register goes below rbp
+        let mut frame_size: i32 = 0;
+        let values = (function.params..function.locals.len()).map(Value::Local)
+            .chain((0..function.temps).map(|temp| Value::Temp(Temp(temp))));
 
-        return new_generator.asm + &new_generator.post_asm;
-    }
+        for value in values {
+            if self.allocation.register(value).is_none() {
+                frame_size += 8;
+                self.slots.insert(value, -frame_size);
+            }
+        }
 
-    fn gen_declare(&mut self, declare_stmt: &NodeStmtDeclare) {
-        if self.var_declared(&declare_stmt.identifier) {
-            exit_message(&format!("Variable {} has already been declared!", declare_stmt.identifier.info));
+        // the entry point never returns, so it doesn't have to keep any registers
+        if !entry {
+            for reg in self.allocation.used_callee_saved() {
+                frame_size += 8;
+                self.saved.push((reg, -frame_size));
+            }
         }
 
-        // insert into the variables hashmap
-        self.variables.insert(declare_stmt.identifier.info.clone(), self.stack_ptr + 1);
+        self.asm.push(Inst::Push(Operand::Reg(Reg::Rbp)));
+        self.asm.push(Inst::Mov(Operand::Reg(Reg::Rbp), Operand::Reg(Reg::Rsp)));
+        if frame_size > 0 {
+            self.asm.push(Inst::Sub(Operand::Reg(Reg::Rsp), Operand::Imm(frame_size as i64)));
+        }
 
-        // comment
-        self.asm.push_str(&format!("    ; declare variable {}\n", declare_stmt.identifier.info));
+        for (reg, offset) in self.saved.clone() {
+            self.asm.push(Inst::Mov(frame_address(offset), Operand::Reg(reg)));
+        }
 
-        if let Some(expression) = &declare_stmt.expression {
-            self.asm.push_str("    ; initial value for variable\n");
-            self.gen_expression(expression);
-        } else {
-            // we can just sub the stack pointer to allocate the space
-            self.asm.push_str("    ; allocate space for variable\n");
-            self.sub_stack_pointer(1);
+        for i in 0..function.params {
+            if let Some(reg) = self.allocation.register(Value::Local(i)) {
+                self.asm.push(Inst::Comment(format!("parameter {} in {}", function.locals[i], reg)));
+                self.asm.push(Inst::Mov(Operand::Reg(reg), frame_address(self.slots[&Value::Local(i)])));
+            }
         }
 
+        for (i, block) in function.blocks.iter().enumerate() {
+            self.asm.push(Inst::Comment(format!("block {}", i)));
+
+            for instr in &block.instrs {
+                self.gen_instr(module, function, instr);
+            }
+
+            match &block.terminator {
+                Terminator::Return(value) => {
+                    self.asm.push(Inst::Comment("return".to_string()));
+                    self.load(Reg::Rax, value);
+                    self.leave();
+                    self.asm.push(Inst::Ret);
+                },
+                Terminator::TailCall { func, args } => {
+                    self.asm.push(Inst::Comment(format!("tail call {}", func)));
+
+                    // everything is pushed before anything is overwritten, the arguments
+                    // can read the parameters they replace
+                    for arg in args {
+                        self.push(arg);
+                    }
+
+                    // our parameters are replaced with the arguments, closest to the
+                    // return address first. There are never more arguments than
+                    // parameters, so our caller removes the right amount
+                    for i in (0..args.len()).rev() {
+                        let offset = 16 + (args.len() - 1 - i) as i32 * 8;
+                        self.asm.push(Inst::Pop(frame_address(offset)));
+                    }
+
+                    self.leave();
+                    self.asm.push(Inst::Jmp(format!("fn_{}", func)));
+                },
+                Terminator::Exit(value) => {
+                    self.asm.push(Inst::Comment("exiting".to_string()));
+                    self.load(Reg::Rdi, value);
+
+                    if let Output::Jit = self.output {
+                        self.asm.push(Inst::Jmp("jit_exit".to_string()));
+                    } else if self.libc {
+                        self.gen_c_call("exit", std::slice::from_ref(value));
+                    } else {
+                        self.asm.push(Inst::Mov(Operand::Reg(Reg::Rax), Operand::Imm(Syscalls::X86_64.exit)));
+                        self.asm.push(Inst::Syscall);
+                    }
+                },
+            }
+        }
     }
 
-    fn gen_set(&mut self, set_stmt: &NodeStmtSet) {
-        if !self.var_declared(&set_stmt.identifier) {
-            exit_message(&format!("Variable referenced before assignment {}", set_stmt.identifier.info));
-            return;
+    /// The exported name of a function, which moves the arguments from the registers C
+    /// puts them in onto the stack, first one first, and calls the function
+    fn gen_export(&mut self, function: &Function, prefix: &str) {
+        self.asm.push(Inst::Comment(format!("exported {}", function.name)));
+        self.asm.push(Inst::Label(format!("{}{}", prefix, function.name)));
+        self.asm.push(Inst::Push(Operand::Reg(Reg::Rbp)));
+        self.asm.push(Inst::Mov(Operand::Reg(Reg::Rbp), Operand::Reg(Reg::Rsp)));
+
+        for i in 0..function.params {
+            match ARGUMENT_REGISTERS.get(i) {
+                Some(reg) => self.asm.push(Inst::Push(Operand::Reg(*reg))),
+                // the rest were pushed by the caller, above the return address
+                None => self.asm.push(Inst::Push(frame_address(16 + (i - ARGUMENT_REGISTERS.len()) as i32 * 8))),
+            }
         }
 
-        self.asm.push_str("    ; setting a variable\n");
-        self.gen_expression(&set_stmt.expression);
-        self.asm.push_str("    ; value is at the top of the stack\n");
-        self.pop("rax");
+        self.asm.push(Inst::Call(format!("fn_{}", function.name)));
+        self.asm.push(Inst::Mov(Operand::Reg(Reg::Rsp), Operand::Reg(Reg::Rbp)));
+        self.asm.push(Inst::Pop(Operand::Reg(Reg::Rbp)));
+        self.asm.push(Inst::Ret);
+    }
+
+    /// The code between the host and the program. The program calls `jit_putchar` and
+    /// jumps to `jit_exit` with the value in rdi, they call the host with the context and
+    /// the value, on a stack aligned the way the host expects. `jit_exit` and the returns
+    /// of the exported functions go back to the host through `jit_leave`, from wherever
+    /// the program was
+    fn gen_jit_runtime(&mut self) {
+        let rsp = Operand::Reg(Reg::Rsp);
+        let rbp = Operand::Reg(Reg::Rbp);
+        let rdi = Operand::Reg(Reg::Rdi);
+        let rsi = Operand::Reg(Reg::Rsi);
+        let rax = Operand::Reg(Reg::Rax);
+        let slot = |label: &str| Operand::Mem(Mem::Label(label.to_string()));
+
+        self.asm.push(Inst::Comment("putchar, the program's stack can be aligned any way".to_string()));
+        self.asm.push(Inst::Label("jit_putchar".to_string()));
+        self.asm.push(Inst::Push(rbp.clone()));
+        self.asm.push(Inst::Mov(rbp.clone(), rsp.clone()));
+        self.asm.push(Inst::And(rsp.clone(), Operand::Imm(-16)));
+        self.asm.push(Inst::Mov(rsi.clone(), rdi.clone()));
+        self.asm.push(Inst::Mov(rdi.clone(), slot(jit::HOST_CONTEXT)));
+        self.asm.push(Inst::Mov(rax.clone(), slot(jit::HOST_PUTCHAR)));
+        self.asm.push(Inst::CallIndirect(rax.clone()));
+        self.asm.push(Inst::Mov(rsp.clone(), rbp.clone()));
+        self.asm.push(Inst::Pop(rbp));
+        self.asm.push(Inst::Ret);
+
+        self.asm.push(Inst::Comment("exit, the program's stack is thrown away".to_string()));
+        self.asm.push(Inst::Label("jit_exit".to_string()));
+        self.asm.push(Inst::Mov(rsp.clone(), slot(jit::SAVED_RSP)));
+        self.asm.push(Inst::Mov(rsi, rdi.clone()));
+        self.asm.push(Inst::Mov(rdi, slot(jit::HOST_CONTEXT)));
+        self.asm.push(Inst::Mov(rax.clone(), slot(jit::HOST_EXIT)));
+        self.asm.push(Inst::CallIndirect(rax));
+        self.asm.push(Inst::Jmp("jit_leave".to_string()));
+
+        self.asm.push(Inst::Comment("back to the host, undoes the entry".to_string()));
+        self.asm.push(Inst::Label("jit_leave".to_string()));
+        self.asm.push(Inst::Add(rsp, Operand::Imm(8)));
+        for reg in JIT_SAVED.iter().rev() {
+            self.asm.push(Inst::Pop(Operand::Reg(*reg)));
+        }
+        self.asm.push(Inst::Ret);
+    }
 
-        let var_ptr = self.get_var_ptr(&set_stmt.identifier);
-        self.asm.push_str(&format!("    mov [rsp + {}], rax\n", (self.stack_ptr - var_ptr) * 8));
+    /// Saves the registers the host expects to be kept, and where the stack is so
+    /// `exit` can get back to it. The stack is left aligned to 16 bytes
+    fn gen_jit_enter(&mut self) {
+        for reg in JIT_SAVED {
+            self.asm.push(Inst::Push(Operand::Reg(reg)));
+        }
+        self.asm.push(Inst::Sub(Operand::Reg(Reg::Rsp), Operand::Imm(8)));
+        self.asm.push(Inst::Mov(Operand::Mem(Mem::Label(jit::SAVED_RSP.to_string())), Operand::Reg(Reg::Rsp)));
     }
 
-    fn gen_exit(&mut self, exit_stmt: &NodeStmtExit) {
-        self.asm.push_str("    ; generating exit value\n");
-        self.gen_expression(&exit_stmt.expression);
+    /// The entry point of a function for the host, which takes a pointer to the
+    /// arguments in rdi and pushes them the way the functions take them
+    fn gen_jit_export(&mut self, function: &Function) {
+        self.asm.push(Inst::Comment(format!("exported {}", function.name)));
+        self.asm.push(Inst::Label(format!("{}{}", jit::EXPORT_PREFIX, function.name)));
+        self.gen_jit_enter();
 
-        self.asm.push_str("    ; exiting\n");
-        self.asm.push_str("    mov rax, 60\n");
-        self.pop("rdi");
+        for i in 0..function.params {
+            self.asm.push(Inst::Push(Operand::Mem(Mem::Base(Reg::Rdi, i as i32 * 8))));
+        }
 
-        self.asm.push_str("    syscall\n");
+        self.asm.push(Inst::Call(format!("fn_{}", function.name)));
+        self.asm.push(Inst::Mov(Operand::Reg(Reg::Rsp), Operand::Mem(Mem::Label(jit::SAVED_RSP.to_string()))));
+        self.asm.push(Inst::Jmp("jit_leave".to_string()));
     }
 
-    fn gen_putchar(&mut self, putchar_stmt: &NodeStmtPutChar) {
-        self.asm.push_str("    ; put char expression\n");
-        self.gen_expression(&putchar_stmt.expression);
+    /// Calls a function the way C does, the first six arguments in registers and the rest
+    /// on the stack, which has to be aligned to 16 bytes at the call. The frames here only
+    /// keep it aligned to 8, so it is aligned at runtime and the old `rsp` is kept above
+    /// the arguments. None of the arguments are floats, which `al` says for variadic
+    /// functions like `printf`. The return value is left in rax
+    fn gen_c_call(&mut self, func: &str, args: &[ir::Operand]) {
+        self.asm.push(Inst::Comment(format!("call {} the way C does", func)));
+
+        let on_stack = args.len().saturating_sub(ARGUMENT_REGISTERS.len());
+        // the arguments on the stack have to end up aligned as well
+        let padding = on_stack % 2;
+
+        self.asm.push(Inst::Mov(Operand::Reg(Reg::Rax), Operand::Reg(Reg::Rsp)));
+        self.asm.push(Inst::And(Operand::Reg(Reg::Rsp), Operand::Imm(-16)));
+        self.asm.push(Inst::Sub(Operand::Reg(Reg::Rsp), Operand::Imm(8)));
+        self.asm.push(Inst::Push(Operand::Reg(Reg::Rax)));
+        self.asm.push(Inst::Sub(Operand::Reg(Reg::Rsp), Operand::Imm(padding as i64 * 8)));
+
+        // everything is pushed, last first, and the first six are popped into their
+        // registers, so loading one register can't overwrite a value another one needs
+        for arg in args.iter().rev() {
+            self.push(arg);
+        }
+        for reg in ARGUMENT_REGISTERS.iter().take(args.len()) {
+            self.asm.push(Inst::Pop(Operand::Reg(*reg)));
+        }
+
+        // a variadic function reads how many vector registers hold arguments from al
+        self.asm.push(Inst::Mov(Operand::Reg(Reg::Rax), Operand::Imm(0)));
+
+        match self.output {
+            Output::Jit => {
+                let slot = format!("{}{}", jit::EXTERN_PREFIX, func);
+                self.asm.push(Inst::CallIndirect(Operand::Mem(Mem::Label(slot))));
+            },
+            _ => self.asm.push(Inst::Call(func.to_string())),
+        }
+
+        let saved = ((on_stack + padding) * 8) as i32;
+        self.asm.push(Inst::Mov(Operand::Reg(Reg::Rsp), Operand::Mem(Mem::Base(Reg::Rsp, saved))));
+    }
 
-        self.asm.push_str("    ; put char syscall\n");
-        self.asm.push_str("    mov rax, 1\n");
-        self.asm.push_str("    mov edi, 1\n");
-        self.asm.push_str("    mov rsi, rsp\n");
-        self.asm.push_str("    mov rdx, 1\n");
+    /// Restores the saved registers and removes the frame, the return address is on top
+    fn leave(&mut self) {
+        for (reg, offset) in self.saved.clone() {
+            self.asm.push(Inst::Mov(Operand::Reg(reg), frame_address(offset)));
+        }
 
-        self.asm.push_str("    syscall\n");
-        self.add_stack_pointer(1);
+        self.asm.push(Inst::Mov(Operand::Reg(Reg::Rsp), Operand::Reg(Reg::Rbp)));
+        self.asm.push(Inst::Pop(Operand::Reg(Reg::Rbp)));
     }
 
-    fn gen_expression(&mut self, expr: &MathValue) {
-        match expr {
-            MathValue::Integer(integer) => self.push(&integer.info),
-            MathValue::Identifier(ident) => self.push_var_value(&ident),
+    fn gen_instr(&mut self, module: &Module, function: &Function, instr: &Instr) {
+        match instr {
+            Instr::Load { dest, var } => {
+                self.asm.push(Inst::Comment(format!("load {}", function.var_name(module, *var))));
+
+                let source = self.var_address(module, *var);
+                self.move_to(Value::Temp(*dest), source);
+            },
 
-            MathValue::Operation(oper) => {
-                match oper.borrow() {
-                    OperationType::Add(add) => {
-                        self.gen_expression(&add.value_1);
-                        self.gen_expression(&add.value_2);
+            Instr::Store { var, value } => {
+                self.asm.push(Inst::Comment(format!("store {}", function.var_name(module, *var))));
 
-                        self.pop("rax");
-                        self.pop("rdi");
+                let source = self.operand(value, Reg::Rax);
+                let address = self.var_address(module, *var);
+                self.mov(address, source);
+            },
 
-                        self.asm.push_str("    add rax, rdi\n");
+            Instr::Binary { dest, op, lhs, rhs } => {
+                self.load(Reg::Rax, lhs);
+                let rax = Operand::Reg(Reg::Rax);
 
-                        self.push("rax");
+                match op {
+                    BinaryOp::Add => {
+                        let rhs = self.operand(rhs, Reg::Rcx);
+                        self.asm.push(Inst::Add(rax, rhs));
                     },
+                    BinaryOp::Sub => {
+                        let rhs = self.operand(rhs, Reg::Rcx);
+                        self.asm.push(Inst::Sub(rax, rhs));
+                    },
+                    BinaryOp::Mul => {
+                        match self.operand(rhs, Reg::Rcx) {
+                            Operand::Imm(value) => self.asm.push(Inst::ImulImm(Reg::Rax, rax, value as i32)),
+                            rhs => self.asm.push(Inst::Imul(Reg::Rax, rhs)),
+                        }
+                    },
+                    BinaryOp::Div => {
+                        // idiv can't take an immediate
+                        let rhs = match rhs {
+                            ir::Operand::Const(_) => {
+                                self.load(Reg::Rcx, rhs);
+                                Operand::Reg(Reg::Rcx)
+                            },
+                            ir::Operand::Temp(_) => self.operand(rhs, Reg::Rcx),
+                        };
+
+                        // sign extend rax into rdx, idiv divides rdx:rax
+                        self.asm.push(Inst::Cqo);
+                        self.asm.push(Inst::Idiv(rhs));
+                    },
+                }
 
-                    OperationType::Sub(sub) => {
-                        self.gen_expression(&sub.value_1);
-                        self.gen_expression(&sub.value_2);
+                self.move_to(Value::Temp(*dest), Operand::Reg(Reg::Rax));
+            },
 
-                        self.pop("rax");
-                        self.pop("rdi");
+            Instr::Unary { dest, op, value } => {
+                self.load(Reg::Rax, value);
 
-                        self.asm.push_str("    sub rdi, rax\n");
+                match op {
+                    UnaryOp::Neg => self.asm.push(Inst::Neg(Operand::Reg(Reg::Rax))),
+                }
 
-                        self.push("rdi");
-                    },
-                    
-                    OperationType::Mult(mult) => {
-                        // i think this works, multiplication is strange though
-                        self.gen_expression(&mult.value_1);
-                        self.gen_expression(&mult.value_2);
+                self.move_to(Value::Temp(*dest), Operand::Reg(Reg::Rax));
+            },
 
-                        self.pop("rax");
-                        self.pop("rdi");
+            Instr::Copy { dest, value } => {
+                let source = self.operand(value, Reg::Rax);
+                self.move_to(Value::Temp(*dest), source);
+            },
+
+            Instr::Call { dest, func, args } => {
+                // the arguments are passed on the stack, the first one is pushed first
+                for (i, arg) in args.iter().enumerate() {
+                    self.asm.push(Inst::Comment(format!("argument {}", i)));
+                    self.push(arg);
+                }
 
-                        self.asm.push_str("    mul rdi\n");
+                self.asm.push(Inst::Call(format!("fn_{}", func)));
 
-                        self.push("rax");
-                    },
-                    
-                    OperationType::Div(div) => {
-                        self.gen_expression(&div.value_1);
-                        self.gen_expression(&div.value_2);
+                self.asm.push(Inst::Comment("remove the arguments".to_string()));
+                self.asm.push(Inst::Add(Operand::Reg(Reg::Rsp), Operand::Imm(args.len() as i64 * 8)));
 
-                        self.asm.push_str("    xor rdx, rdx\n");
-                        
-                        // different order because division is the arg / rax i think
-                        self.pop("rbx");
-                        self.pop("rax");
+                // the return value is in rax
+                if let Some(dest) = dest {
+                    self.move_to(Value::Temp(*dest), Operand::Reg(Reg::Rax));
+                }
+            },
 
-                        self.asm.push_str("    idiv rbx\n");
+            Instr::CallExtern { dest, func, args } => {
+                self.gen_c_call(func, args);
 
-                        self.push("rax");
-                    },
+                if let Some(dest) = dest {
+                    self.move_to(Value::Temp(*dest), Operand::Reg(Reg::Rax));
                 }
             },
+
+            Instr::PutChar { value } if self.libc => {
+                self.gen_c_call("putchar", std::slice::from_ref(value));
+            },
+
+            Instr::PutChar { value } if matches!(self.output, Output::Jit) => {
+                self.asm.push(Inst::Comment("put char through the host".to_string()));
+                self.load(Reg::Rdi, value);
+                self.asm.push(Inst::Call("jit_putchar".to_string()));
+            },
+
+            Instr::PutChar { value } => {
+                self.asm.push(Inst::Comment("put char, the syscall reads the byte from the stack".to_string()));
+                self.push(value);
+
+                self.asm.push(Inst::Mov(Operand::Reg(Reg::Rax), Operand::Imm(Syscalls::X86_64.write)));
+                self.asm.push(Inst::Mov(Operand::Reg(Reg::Rdi), Operand::Imm(1)));
+                self.asm.push(Inst::Mov(Operand::Reg(Reg::Rsi), Operand::Reg(Reg::Rsp)));
+                self.asm.push(Inst::Mov(Operand::Reg(Reg::Rdx), Operand::Imm(1)));
+                self.asm.push(Inst::Syscall);
+
+                self.asm.push(Inst::Add(Operand::Reg(Reg::Rsp), Operand::Imm(8)));
+            },
         }
     }
 
-
-    fn push(&mut self, reg_or_lit: &str) {
-        self.stack_ptr += 1;
-        self.asm.push_str(&format!("    push {}\n", reg_or_lit));
+    /// Where a value lives, a register or a stack slot
+    fn location(&self, value: Value) -> Operand {
+        match self.allocation.register(value) {
+            Some(reg) => Operand::Reg(reg),
+            None => frame_address(self.slots[&value]),
+        }
     }
 
-    fn pop(&mut self, reg: &str) {
-        self.stack_ptr -= 1;
-        self.asm.push_str(&format!("    pop {}\n", reg));
+    /// An operand for an instruction that takes a register, memory or a 32 bit immediate.
+    /// Bigger immediates are moved into `scratch` first
+    fn operand(&mut self, operand: &ir::Operand, scratch: Reg) -> Operand {
+        match operand {
+            ir::Operand::Const(value) if i32::try_from(*value).is_ok() => Operand::Imm(*value),
+            ir::Operand::Const(_) => {
+                self.load(scratch, operand);
+                Operand::Reg(scratch)
+            },
+            ir::Operand::Temp(temp) => self.location(Value::Temp(*temp)),
+        }
     }
 
-    /// Allocates more space
-    fn sub_stack_pointer(&mut self, amount: isize) {
-        self.stack_ptr += amount;
-        self.asm.push_str(&format!("    sub rsp, {}\n", amount * 8));
+    /// Moves an operand into a register
+    fn load(&mut self, reg: Reg, operand: &ir::Operand) {
+        let source = match operand {
+            ir::Operand::Const(value) => Operand::Imm(*value),
+            ir::Operand::Temp(temp) => self.location(Value::Temp(*temp)),
+        };
+
+        self.mov(Operand::Reg(reg), source);
     }
 
-    /// Un-allocates space
-    fn add_stack_pointer(&mut self, amount: isize) {
-        self.stack_ptr -= amount;
-        self.asm.push_str(&format!("    add rsp, {}\n", amount * 8));
+    /// Moves a register, memory or immediate into where a value lives
+    fn move_to(&mut self, value: Value, source: Operand) {
+        let dest = self.location(value);
+        self.mov(dest, source);
     }
 
-    fn var_declared(&self, identifier: &Token) -> bool {
-        self.variables.contains_key(&identifier.info)
+    /// A `mov` that goes through rax when both sides are memory, and is left out when
+    /// both sides are the same
+    fn mov(&mut self, dest: Operand, source: Operand) {
+        if dest == source {
+            return;
+        }
+
+        if dest.is_mem() && source.is_mem() {
+            self.asm.push(Inst::Mov(Operand::Reg(Reg::Rax), source));
+            self.asm.push(Inst::Mov(dest, Operand::Reg(Reg::Rax)));
+        } else {
+            self.asm.push(Inst::Mov(dest, source));
+        }
     }
 
-    fn get_var_ptr(&self, identifier: &Token) -> isize {
-        let Some(value) = self.variables.get_key_value(&identifier.info) else {
-            panic!("Unknown identifier {}", identifier.info);
+    /// Constants always go through rax, the peephole pass pushes the small ones directly
+    fn push(&mut self, operand: &ir::Operand) {
+        let source = match operand {
+            ir::Operand::Const(_) => {
+                self.load(Reg::Rax, operand);
+                Operand::Reg(Reg::Rax)
+            },
+            ir::Operand::Temp(temp) => self.location(Value::Temp(*temp)),
         };
 
-        return *value.1;
+        self.asm.push(Inst::Push(source));
+    }
+
+    fn var_address(&self, module: &Module, var: Var) -> Operand {
+        match var {
+            Var::Global(index) => Operand::Mem(Mem::Label(format!("global_{}", module.globals[index]))),
+            Var::Local(index) => self.location(Value::Local(index)),
+        }
     }
+}
+
+/// Where C takes the first six arguments
+const ARGUMENT_REGISTERS: [Reg; 6] = [Reg::Rdi, Reg::Rsi, Reg::Rdx, Reg::Rcx, Reg::R8, Reg::R9];
+
+/// The registers the host expects a call into the program to keep, in the order they
+/// are pushed
+const JIT_SAVED: [Reg; 6] = [Reg::Rbp, Reg::Rbx, Reg::R12, Reg::R13, Reg::R14, Reg::R15];
+
+/// The constants the top level code first stores in the globals, by index. Only the first
+/// block is looked at, and temps with constant values are worked out on the way, so the
+/// values are found whether or not the expressions were folded
+fn initial_values(main: &Function) -> HashMap<usize, i64> {
+    let mut temps: HashMap<Temp, i64> = HashMap::new();
+    let mut stored: HashMap<usize, Option<i64>> = HashMap::new();
+
+    let Some(block) = main.blocks.first() else {
+        return HashMap::new();
+    };
+
+    for instr in &block.instrs {
+        let constant = |operand: &ir::Operand| match operand {
+            ir::Operand::Const(value) => Some(*value),
+            ir::Operand::Temp(temp) => temps.get(temp).copied(),
+        };
 
-    fn push_var_value(&mut self, identifier: &Token) {
-        let var_ptr = self.get_var_ptr(&identifier);
-        self.asm.push_str(&format!("    mov rax, QWORD [rsp + {}]\n", (self.stack_ptr - var_ptr) * 8));
+        let (dest, value) = match instr {
+            Instr::Binary { dest, op, lhs, rhs } => {
+                let value = constant(lhs).zip(constant(rhs)).and_then(|(lhs, rhs)| match op {
+                    BinaryOp::Add => Some(lhs.wrapping_add(rhs)),
+                    BinaryOp::Sub => Some(lhs.wrapping_sub(rhs)),
+                    BinaryOp::Mul => Some(lhs.wrapping_mul(rhs)),
+                    // dividing by zero or the smallest value by -1 traps, that is left to `init`
+                    BinaryOp::Div => lhs.checked_div(rhs),
+                });
+                (*dest, value)
+            },
+            Instr::Unary { dest, op: UnaryOp::Neg, value } => (*dest, constant(value).map(i64::wrapping_neg)),
+            Instr::Copy { dest, value } => (*dest, constant(value)),
+            Instr::Store { var: Var::Global(index), value } => {
+                let value = constant(value);
+                stored.entry(*index).or_insert(value);
+                continue;
+            },
+            _ => continue,
+        };
 
-        self.push("rax");
+        if let Some(value) = value {
+            temps.insert(dest, value);
+        }
     }
+
+    return stored.into_iter()
+        .filter_map(|(index, value)| Some((index, value?)))
+        .collect();
+}
+
+fn frame_address(offset: i32) -> Operand {
+    Operand::Mem(Mem::Base(Reg::Rbp, offset))
 }
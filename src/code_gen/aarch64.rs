@@ -0,0 +1,301 @@
+use crate::ir::{BinaryOp, Function, Instr, Module, Operand, Temp, Terminator, UnaryOp, Var};
+
+use super::target::Syscalls;
+
+/// Generates AArch64 Linux assembly in GNU assembler syntax from the ir.
+///
+/// The frames work like the x86-64 ones: `x29` points at the saved frame pointer and
+/// return address, the arguments are above them and every local and temp has a slot
+/// below. `sp` has to stay 16 byte aligned, so the caller reserves a 16 byte aligned area
+/// for the arguments and stores them there, the first one lowest. There is no register
+/// allocation, `x9` to `x11` are only used within an instruction. The listing starts at
+/// `_start` and makes system calls itself, so it can't call extern functions
+pub struct Aarch64Gen {
+    pub asm: Vec<String>,
+    pub post_asm: Vec<String>,
+}
+
+impl Aarch64Gen {
+    pub fn new() -> Aarch64Gen {
+        Aarch64Gen {
+            asm: vec!(".global _start".to_string(), ".text".to_string()),
+            post_asm: vec!(),
+        }
+    }
+
+    /// The whole listing, the text followed by the bss section
+    pub fn gen_asm(&mut self, module: &Module) -> String {
+        self.generate(module);
+
+        let mut output = String::new();
+        for line in self.asm.iter().chain(self.post_asm.iter()) {
+            output.push_str(line);
+            output.push('\n');
+        }
+
+        return output;
+    }
+
+    pub fn generate(&mut self, module: &Module) {
+        self.label("_start");
+        self.gen_function(module, &module.main);
+
+        for function in &module.functions {
+            self.comment("function definition");
+            self.label(&format!("fn_{}", function.name));
+            self.gen_function(module, function);
+        }
+
+        if !module.globals.is_empty() {
+            self.post_asm.push(".bss".to_string());
+            self.post_asm.push(".balign 8".to_string());
+        }
+
+        for global in &module.globals {
+            self.post_asm.push(format!("global_{}:", global));
+            self.post_asm.push("    .zero 8".to_string());
+        }
+    }
+
+    fn gen_function(&mut self, module: &Module, function: &Function) {
+        let slots = function.locals.len() - function.params + function.temps;
+        let frame_size = (slots * 8).next_multiple_of(16) as i64;
+
+        self.inst("stp x29, x30, [sp, #-16]!");
+        self.inst("mov x29, sp");
+        self.adjust_sp("sub", frame_size);
+
+        for (i, block) in function.blocks.iter().enumerate() {
+            self.comment(&format!("block {}", i));
+
+            for instr in &block.instrs {
+                self.gen_instr(module, function, instr);
+            }
+
+            match &block.terminator {
+                Terminator::Return(value) => {
+                    self.comment("return");
+                    self.load("x0", value, function);
+                    self.leave();
+                    self.inst("ret");
+                },
+                Terminator::TailCall { func, args } => {
+                    self.comment(&format!("tail call {}", func));
+
+                    // the arguments replace our parameters, there are never more of them.
+                    // They are all in temps, so nothing they need is overwritten
+                    for (i, arg) in args.iter().enumerate() {
+                        self.load("x9", arg, function);
+                        let address = self.frame_address(16 + i as i64 * 8);
+                        self.inst(&format!("str x9, {}", address));
+                    }
+
+                    self.leave();
+                    self.inst(&format!("b fn_{}", func));
+                },
+                Terminator::Exit(value) => {
+                    self.comment("exiting");
+                    self.load("x0", value, function);
+                    self.mov_imm("x8", Syscalls::AARCH64.exit);
+                    self.inst("svc #0");
+                },
+            }
+        }
+    }
+
+    /// Removes the frame and restores the frame pointer and the return address
+    fn leave(&mut self) {
+        self.inst("mov sp, x29");
+        self.inst("ldp x29, x30, [sp], #16");
+    }
+
+    fn gen_instr(&mut self, module: &Module, function: &Function, instr: &Instr) {
+        match instr {
+            Instr::Load { dest, var } => {
+                self.comment(&format!("load {}", function.var_name(module, *var)));
+
+                let address = self.var_address(module, function, *var);
+                self.inst(&format!("ldr x9, {}", address));
+                self.store_temp("x9", *dest, function);
+            },
+
+            Instr::Store { var, value } => {
+                self.comment(&format!("store {}", function.var_name(module, *var)));
+
+                self.load("x9", value, function);
+                let address = self.var_address(module, function, *var);
+                self.inst(&format!("str x9, {}", address));
+            },
+
+            Instr::Binary { dest, op, lhs, rhs } => {
+                self.load("x9", lhs, function);
+                self.load("x10", rhs, function);
+
+                let op = match op {
+                    BinaryOp::Add => "add",
+                    BinaryOp::Sub => "sub",
+                    BinaryOp::Mul => "mul",
+                    BinaryOp::Div => "sdiv",
+                };
+                self.inst(&format!("{} x9, x9, x10", op));
+
+                self.store_temp("x9", *dest, function);
+            },
+
+            Instr::Unary { dest, op, value } => {
+                self.load("x9", value, function);
+
+                match op {
+                    UnaryOp::Neg => self.inst("neg x9, x9"),
+                }
+
+                self.store_temp("x9", *dest, function);
+            },
+
+            Instr::Copy { dest, value } => {
+                self.load("x9", value, function);
+                self.store_temp("x9", *dest, function);
+            },
+
+            Instr::Call { dest, func, args } => {
+                let area = (args.len() * 8).next_multiple_of(16) as i64;
+                self.adjust_sp("sub", area);
+
+                for (i, arg) in args.iter().enumerate() {
+                    self.comment(&format!("argument {}", i));
+                    self.load("x9", arg, function);
+                    self.inst(&format!("str x9, [sp, #{}]", i * 8));
+                }
+
+                self.inst(&format!("bl fn_{}", func));
+
+                if area > 0 {
+                    self.comment("remove the arguments");
+                    self.adjust_sp("add", area);
+                }
+
+                // the return value is in x0
+                if let Some(dest) = dest {
+                    self.store_temp("x0", *dest, function);
+                }
+            },
+
+            // the listing is linked without the C library, so programs with extern
+            // functions are turned away by `check_no_externs`
+            Instr::CallExtern { .. } => unreachable!("Extern functions can't be called from AArch64 code"),
+
+            Instr::PutChar { value } => {
+                self.comment("put char, the syscall reads the byte from the stack");
+                self.load("x9", value, function);
+
+                self.inst("sub sp, sp, #16");
+                self.inst("strb w9, [sp]");
+                self.inst("mov x0, #1");
+                self.inst("mov x1, sp");
+                self.inst("mov x2, #1");
+                self.mov_imm("x8", Syscalls::AARCH64.write);
+                self.inst("svc #0");
+                self.inst("add sp, sp, #16");
+            },
+        }
+    }
+
+    /// Moves an operand into a register
+    fn load(&mut self, reg: &str, operand: &Operand, function: &Function) {
+        match operand {
+            Operand::Const(value) => self.mov_imm(reg, *value),
+            Operand::Temp(temp) => {
+                let address = self.frame_address(temp_offset(function, *temp));
+                self.inst(&format!("ldr {}, {}", reg, address));
+            },
+        }
+    }
+
+    fn store_temp(&mut self, reg: &str, temp: Temp, function: &Function) {
+        let address = self.frame_address(temp_offset(function, temp));
+        self.inst(&format!("str {}, {}", reg, address));
+    }
+
+    /// `mov` takes 16 bits, bigger constants are built 16 bits at a time
+    fn mov_imm(&mut self, reg: &str, value: i64) {
+        if (-65536..65536).contains(&value) {
+            self.inst(&format!("mov {}, #{}", reg, value));
+            return;
+        }
+
+        let mut first = true;
+        for shift in [0, 16, 32, 48] {
+            let chunk = (value as u64 >> shift) & 0xffff;
+
+            if chunk != 0 {
+                let op = if first { "movz" } else { "movk" };
+                self.inst(&format!("{} {}, #{}, lsl #{}", op, reg, chunk, shift));
+                first = false;
+            }
+        }
+    }
+
+    /// Adds to or subtracts from `sp`, an immediate only has 12 bits
+    fn adjust_sp(&mut self, op: &str, amount: i64) {
+        if amount == 0 {
+            return;
+        }
+
+        if amount < 4096 {
+            self.inst(&format!("{} sp, sp, #{}", op, amount));
+        } else {
+            self.mov_imm("x11", amount);
+            self.inst(&format!("{} sp, sp, x11", op));
+        }
+    }
+
+    /// The address of a slot in the frame. Loads and stores only reach so far from a
+    /// register, further slots have their address worked out in `x11`
+    fn frame_address(&mut self, offset: i64) -> String {
+        if (-256..256).contains(&offset) || (0..=32760).contains(&offset) {
+            return format!("[x29, #{}]", offset);
+        }
+
+        self.mov_imm("x11", offset);
+        self.inst("add x11, x29, x11");
+        return "[x11]".to_string();
+    }
+
+    fn var_address(&mut self, module: &Module, function: &Function, var: Var) -> String {
+        match var {
+            Var::Global(index) => {
+                let label = format!("global_{}", module.globals[index]);
+                self.inst(&format!("adrp x11, {}", label));
+                self.inst(&format!("add x11, x11, :lo12:{}", label));
+                return "[x11]".to_string();
+            },
+            Var::Local(index) => self.frame_address(local_offset(function, index)),
+        }
+    }
+
+    fn inst(&mut self, inst: &str) {
+        self.asm.push(format!("    {}", inst));
+    }
+
+    fn label(&mut self, label: &str) {
+        self.asm.push(format!("{}:", label));
+    }
+
+    fn comment(&mut self, text: &str) {
+        self.asm.push(format!("    // {}", text));
+    }
+}
+
+/// Parameters are above the saved registers, the first one lowest, other locals below
+fn local_offset(function: &Function, index: usize) -> i64 {
+    if index < function.params {
+        return 16 + index as i64 * 8;
+    }
+
+    return -8 * (index - function.params + 1) as i64;
+}
+
+/// Temps are below the locals
+fn temp_offset(function: &Function, temp: Temp) -> i64 {
+    return -8 * (function.locals.len() - function.params + temp.0 + 1) as i64;
+}
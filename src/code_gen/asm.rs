@@ -0,0 +1,257 @@
+use std::fmt;
+
+/// The 64 bit general purpose registers
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq, Hash)]
+pub enum Reg {
+    Rax,
+    Rcx,
+    Rdx,
+    Rbx,
+    Rsp,
+    Rbp,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+    R10,
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+}
+
+/// A memory operand, always 64 bits wide
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum Mem {
+    /// `[base + offset]`
+    Base(Reg, i32),
+    /// A label in the data or bss section, addressed relative to rip
+    Label(String),
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum Operand {
+    Reg(Reg),
+    Mem(Mem),
+    /// Only `mov` to a register can take a full 64 bit immediate, everything else takes
+    /// a sign extended 32 bit one
+    Imm(i64),
+}
+
+/// A single line of assembly
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum Inst {
+    Global(String),
+    /// A label defined outside the program, an extern function
+    Extern(String),
+    Section(String),
+    Label(String),
+    Comment(String),
+    /// Reserves a number of zeroed qwords in the bss section
+    Resq(String, usize),
+    /// A qword with a value in the data section
+    Dq(String, i64),
+
+    Mov(Operand, Operand),
+    Push(Operand),
+    Pop(Operand),
+    Add(Operand, Operand),
+    Sub(Operand, Operand),
+    And(Operand, Operand),
+    Imul(Reg, Operand),
+    /// `imul dest, source, imm`
+    ImulImm(Reg, Operand, i32),
+    Idiv(Operand),
+    Neg(Operand),
+    /// Sign extends rax into rdx
+    Cqo,
+    Call(String),
+    /// Calls the address in a register or memory
+    CallIndirect(Operand),
+    Jmp(String),
+    Ret,
+    Syscall,
+}
+
+impl Operand {
+    pub fn is_mem(&self) -> bool {
+        matches!(self, Operand::Mem(_))
+    }
+
+    /// Whether the operand reads or is the register
+    pub fn uses(&self, reg: Reg) -> bool {
+        match self {
+            Operand::Reg(used) | Operand::Mem(Mem::Base(used, _)) => *used == reg,
+            _ => false,
+        }
+    }
+}
+
+impl Inst {
+    /// Whether the line is an instruction, rather than a label, comment or directive
+    pub fn is_instruction(&self) -> bool {
+        !matches!(self, Inst::Global(_) | Inst::Extern(_) | Inst::Section(_) | Inst::Label(_) | Inst::Comment(_) | Inst::Resq(..) | Inst::Dq(..))
+    }
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Reg::Rax => "rax",
+            Reg::Rcx => "rcx",
+            Reg::Rdx => "rdx",
+            Reg::Rbx => "rbx",
+            Reg::Rsp => "rsp",
+            Reg::Rbp => "rbp",
+            Reg::Rsi => "rsi",
+            Reg::Rdi => "rdi",
+            Reg::R8 => "r8",
+            Reg::R9 => "r9",
+            Reg::R10 => "r10",
+            Reg::R11 => "r11",
+            Reg::R12 => "r12",
+            Reg::R13 => "r13",
+            Reg::R14 => "r14",
+            Reg::R15 => "r15",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Mem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mem::Base(base, 0) => write!(f, "QWORD [{}]", base),
+            Mem::Base(base, offset) if *offset < 0 => write!(f, "QWORD [{} - {}]", base, -(*offset as i64)),
+            Mem::Base(base, offset) => write!(f, "QWORD [{} + {}]", base, offset),
+            Mem::Label(label) => write!(f, "QWORD [rel {}]", label),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Reg(reg) => write!(f, "{}", reg),
+            Operand::Mem(mem) => write!(f, "{}", mem),
+            Operand::Imm(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// The assemblers the listing can be written for, both with Intel operand order
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Syntax {
+    Nasm,
+    /// The GNU assembler, with `.intel_syntax noprefix`
+    Gas,
+}
+
+impl Mem {
+    /// The same address as the GNU assembler writes it
+    fn gas(&self) -> String {
+        match self {
+            Mem::Base(base, 0) => format!("QWORD PTR [{}]", base),
+            Mem::Base(base, offset) if *offset < 0 => format!("QWORD PTR [{} - {}]", base, -(*offset as i64)),
+            Mem::Base(base, offset) => format!("QWORD PTR [{} + {}]", base, offset),
+            Mem::Label(label) => format!("QWORD PTR [rip + {}]", label),
+        }
+    }
+}
+
+impl Inst {
+    /// The line of the listing for the instruction or directive
+    pub fn format(&self, syntax: Syntax) -> String {
+        let op = |operand: &Operand| match (syntax, operand) {
+            (Syntax::Gas, Operand::Mem(mem)) => mem.gas(),
+            _ => operand.to_string(),
+        };
+
+        match (syntax, self) {
+            // the `$` stops nasm reading an exported function called `add` as the instruction
+            (Syntax::Nasm, Inst::Global(label)) => format!("global ${}", label),
+            (Syntax::Nasm, Inst::Extern(label)) => format!("extern ${}", label),
+            (Syntax::Nasm, Inst::Section(name)) => format!("section {}", name),
+            (Syntax::Nasm, Inst::Label(label)) => format!("${}:", label),
+            (Syntax::Nasm, Inst::Comment(text)) => format!("    ; {}", text),
+            (Syntax::Nasm, Inst::Resq(label, count)) => format!("{}: resq {}", label, count),
+            (Syntax::Nasm, Inst::Dq(label, value)) => format!("{}: dq {}", label, value),
+
+            (Syntax::Gas, Inst::Global(label)) => format!(".globl {}", label),
+            (Syntax::Gas, Inst::Extern(label)) => format!(".extern {}", label),
+            (Syntax::Gas, Inst::Section(name)) => format!(".section {}", name),
+            (Syntax::Gas, Inst::Label(label)) => format!("{}:", label),
+            (Syntax::Gas, Inst::Comment(text)) => format!("    # {}", text),
+            (Syntax::Gas, Inst::Resq(label, count)) => format!("{}: .zero {}", label, count * 8),
+            (Syntax::Gas, Inst::Dq(label, value)) => format!("{}: .quad {}", label, value),
+
+            (_, Inst::Mov(dest, source)) => format!("    mov {}, {}", op(dest), op(source)),
+            (_, Inst::Push(source)) => format!("    push {}", op(source)),
+            (_, Inst::Pop(dest)) => format!("    pop {}", op(dest)),
+            (_, Inst::Add(dest, source)) => format!("    add {}, {}", op(dest), op(source)),
+            (_, Inst::Sub(dest, source)) => format!("    sub {}, {}", op(dest), op(source)),
+            (_, Inst::And(dest, source)) => format!("    and {}, {}", op(dest), op(source)),
+            (_, Inst::Imul(dest, source)) => format!("    imul {}, {}", dest, op(source)),
+            (_, Inst::ImulImm(dest, source, value)) => format!("    imul {}, {}, {}", dest, op(source), value),
+            (_, Inst::Idiv(source)) => format!("    idiv {}", op(source)),
+            (_, Inst::Neg(dest)) => format!("    neg {}", op(dest)),
+            (_, Inst::Cqo) => "    cqo".to_string(),
+            (_, Inst::Call(label)) => format!("    call {}", label),
+            (_, Inst::CallIndirect(target)) => format!("    call {}", op(target)),
+            (_, Inst::Jmp(label)) => format!("    jmp {}", label),
+            (_, Inst::Ret) => "    ret".to_string(),
+            (_, Inst::Syscall) => "    syscall".to_string(),
+        }
+    }
+}
+
+/// Formats the instruction as nasm
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format(Syntax::Nasm))
+    }
+}
+
+/// Formats a whole listing, one line each. nasm calls extern functions through the
+/// procedure linkage table only when told to, without it `cc` can't link them into a
+/// position independent executable. `as` always does
+pub fn render(insts: &[Inst], syntax: Syntax) -> String {
+    let mut output = String::new();
+
+    if syntax == Syntax::Gas {
+        output.push_str(".intel_syntax noprefix\n");
+    }
+
+    let externs: Vec<&String> = insts.iter()
+        .filter_map(|inst| match inst {
+            Inst::Extern(label) => Some(label),
+            _ => None,
+        })
+        .collect();
+
+    for inst in insts {
+        match (syntax, inst) {
+            (Syntax::Nasm, Inst::Call(label)) if externs.contains(&label) => output.push_str(&format!("    call {} wrt ..plt", label)),
+            _ => output.push_str(&inst.format(syntax)),
+        }
+        output.push('\n');
+    }
+
+    // without it the linker warns that the stack is executable
+    if syntax == Syntax::Gas {
+        output.push_str(".section .note.GNU-stack,\"\",@progbits\n");
+    }
+
+    return output;
+}
@@ -0,0 +1,373 @@
+use std::collections::{BTreeSet, HashSet};
+
+use crate::parser::{*, math::OperationType};
+
+/// The helpers a file can start with, only the ones it uses are written. Atomic integers
+/// wrap around where C's would be undefined. Dividing by zero or the smallest value by -1
+/// is undefined in C as well, it raises `SIGFPE` instead, the way `idiv` traps in the
+/// native code
+const HELPERS: &[(&str, &str)] = &[
+    ("atomic_add", "static inline int64_t atomic_add(int64_t a, int64_t b) { return (int64_t)((uint64_t)a + (uint64_t)b); }\n"),
+    ("atomic_sub", "static inline int64_t atomic_sub(int64_t a, int64_t b) { return (int64_t)((uint64_t)a - (uint64_t)b); }\n"),
+    ("atomic_mul", "static inline int64_t atomic_mul(int64_t a, int64_t b) { return (int64_t)((uint64_t)a * (uint64_t)b); }\n"),
+    ("atomic_neg", "static inline int64_t atomic_neg(int64_t a) { return (int64_t)(0 - (uint64_t)a); }\n"),
+    ("atomic_divide", "\
+static inline int64_t atomic_divide(int64_t a, int64_t b) {
+    if (b == 0 || (a == INT64_MIN && b == -1)) {
+        raise(SIGFPE);
+    }
+    return a / b;
+}
+"),
+];
+
+/// The C library headers that can be included, and the functions they declare. An extern
+/// function named after one of them is called through the header's declaration, since a
+/// prototype of our own would conflict with it
+const HEADERS: &[(&str, &[&str])] = &[
+    ("ctype.h", &[
+        "isalnum", "isalpha", "isblank", "iscntrl", "isdigit", "isgraph", "islower", "isprint", "ispunct",
+        "isspace", "isupper", "isxdigit", "tolower", "toupper",
+    ]),
+    ("signal.h", &["raise", "signal"]),
+    ("stdio.h", &[
+        "clearerr", "fclose", "feof", "ferror", "fflush", "fgetc", "fgetpos", "fgets", "fopen", "fprintf",
+        "fputc", "fputs", "fread", "freopen", "fscanf", "fseek", "fsetpos", "ftell", "fwrite", "getc",
+        "getchar", "gets", "perror", "printf", "putc", "putchar", "puts", "remove", "rename", "rewind",
+        "scanf", "setbuf", "setvbuf", "snprintf", "sprintf", "sscanf", "tmpfile", "tmpnam", "ungetc",
+        "vfprintf", "vfscanf", "vprintf", "vscanf", "vsnprintf", "vsprintf", "vsscanf",
+    ]),
+    ("stdlib.h", &[
+        "_Exit", "abort", "abs", "atexit", "atof", "atoi", "atol", "atoll", "bsearch", "calloc", "div",
+        "exit", "free", "getenv", "labs", "ldiv", "llabs", "lldiv", "malloc", "mblen", "mbstowcs", "mbtowc",
+        "qsort", "rand", "realloc", "srand", "strtod", "strtof", "strtol", "strtold", "strtoll", "strtoul",
+        "strtoull", "system", "wcstombs", "wctomb",
+    ]),
+];
+
+/// The header that declares the C library function
+fn header_of(name: &str) -> Option<&'static str> {
+    return HEADERS.iter()
+        .find(|(_, functions)| functions.contains(&name))
+        .map(|(header, _)| *header);
+}
+
+/// Translates the ast into C99, as a way to run programs anywhere with a C compiler and
+/// to check the native code against. Only the headers and helpers the program needs are
+/// included, so the names it can use for externs stay free.
+///
+/// Every function, nested or not, becomes a C function named `fn_` and its name, and the
+/// top level code becomes `main`. Top level variables are the globals `global_`, the
+/// variables of functions are `local_`. C doesn't say which order the operands of an
+/// expression are evaluated in, so calls are moved into temps before the expression,
+/// along with any value read before them. Extern functions keep their own names. The
+/// program must already have been checked by lowering it into the ir
+pub struct CGen {
+    globals: Vec<String>,
+    /// The names of the extern functions, calls to them aren't prefixed
+    externs: HashSet<String>,
+    headers: BTreeSet<&'static str>,
+    /// The helpers that have been used
+    helpers: HashSet<&'static str>,
+    prototypes: Vec<String>,
+    functions: Vec<String>,
+
+    /// The function that is being translated
+    current: CFunction,
+}
+
+#[derive(Default)]
+struct CFunction {
+    lines: Vec<String>,
+    /// The variables declared so far, anything else is a global
+    locals: HashSet<String>,
+    temps: usize,
+}
+
+impl CGen {
+    pub fn new() -> CGen {
+        CGen {
+            globals: vec!(),
+            externs: HashSet::new(),
+            headers: BTreeSet::from(["stdint.h"]),
+            helpers: HashSet::new(),
+            prototypes: vec!(),
+            functions: vec!(),
+            current: CFunction::default(),
+        }
+    }
+
+    /// The whole C file
+    pub fn generate(&mut self, program: &NodeProgram) -> String {
+        for extern_stmt in program.externs() {
+            let name = &extern_stmt.identifier.info;
+            let returns = if extern_stmt.returns { "int64_t" } else { "void" };
+            let params = if extern_stmt.args.is_empty() { "void".to_string() } else { vec!("int64_t"; extern_stmt.args.len()).join(", ") };
+
+            self.externs.insert(name.clone());
+            match header_of(name) {
+                Some(header) => {
+                    self.headers.insert(header);
+                },
+                None => self.prototypes.push(format!("{} {}({})", returns, name, params)),
+            }
+        }
+
+        self.gen_scope(program, true);
+        self.current.lines.push("return 0;".to_string());
+
+        let main = std::mem::take(&mut self.current);
+        self.functions.push(format!("int main(void) {{\n{}}}\n", indent(&main.lines)));
+
+        let mut output = String::new();
+        for header in &self.headers {
+            output.push_str(&format!("#include <{}>\n", header));
+        }
+
+        if !self.helpers.is_empty() {
+            output.push('\n');
+        }
+        for (name, helper) in HELPERS {
+            if self.helpers.contains(name) {
+                output.push_str(helper);
+            }
+        }
+
+        if !self.globals.is_empty() {
+            output.push('\n');
+        }
+        for global in &self.globals {
+            output.push_str(&format!("static int64_t {};\n", global));
+        }
+
+        if !self.prototypes.is_empty() {
+            output.push('\n');
+        }
+        for prototype in &self.prototypes {
+            output.push_str(&format!("{};\n", prototype));
+        }
+
+        for function in &self.functions {
+            output.push('\n');
+            output.push_str(function);
+        }
+
+        return output;
+    }
+
+    fn gen_scope(&mut self, program: &NodeProgram, top_level: bool) {
+        for stmt in &program.statements {
+            match stmt {
+                NodeStatements::Declare(declare_stmt) => {
+                    // the value can't refer to the variable it declares
+                    let value = match &declare_stmt.expression {
+                        Some(expression) => self.gen_expression(expression),
+                        None => "0".to_string(),
+                    };
+
+                    let name = &declare_stmt.identifier.info;
+                    if top_level {
+                        self.globals.push(global(name));
+                        self.line(format!("{} = {};", global(name), value));
+                    } else {
+                        self.current.locals.insert(name.clone());
+                        self.line(format!("int64_t {} = {};", local(name), value));
+                    }
+                },
+                NodeStatements::Set(set_stmt) => {
+                    let value = self.gen_expression(&set_stmt.expression);
+                    let name = self.variable(&set_stmt.identifier.info);
+                    self.line(format!("{} = {};", name, value));
+                },
+                NodeStatements::Exit(exit_stmt) => {
+                    let value = self.gen_expression(&exit_stmt.expression);
+                    self.headers.insert("stdlib.h");
+                    self.line(format!("exit((int){});", value));
+                },
+                NodeStatements::PutChar(putchar_stmt) => {
+                    let value = self.gen_expression(&putchar_stmt.expression);
+                    self.headers.insert("stdio.h");
+                    self.line(format!("putchar((unsigned char){});", value));
+                },
+                NodeStatements::Function(func_stmt) => self.gen_function(func_stmt),
+                // the prototypes were all made before anything else
+                NodeStatements::Extern(_) => (),
+                NodeStatements::FunctionCall(func_call_stmt) => {
+                    let call = self.gen_call(func_call_stmt);
+                    self.line(format!("{};", call));
+                },
+                NodeStatements::Return(return_stmt) => {
+                    let value = match &return_stmt.expression {
+                        Some(expression) => self.gen_expression(expression),
+                        None => "0".to_string(),
+                    };
+                    self.line(format!("return {};", value));
+                },
+            }
+        }
+    }
+
+    /// Adds the function to the file, its body is translated on its own so nested
+    /// functions don't end up inside the enclosing one
+    fn gen_function(&mut self, func_stmt: &NodeStmtFunction) {
+        let outer = std::mem::take(&mut self.current);
+
+        let params: Vec<String> = func_stmt.args.iter()
+            .map(|arg| format!("int64_t {}", local(&arg.identifier.info)))
+            .collect();
+        for arg in &func_stmt.args {
+            self.current.locals.insert(arg.identifier.info.clone());
+        }
+
+        self.gen_scope(&func_stmt.scope, false);
+
+        // falling off the end returns 0
+        self.current.lines.push("return 0;".to_string());
+
+        let params = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+        let signature = format!("static int64_t {}({})", function(&func_stmt.identifier.info), params);
+
+        let body = std::mem::replace(&mut self.current, outer);
+        self.functions.push(format!("{} {{\n{}}}\n", signature, indent(&body.lines)));
+        self.prototypes.push(signature);
+    }
+
+    /// A C expression without side effects, the calls in it are done before it
+    fn gen_expression(&mut self, expr: &MathValue) -> String {
+        match expr {
+            MathValue::Integer(integer) => literal(integer.value.expect("Integer literal without a value")),
+            MathValue::Identifier(ident) => self.variable(&ident.info),
+
+            MathValue::Operation(oper) => {
+                let operands = self.gen_operands(&oper.operands());
+
+                let helper = match oper.as_ref() {
+                    OperationType::Add(_) => "atomic_add",
+                    OperationType::Sub(_) => "atomic_sub",
+                    OperationType::Mult(_) => "atomic_mul",
+                    OperationType::Div(_) => {
+                        self.headers.insert("signal.h");
+                        "atomic_divide"
+                    },
+                    OperationType::Negate(_) => "atomic_neg",
+                };
+                self.helpers.insert(helper);
+
+                format!("{}({})", helper, operands.join(", "))
+            },
+
+            MathValue::Call(call) => {
+                let call = self.gen_call(call);
+                let temp = format!("t{}", self.current.temps);
+                self.current.temps += 1;
+
+                self.line(format!("int64_t {} = {};", temp, call));
+                temp
+            },
+        }
+    }
+
+    /// The call itself, after the calls in its arguments
+    fn gen_call(&mut self, call: &NodeStmtFunctionCall) -> String {
+        let args = self.gen_operands(&call.args.iter().collect::<Vec<_>>());
+
+        let name = &call.identifier.info;
+        if self.externs.contains(name) {
+            return format!("{}({})", name, args.join(", "));
+        }
+
+        return format!("{}({})", function(name), args.join(", "));
+    }
+
+    /// Translates the operands from left to right. An operand that reads a variable is
+    /// kept in a temp when a later one makes a call, since the call could change it
+    fn gen_operands(&mut self, operands: &[&MathValue]) -> Vec<String> {
+        let mut output: Vec<String> = vec!();
+        // where each operand's calls start
+        let mut starts: Vec<usize> = vec!();
+
+        for operand in operands {
+            starts.push(self.current.lines.len());
+            output.push(self.gen_expression(operand));
+        }
+
+        // backwards, so inserting a line doesn't move the places still to come
+        let mut calls_after = false;
+        for i in (0..operands.len()).rev() {
+            if calls_after && reads_variables(operands[i]) {
+                let temp = format!("t{}", self.current.temps);
+                self.current.temps += 1;
+
+                let end = starts.get(i + 1).copied().unwrap_or(self.current.lines.len());
+                self.current.lines.insert(end, format!("int64_t {} = {};", temp, output[i]));
+                output[i] = temp;
+            }
+
+            calls_after |= !operands[i].calls().is_empty();
+        }
+
+        return output;
+    }
+
+    /// A variable is a local if it has been declared in this function, otherwise it is a
+    /// global
+    fn variable(&self, name: &str) -> String {
+        if self.current.locals.contains(name) {
+            return local(name);
+        }
+
+        return global(name);
+    }
+
+    fn line(&mut self, line: String) {
+        self.current.lines.push(line);
+    }
+}
+
+/// Whether the value of the expression depends on variables, the calls in it are
+/// already done by the time it is evaluated
+fn reads_variables(expr: &MathValue) -> bool {
+    match expr {
+        MathValue::Integer(_) | MathValue::Call(_) => false,
+        MathValue::Identifier(_) => true,
+        MathValue::Operation(oper) => oper.operands().into_iter().any(reads_variables),
+    }
+}
+
+fn literal(value: i64) -> String {
+    if value == i64::MIN {
+        return "INT64_MIN".to_string();
+    }
+
+    if i32::try_from(value).is_ok() {
+        return value.to_string();
+    }
+
+    return format!("INT64_C({})", value);
+}
+
+fn indent(lines: &[String]) -> String {
+    lines.iter().map(|line| format!("    {}\n", line)).collect()
+}
+
+fn function(name: &str) -> String {
+    format!("fn_{}", identifier(name))
+}
+
+fn global(name: &str) -> String {
+    format!("global_{}", identifier(name))
+}
+
+fn local(name: &str) -> String {
+    format!("local_{}", identifier(name))
+}
+
+/// C identifiers can't contain `-`. Names with one get an extra `_` in front, which names
+/// without one never start with, and `_` is doubled so `a-_b` and `a_-b` stay different
+fn identifier(name: &str) -> String {
+    if !name.contains('-') {
+        return name.to_string();
+    }
+
+    return format!("_{}", name.replace('_', "__").replace('-', "_h"));
+}
@@ -0,0 +1,269 @@
+use super::encode::{Object, Section};
+
+/// Where the executable is loaded
+const BASE_ADDRESS: u64 = 0x400000;
+const PAGE_SIZE: u64 = 0x1000;
+
+const ELF_HEADER_SIZE: u64 = 64;
+const PROGRAM_HEADER_SIZE: u64 = 56;
+const SECTION_HEADER_SIZE: usize = 64;
+const SYMBOL_SIZE: usize = 24;
+const RELOCATION_SIZE: usize = 24;
+
+/// The sections of an object file, the section names come last
+const TEXT_INDEX: u16 = 1;
+const DATA_INDEX: u16 = 2;
+const BSS_INDEX: u16 = 3;
+const SYMBOLS_INDEX: u32 = 4;
+const STRINGS_INDEX: u32 = 5;
+
+/// Links an object into a static ELF64 executable that starts at `_start`.
+///
+/// The headers and the text share one read and execute segment. The data and the bss
+/// share a read and write segment on the next page, the data is read from the file
+/// after the text and the kernel fills the bss after it with zeros
+pub fn executable(object: &Object) -> Result<Vec<u8>, String> {
+    let writable_size = (object.data.len() + object.bss_size) as u64;
+    let segments = if writable_size > 0 { 2 } else { 1 };
+    let headers_size = ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE * segments;
+
+    let text_address = BASE_ADDRESS + headers_size;
+    let text_end = text_address + object.text.len() as u64;
+
+    // a segment has to be at the same offset within a page in the file and in memory
+    let data_offset = (headers_size + object.text.len() as u64).next_multiple_of(8);
+    let data_address = text_end.div_ceil(PAGE_SIZE) * PAGE_SIZE + data_offset % PAGE_SIZE;
+    let bss_address = data_address + object.data.len() as u64;
+
+    let address = |label: &str| -> Result<u64, String> {
+        match object.labels.get(label) {
+            Some((Section::Text, offset)) => Ok(text_address + *offset as u64),
+            Some((Section::Data, offset)) => Ok(data_address + *offset as u64),
+            Some((Section::Bss, offset)) => Ok(bss_address + *offset as u64),
+            None => Err(format!("Undefined label {}", label)),
+        }
+    };
+
+    let mut text = object.text.clone();
+    for fixup in &object.fixups {
+        let place = text_address + fixup.offset as u64;
+        let distance = address(&fixup.label)? as i64 + fixup.addend - place as i64;
+
+        let Ok(distance) = i32::try_from(distance) else {
+            return Err(format!("{} is too far away to reach", fixup.label));
+        };
+
+        text[fixup.offset..fixup.offset + 4].copy_from_slice(&distance.to_le_bytes());
+    }
+
+    let entry = address("_start").map_err(|_| "There is no _start label to begin at".to_string())?;
+
+    let mut output = vec!();
+    elf_header(&mut output, ElfType::Executable, entry, segments as u16, 0, 0);
+
+    // flags are read 4, write 2, execute 1
+    program_header(&mut output, 0, BASE_ADDRESS, headers_size + text.len() as u64, headers_size + text.len() as u64, 4 | 1);
+    if writable_size > 0 {
+        program_header(&mut output, data_offset, data_address, object.data.len() as u64, writable_size, 4 | 2);
+    }
+
+    output.extend_from_slice(&text);
+    output.resize(data_offset as usize, 0);
+    output.extend_from_slice(&object.data);
+
+    return Ok(output);
+}
+
+/// Makes a relocatable object file for `ld` or `cc`, with a symbol for every label.
+///
+/// Jumps and calls within the text are filled in here, references to the data and bss and
+/// calls to extern functions are left as relocations for the linker
+pub fn relocatable(object: &Object) -> Result<Vec<u8>, String> {
+    for global in &object.globals {
+        if !object.labels.contains_key(global) {
+            return Err(format!("Undefined label {}", global));
+        }
+    }
+
+    // the local symbols have to come before the global ones
+    let mut labels: Vec<(&String, &(Section, usize))> = object.labels.iter().collect();
+    labels.sort_by_key(|(label, (section, offset))| (object.globals.contains(label), *section as u8, *offset, label.to_string()));
+
+    let first_global = 1 + labels.iter().filter(|(label, _)| !object.globals.contains(label)).count();
+
+    let mut strings: Vec<u8> = vec!(0);
+    let mut symbols: Vec<u8> = vec!(0; SYMBOL_SIZE);
+    for (label, (section, offset)) in &labels {
+        push_u32(&mut symbols, strings.len() as u32);
+        strings.extend_from_slice(label.as_bytes());
+        strings.push(0);
+
+        let binding = if object.globals.contains(label) { 1 } else { 0 };
+        let symbol_type = match section {
+            _ if binding == 0 => 0,
+            Section::Text => 2,
+            Section::Data | Section::Bss => 1,
+        };
+        symbols.push(binding << 4 | symbol_type);
+        symbols.push(0);
+        push_u16(&mut symbols, match section { Section::Text => TEXT_INDEX, Section::Data => DATA_INDEX, Section::Bss => BSS_INDEX });
+        push_u64(&mut symbols, *offset as u64);
+        push_u64(&mut symbols, 0);
+    }
+
+    // the extern labels are global symbols without a section, the linker finds them
+    for label in &object.externs {
+        push_u32(&mut symbols, strings.len() as u32);
+        strings.extend_from_slice(label.as_bytes());
+        strings.push(0);
+
+        symbols.push(1 << 4);
+        symbols.push(0);
+        push_u16(&mut symbols, 0);
+        push_u64(&mut symbols, 0);
+        push_u64(&mut symbols, 0);
+    }
+
+    let mut text = object.text.clone();
+    let mut relocations: Vec<u8> = vec!();
+    for fixup in &object.fixups {
+        match object.labels.get(&fixup.label) {
+            Some((Section::Text, offset)) => {
+                let distance = *offset as i64 + fixup.addend - fixup.offset as i64;
+                text[fixup.offset..fixup.offset + 4].copy_from_slice(&(distance as i32).to_le_bytes());
+            },
+            Some((Section::Data | Section::Bss, _)) => {
+                let symbol = 1 + labels.iter().position(|(label, _)| **label == fixup.label).unwrap();
+
+                // R_X86_64_PC32, the same calculation as the one the cpu does
+                push_u64(&mut relocations, fixup.offset as u64);
+                push_u64(&mut relocations, (symbol as u64) << 32 | 2);
+                push_u64(&mut relocations, fixup.addend as u64);
+            },
+            None => {
+                let Some(position) = object.externs.iter().position(|label| *label == fixup.label) else {
+                    return Err(format!("Undefined label {}", fixup.label));
+                };
+                let symbol = 1 + labels.len() + position;
+
+                // R_X86_64_PLT32, the call goes through the procedure linkage table if
+                // the function is in a shared library
+                push_u64(&mut relocations, fixup.offset as u64);
+                push_u64(&mut relocations, (symbol as u64) << 32 | 4);
+                push_u64(&mut relocations, fixup.addend as u64);
+            },
+        }
+    }
+
+    let mut names: Vec<u8> = vec!(0);
+    let mut name = |section: &str| {
+        let offset = names.len() as u32;
+        names.extend_from_slice(section.as_bytes());
+        names.push(0);
+        return offset;
+    };
+    let section_names = [name(".text"), name(".data"), name(".bss"), name(".symtab"), name(".strtab"), name(".rela.text"), name(".note.GNU-stack"), name(".shstrtab")];
+
+    // the contents of the sections follow the header, each aligned to 8
+    let mut contents: Vec<u8> = vec!();
+    let mut place = |data: &[u8]| {
+        let offset = ELF_HEADER_SIZE + contents.len() as u64;
+        contents.extend_from_slice(data);
+        contents.resize(contents.len().next_multiple_of(8), 0);
+        return offset;
+    };
+    let text_offset = place(&text);
+    let data_offset = place(&object.data);
+    let symbols_offset = place(&symbols);
+    let strings_offset = place(&strings);
+    let relocations_offset = place(&relocations);
+    let names_offset = place(&names);
+    let section_offset = ELF_HEADER_SIZE + contents.len() as u64;
+
+    let mut output = vec!();
+    elf_header(&mut output, ElfType::Relocatable, 0, 0, section_offset, 9);
+    output.extend_from_slice(&contents);
+
+    output.extend_from_slice(&[0; SECTION_HEADER_SIZE]);
+    // flags are write 1, alloc 2, execute 4, and 0x40 for the relocations naming the section they apply to
+    section_header(&mut output, section_names[0], 1, 2 | 4, text_offset, text.len(), 0, 0, 16, 0);
+    section_header(&mut output, section_names[1], 1, 1 | 2, data_offset, object.data.len(), 0, 0, 8, 0);
+    section_header(&mut output, section_names[2], 8, 1 | 2, text_offset, object.bss_size, 0, 0, 8, 0);
+    section_header(&mut output, section_names[3], 2, 0, symbols_offset, symbols.len(), STRINGS_INDEX, first_global as u32, 8, SYMBOL_SIZE);
+    section_header(&mut output, section_names[4], 3, 0, strings_offset, strings.len(), 0, 0, 1, 0);
+    section_header(&mut output, section_names[5], 4, 0x40, relocations_offset, relocations.len(), SYMBOLS_INDEX, TEXT_INDEX as u32, 8, RELOCATION_SIZE);
+    // tells the linker the stack doesn't have to be executable
+    section_header(&mut output, section_names[6], 1, 0, names_offset, 0, 0, 0, 1, 0);
+    section_header(&mut output, section_names[7], 3, 0, names_offset, names.len(), 0, 0, 1, 0);
+
+    return Ok(output);
+}
+
+#[derive(Clone, Copy)]
+enum ElfType {
+    Relocatable = 1,
+    Executable = 2,
+}
+
+/// The file header, the program headers follow it and the section headers are at
+/// `section_offset`
+fn elf_header(output: &mut Vec<u8>, elf_type: ElfType, entry: u64, segments: u16, section_offset: u64, sections: u16) {
+    // the magic number, 64 bit, little endian, version 1, the System V abi
+    output.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    output.extend_from_slice(&[0; 8]);
+
+    push_u16(output, elf_type as u16);
+    // x86-64
+    push_u16(output, 0x3e);
+    push_u32(output, 1);
+    push_u64(output, entry);
+    push_u64(output, if segments > 0 { ELF_HEADER_SIZE } else { 0 });
+    push_u64(output, section_offset);
+    push_u32(output, 0);
+    push_u16(output, ELF_HEADER_SIZE as u16);
+    push_u16(output, if segments > 0 { PROGRAM_HEADER_SIZE as u16 } else { 0 });
+    push_u16(output, segments);
+    push_u16(output, if sections > 0 { SECTION_HEADER_SIZE as u16 } else { 0 });
+    push_u16(output, sections);
+    // the section names are always in the last section
+    push_u16(output, sections.saturating_sub(1));
+}
+
+/// A loadable segment
+fn program_header(output: &mut Vec<u8>, offset: u64, address: u64, file_size: u64, memory_size: u64, flags: u32) {
+    push_u32(output, 1);
+    push_u32(output, flags);
+    push_u64(output, offset);
+    push_u64(output, address);
+    push_u64(output, address);
+    push_u64(output, file_size);
+    push_u64(output, memory_size);
+    push_u64(output, PAGE_SIZE);
+}
+
+/// Describes one section of an object file
+#[allow(clippy::too_many_arguments)]
+fn section_header(output: &mut Vec<u8>, name: u32, section_type: u32, flags: u64, offset: u64, size: usize, link: u32, info: u32, align: u64, entry_size: usize) {
+    push_u32(output, name);
+    push_u32(output, section_type);
+    push_u64(output, flags);
+    push_u64(output, 0);
+    push_u64(output, offset);
+    push_u64(output, size as u64);
+    push_u32(output, link);
+    push_u32(output, info);
+    push_u64(output, align);
+    push_u64(output, entry_size as u64);
+}
+
+fn push_u16(output: &mut Vec<u8>, value: u16) {
+    output.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(output: &mut Vec<u8>, value: u32) {
+    output.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u64(output: &mut Vec<u8>, value: u64) {
+    output.extend_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+
+use super::asm::{Inst, Mem, Operand, Reg};
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Section {
+    Text,
+    Data,
+    Bss,
+}
+
+/// A 32 bit field in the text that holds the distance to a label, measured the way the
+/// cpu does, from the address in the field plus `addend`
+#[derive(Debug)]
+pub struct Fixup {
+    pub offset: usize,
+    pub label: String,
+    pub addend: i64,
+}
+
+/// Machine code before it is placed at an address
+#[derive(Debug)]
+pub struct Object {
+    pub text: Vec<u8>,
+    pub data: Vec<u8>,
+    pub bss_size: usize,
+    /// Every label, the section it is in and its offset in that section
+    pub labels: HashMap<String, (Section, usize)>,
+    /// The labels declared with `global`
+    pub globals: Vec<String>,
+    /// The labels declared with `extern`, the linker finds them
+    pub externs: Vec<String>,
+    pub fixups: Vec<Fixup>,
+}
+
+/// Encodes the instructions the code generator makes into x86-64 machine code. Labels are
+/// left as fixups, so the same object can become an executable or an object file. Fails
+/// on operands the instruction doesn't have a form for, like two memory operands
+pub fn encode(insts: &[Inst]) -> Result<Object, String> {
+    let mut encoder = Encoder { bytes: vec!(), fixups: vec!() };
+
+    let mut object = Object {
+        text: vec!(),
+        data: vec!(),
+        bss_size: 0,
+        labels: HashMap::new(),
+        globals: vec!(),
+        externs: vec!(),
+        fixups: vec!(),
+    };
+    let mut section = Section::Text;
+
+    for inst in insts {
+        match inst {
+            Inst::Global(label) => object.globals.push(label.clone()),
+            Inst::Extern(label) => object.externs.push(label.clone()),
+            Inst::Section(name) => {
+                section = match name.as_str() {
+                    ".data" => Section::Data,
+                    ".bss" => Section::Bss,
+                    _ => Section::Text,
+                };
+            },
+            Inst::Label(label) => {
+                let offset = match section {
+                    Section::Text => encoder.bytes.len(),
+                    Section::Data => object.data.len(),
+                    Section::Bss => object.bss_size,
+                };
+
+                object.labels.insert(label.clone(), (section, offset));
+            },
+            Inst::Comment(_) => (),
+            Inst::Resq(label, count) => {
+                object.labels.insert(label.clone(), (Section::Bss, object.bss_size));
+                object.bss_size += count * 8;
+            },
+            Inst::Dq(label, value) => {
+                object.labels.insert(label.clone(), (Section::Data, object.data.len()));
+                object.data.extend_from_slice(&value.to_le_bytes());
+            },
+
+            inst => {
+                if let Err(msg) = encoder.instruction(inst) {
+                    return Err(format!("Can't encode `{}`, {}", inst.to_string().trim(), msg));
+                }
+            },
+        }
+    }
+
+    object.text = encoder.bytes;
+    object.fixups = encoder.fixups;
+
+    return Ok(object);
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+    fixups: Vec<Fixup>,
+}
+
+impl Encoder {
+    fn instruction(&mut self, inst: &Inst) -> Result<(), String> {
+        let start = self.fixups.len();
+
+        match inst {
+            Inst::Mov(Operand::Reg(dest), Operand::Imm(value)) => {
+                if let Ok(value) = i32::try_from(*value) {
+                    self.modrm(&[0xc7], 0, &Operand::Reg(*dest))?;
+                    self.imm32(value);
+                } else {
+                    // movabs, the only instruction that takes a 64 bit immediate
+                    self.rex(true, None, Some(*dest));
+                    self.bytes.push(0xb8 + low_bits(*dest));
+                    self.bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            },
+            Inst::Mov(dest, Operand::Imm(value)) => {
+                self.modrm(&[0xc7], 0, dest)?;
+                self.imm32(imm32(*value)?);
+            },
+            Inst::Mov(dest, Operand::Reg(source)) => self.modrm_reg(&[0x89], *source, dest)?,
+            Inst::Mov(Operand::Reg(dest), source) => self.modrm_reg(&[0x8b], *dest, source)?,
+
+            Inst::Push(Operand::Reg(reg)) => {
+                self.rex(false, None, Some(*reg));
+                self.bytes.push(0x50 + low_bits(*reg));
+            },
+            Inst::Push(Operand::Imm(value)) => match i8::try_from(*value) {
+                Ok(value) => self.bytes.extend_from_slice(&[0x6a, value as u8]),
+                Err(_) => {
+                    self.bytes.push(0x68);
+                    self.imm32(imm32(*value)?);
+                },
+            },
+            Inst::Push(source) => self.modrm_without_rex_w(&[0xff], 6, source)?,
+
+            Inst::Pop(Operand::Reg(reg)) => {
+                self.rex(false, None, Some(*reg));
+                self.bytes.push(0x58 + low_bits(*reg));
+            },
+            Inst::Pop(dest) => self.modrm_without_rex_w(&[0x8f], 0, dest)?,
+
+            Inst::Add(dest, source) => self.arithmetic(0x01, 0x03, 0, dest, source)?,
+            Inst::Sub(dest, source) => self.arithmetic(0x29, 0x2b, 5, dest, source)?,
+            Inst::And(dest, source) => self.arithmetic(0x21, 0x23, 4, dest, source)?,
+
+            Inst::Imul(dest, source) => self.modrm_reg(&[0x0f, 0xaf], *dest, source)?,
+            Inst::ImulImm(dest, source, value) => match i8::try_from(*value) {
+                Ok(value) => {
+                    self.modrm_reg(&[0x6b], *dest, source)?;
+                    self.bytes.push(value as u8);
+                },
+                Err(_) => {
+                    self.modrm_reg(&[0x69], *dest, source)?;
+                    self.imm32(*value);
+                },
+            },
+            Inst::Idiv(source) => self.modrm(&[0xf7], 7, source)?,
+            Inst::Neg(dest) => self.modrm(&[0xf7], 3, dest)?,
+            Inst::Cqo => self.bytes.extend_from_slice(&[0x48, 0x99]),
+
+            Inst::Call(label) => self.branch(0xe8, label),
+            Inst::CallIndirect(target) => self.modrm_without_rex_w(&[0xff], 2, target)?,
+            Inst::Jmp(label) => self.branch(0xe9, label),
+            Inst::Ret => self.bytes.push(0xc3),
+            Inst::Syscall => self.bytes.extend_from_slice(&[0x0f, 0x05]),
+
+            _ => return Err("the operands can't be used together".to_string()),
+        }
+
+        // a rip relative address is measured from the end of the instruction, which can
+        // be after an immediate that follows the address
+        let end = self.bytes.len();
+        for fixup in &mut self.fixups[start..] {
+            fixup.addend = fixup.offset as i64 - end as i64;
+        }
+
+        return Ok(());
+    }
+
+    /// `add`, `sub` and `and`, which have the same forms with different opcodes. The form
+    /// with an immediate and rax as the destination is the `to_rm` opcode plus 4
+    fn arithmetic(&mut self, to_rm: u8, from_rm: u8, extension: u8, dest: &Operand, source: &Operand) -> Result<(), String> {
+        match (dest, source) {
+            (_, Operand::Imm(value)) => match i8::try_from(*value) {
+                Ok(value) => {
+                    self.modrm(&[0x83], extension, dest)?;
+                    self.bytes.push(value as u8);
+                },
+                // rax has a form without the modrm byte, one byte shorter
+                Err(_) if *dest == Operand::Reg(Reg::Rax) => {
+                    self.rex(true, None, None);
+                    self.bytes.push(to_rm + 4);
+                    self.imm32(imm32(*value)?);
+                },
+                Err(_) => {
+                    self.modrm(&[0x81], extension, dest)?;
+                    self.imm32(imm32(*value)?);
+                },
+            },
+            (_, Operand::Reg(source)) => self.modrm_reg(&[to_rm], *source, dest)?,
+            (Operand::Reg(dest), _) => self.modrm_reg(&[from_rm], *dest, source)?,
+            _ => return Err("the operands can't be used together".to_string()),
+        }
+
+        return Ok(());
+    }
+
+    fn branch(&mut self, opcode: u8, label: &str) {
+        self.bytes.push(opcode);
+        self.fixups.push(Fixup { offset: self.bytes.len(), label: label.to_string(), addend: 0 });
+        self.bytes.extend_from_slice(&[0; 4]);
+    }
+
+    fn imm32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// An instruction with a register in the reg field of the modrm byte
+    fn modrm_reg(&mut self, opcode: &[u8], reg: Reg, rm: &Operand) -> Result<(), String> {
+        self.encode_modrm(true, opcode, low_bits(reg), Some(reg), rm)
+    }
+
+    /// An instruction with an opcode extension in the reg field of the modrm byte
+    fn modrm(&mut self, opcode: &[u8], extension: u8, rm: &Operand) -> Result<(), String> {
+        self.encode_modrm(true, opcode, extension, None, rm)
+    }
+
+    /// The same as `modrm`, for `push` and `pop` which are 64 bit without `REX.W`
+    fn modrm_without_rex_w(&mut self, opcode: &[u8], extension: u8, rm: &Operand) -> Result<(), String> {
+        self.encode_modrm(false, opcode, extension, None, rm)
+    }
+
+    fn encode_modrm(&mut self, wide: bool, opcode: &[u8], reg_bits: u8, reg: Option<Reg>, rm: &Operand) -> Result<(), String> {
+        let base = match rm {
+            Operand::Reg(rm) | Operand::Mem(Mem::Base(rm, _)) => Some(*rm),
+            _ => None,
+        };
+
+        self.rex(wide, reg, base);
+        self.bytes.extend_from_slice(opcode);
+
+        match rm {
+            Operand::Reg(rm) => self.bytes.push(0xc0 | reg_bits << 3 | low_bits(*rm)),
+
+            Operand::Mem(Mem::Base(base, offset)) => {
+                // rbp and r13 can't be used without a displacement, that encoding means rip
+                let (mode, displacement): (u8, Vec<u8>) = match i8::try_from(*offset) {
+                    Ok(0) if low_bits(*base) != 5 => (0b00, vec!()),
+                    Ok(offset) => (0b01, vec!(offset as u8)),
+                    Err(_) => (0b10, offset.to_le_bytes().to_vec()),
+                };
+
+                self.bytes.push(mode << 6 | reg_bits << 3 | low_bits(*base));
+
+                // rsp and r12 as the base need a sib byte
+                if low_bits(*base) == 4 {
+                    self.bytes.push(0x24);
+                }
+
+                self.bytes.extend_from_slice(&displacement);
+            },
+
+            Operand::Mem(Mem::Label(label)) => {
+                self.bytes.push(reg_bits << 3 | 0b101);
+                self.fixups.push(Fixup { offset: self.bytes.len(), label: label.clone(), addend: 0 });
+                self.bytes.extend_from_slice(&[0; 4]);
+            },
+
+            Operand::Imm(_) => return Err("an immediate can't be used there".to_string()),
+        }
+
+        return Ok(());
+    }
+
+    /// `wide` sets the operand size to 64 bits, the registers set the bits that extend the
+    /// reg and rm fields to reach r8 to r15
+    fn rex(&mut self, wide: bool, reg: Option<Reg>, rm: Option<Reg>) {
+        let mut rex = 0x40;
+
+        if wide {
+            rex |= 0b1000;
+        }
+        if reg.is_some_and(is_extended) {
+            rex |= 0b0100;
+        }
+        if rm.is_some_and(is_extended) {
+            rex |= 0b0001;
+        }
+
+        if rex != 0x40 {
+            self.bytes.push(rex);
+        }
+    }
+}
+
+fn imm32(value: i64) -> Result<i32, String> {
+    match i32::try_from(value) {
+        Ok(value) => Ok(value),
+        Err(_) => Err(format!("the immediate {} doesn't fit in 32 bits", value)),
+    }
+}
+
+/// The number of the register, r8 to r15 are the same as rax to rdi with an extra bit
+fn register_number(reg: Reg) -> u8 {
+    match reg {
+        Reg::Rax => 0,
+        Reg::Rcx => 1,
+        Reg::Rdx => 2,
+        Reg::Rbx => 3,
+        Reg::Rsp => 4,
+        Reg::Rbp => 5,
+        Reg::Rsi => 6,
+        Reg::Rdi => 7,
+        Reg::R8 => 8,
+        Reg::R9 => 9,
+        Reg::R10 => 10,
+        Reg::R11 => 11,
+        Reg::R12 => 12,
+        Reg::R13 => 13,
+        Reg::R14 => 14,
+        Reg::R15 => 15,
+    }
+}
+
+fn low_bits(reg: Reg) -> u8 {
+    register_number(reg) & 0b111
+}
+
+fn is_extended(reg: Reg) -> bool {
+    register_number(reg) >= 8
+}
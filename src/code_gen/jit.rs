@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CString};
+
+use crate::ir::Module;
+
+use super::encode::{Object, Section};
+
+/// The entry point that runs the top level code
+pub const MAIN: &str = "jit_main";
+/// Each function is called through a label with its name after this
+pub const EXPORT_PREFIX: &str = "jit_export_";
+
+/// The bss slots the host fills in before calling the program
+pub const HOST_PUTCHAR: &str = "jit_host_putchar";
+pub const HOST_EXIT: &str = "jit_host_exit";
+pub const HOST_CONTEXT: &str = "jit_host_context";
+/// Where the stack was when the host called in, `exit` goes back to it
+pub const SAVED_RSP: &str = "jit_saved_rsp";
+/// The slot with the address of each extern function has its name after this
+pub const EXTERN_PREFIX: &str = "jit_extern_";
+
+const PAGE_SIZE: usize = 0x1000;
+
+// from the Linux headers
+const PROT_READ: i32 = 1;
+const PROT_WRITE: i32 = 2;
+const PROT_EXEC: i32 = 4;
+const MAP_PRIVATE: i32 = 2;
+const MAP_ANONYMOUS: i32 = 0x20;
+/// Looks a symbol up in every library the process has loaded
+const RTLD_DEFAULT: *mut c_void = std::ptr::null_mut();
+
+extern "C" {
+    fn mmap(address: *mut c_void, length: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut c_void;
+    fn mprotect(address: *mut c_void, length: usize, prot: i32) -> i32;
+    fn munmap(address: *mut c_void, length: usize) -> i32;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
+
+/// What the program does to the outside world, the generated code calls these instead
+/// of making system calls
+pub trait Host {
+    fn putchar(&mut self, byte: u8);
+
+    /// Called for `exit`, the program stops after it returns
+    fn exit(&mut self, _code: i64) {}
+}
+
+/// How a call into the program ended
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Finish {
+    Returned(i64),
+    /// Unlike in an executable, the whole exit code is kept
+    Exited(i64),
+}
+
+/// A program loaded into executable memory. The text and the bss are mapped together,
+/// the bss on the pages after the text, so everything is reachable relative to rip.
+///
+/// The top level variables keep their values between calls. A host callback can't call
+/// back into the program, there is only one saved stack to exit to. Extern functions are
+/// looked up in the libraries the host has loaded, like the C library
+pub struct Jit {
+    memory: *mut u8,
+    size: usize,
+    /// The address of every label
+    labels: HashMap<String, usize>,
+    /// How many arguments each function takes
+    functions: HashMap<String, usize>,
+}
+
+/// What the callbacks get, the host and whether the program exited
+struct Context<'a> {
+    host: &'a mut dyn Host,
+    exited: Option<i64>,
+}
+
+extern "C" fn host_putchar(context: *mut c_void, value: i64) {
+    // the context is alive for the whole call into the program
+    let context = unsafe { &mut *(context as *mut Context) };
+    context.host.putchar(value as u8);
+}
+
+extern "C" fn host_exit(context: *mut c_void, code: i64) {
+    let context = unsafe { &mut *(context as *mut Context) };
+    context.exited = Some(code);
+    context.host.exit(code);
+}
+
+impl Jit {
+    /// Maps the encoded program generated with `Output::Jit` and fills in its jumps and
+    /// references to the data and bss
+    pub fn load(object: &Object, module: &Module) -> Result<Jit, String> {
+        let text_size = object.text.len().div_ceil(PAGE_SIZE) * PAGE_SIZE;
+        let size = text_size + (object.data.len() + object.bss_size).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+
+        let memory = unsafe { mmap(std::ptr::null_mut(), size, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0) };
+        // MAP_FAILED is -1
+        if memory as isize == -1 {
+            return Err("Could not map memory for the program".to_string());
+        }
+
+        let mut jit = Jit {
+            memory: memory as *mut u8,
+            size,
+            labels: HashMap::new(),
+            functions: module.functions.iter().map(|function| (function.name.clone(), function.params)).collect(),
+        };
+
+        for (label, (section, offset)) in &object.labels {
+            let address = match section {
+                Section::Text => memory as usize + offset,
+                Section::Data => memory as usize + text_size + offset,
+                Section::Bss => memory as usize + text_size + object.data.len() + offset,
+            };
+            jit.labels.insert(label.clone(), address);
+        }
+
+        // mmap gives zeroed memory, so the bss is ready as it is
+        let data = unsafe { std::slice::from_raw_parts_mut(jit.memory.add(text_size), object.data.len()) };
+        data.copy_from_slice(&object.data);
+
+        let text = unsafe { std::slice::from_raw_parts_mut(jit.memory, object.text.len()) };
+        text.copy_from_slice(&object.text);
+
+        for fixup in &object.fixups {
+            let place = memory as usize + fixup.offset;
+            let distance = jit.address(&fixup.label)? as i64 + fixup.addend - place as i64;
+
+            let Ok(distance) = i32::try_from(distance) else {
+                return Err(format!("{} is too far away to reach", fixup.label));
+            };
+
+            text[fixup.offset..fixup.offset + 4].copy_from_slice(&distance.to_le_bytes());
+        }
+
+        jit.write_slot(HOST_PUTCHAR, host_putchar as *const () as i64)?;
+        jit.write_slot(HOST_EXIT, host_exit as *const () as i64)?;
+
+        for external in &module.externs {
+            let name = CString::new(external.name.clone()).expect("Extern names don't contain 0");
+            let address = unsafe { dlsym(RTLD_DEFAULT, name.as_ptr()) };
+
+            if address.is_null() {
+                return Err(format!("Could not find the extern function {}", external.name));
+            }
+
+            jit.write_slot(&format!("{}{}", EXTERN_PREFIX, external.name), address as i64)?;
+        }
+
+        if unsafe { mprotect(memory, text_size, PROT_READ | PROT_EXEC) } != 0 {
+            return Err("Could not make the program executable".to_string());
+        }
+
+        return Ok(jit);
+    }
+
+    /// Runs the top level code
+    pub fn run_main(&mut self, host: &mut dyn Host) -> Result<Finish, String> {
+        return self.enter(MAIN, &[], host);
+    }
+
+    /// Calls a function with the arguments, `exit` inside it ends the call
+    pub fn call(&mut self, name: &str, args: &[i64], host: &mut dyn Host) -> Result<Finish, String> {
+        let Some(params) = self.functions.get(name) else {
+            return Err(format!("There is no function called {}", name));
+        };
+        if *params != args.len() {
+            return Err(format!("{} takes {} arguments, not {}", name, params, args.len()));
+        }
+
+        return self.enter(&format!("{}{}", EXPORT_PREFIX, name), args, host);
+    }
+
+    fn enter(&mut self, label: &str, args: &[i64], host: &mut dyn Host) -> Result<Finish, String> {
+        let entry = self.address(label)?;
+        let mut context = Context { host, exited: None };
+
+        self.write_slot(HOST_CONTEXT, &mut context as *mut Context as usize as i64)?;
+
+        // the entry points take the arguments in rdi and return in rax like C functions
+        let entry: extern "C" fn(*const i64) -> i64 = unsafe { std::mem::transmute(entry) };
+        let value = entry(args.as_ptr());
+
+        match context.exited {
+            Some(code) => return Ok(Finish::Exited(code)),
+            None => return Ok(Finish::Returned(value)),
+        }
+    }
+
+    fn address(&self, label: &str) -> Result<usize, String> {
+        match self.labels.get(label) {
+            Some(address) => Ok(*address),
+            None => Err(format!("Undefined label {}", label)),
+        }
+    }
+
+    fn write_slot(&mut self, label: &str, value: i64) -> Result<(), String> {
+        let address = self.address(label)?;
+        unsafe { *(address as *mut i64) = value };
+
+        return Ok(());
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        unsafe { munmap(self.memory as *mut c_void, self.size) };
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::{*, math::OperationType};
+
+/// Translates the ast into textual LLVM IR, for `llc` or `clang` to optimise and compile
+/// for any target LLVM has.
+///
+/// Like the C backend, every function becomes `@fn_` and its name and the top level code
+/// becomes `@main`. Top level variables are the globals `@global_`, the variables of a
+/// function are allocas named `%local_`, which LLVM turns back into registers. `putchar`
+/// and `exit` are the C library's, and extern functions keep their own names. The
+/// program must already have been checked by lowering it into the ir.
+///
+/// Pointers are written with their type, `i64*`, which LLVM 14 needs and later versions
+/// read as the opaque `ptr`
+pub struct LlvmGen {
+    globals: Vec<String>,
+    /// The extern functions, and whether they return a value
+    externs: HashMap<String, bool>,
+    declarations: Vec<String>,
+    functions: Vec<String>,
+
+    /// The function that is being translated
+    current: LlvmFunction,
+}
+
+#[derive(Default)]
+struct LlvmFunction {
+    /// The allocas, which have to be in the entry block
+    allocas: Vec<String>,
+    lines: Vec<String>,
+    /// The variables declared so far, anything else is a global
+    locals: HashSet<String>,
+    temps: usize,
+    blocks: usize,
+}
+
+impl LlvmGen {
+    pub fn new() -> LlvmGen {
+        LlvmGen {
+            globals: vec!(),
+            externs: HashMap::new(),
+            declarations: vec!(),
+            functions: vec!(),
+            current: LlvmFunction::default(),
+        }
+    }
+
+    /// The whole module
+    pub fn generate(&mut self, program: &NodeProgram) -> String {
+        for extern_stmt in program.externs() {
+            let name = &extern_stmt.identifier.info;
+            let returns = if extern_stmt.returns { "i64" } else { "void" };
+            let params = vec!("i64"; extern_stmt.args.len()).join(", ");
+
+            self.externs.insert(name.clone(), extern_stmt.returns);
+            self.declarations.push(format!("declare {} {}({})", returns, identifier('@', "", name), params));
+        }
+
+        self.gen_scope(program, true);
+        self.line("ret i32 0".to_string());
+
+        let main = std::mem::take(&mut self.current);
+        self.functions.push(format!("define i32 @main() {{\n{}}}\n", body(&main)));
+
+        let mut output = String::new();
+
+        for global in &self.globals {
+            output.push_str(&format!("{} = internal global i64 0\n", global));
+        }
+        if !self.globals.is_empty() {
+            output.push('\n');
+        }
+
+        for function in &self.functions {
+            output.push_str(function);
+            output.push('\n');
+        }
+
+        output.push_str("declare i32 @putchar(i32)\n");
+        output.push_str("declare void @exit(i32) noreturn\n");
+        for declaration in &self.declarations {
+            output.push_str(&format!("{}\n", declaration));
+        }
+
+        return output;
+    }
+
+    fn gen_scope(&mut self, program: &NodeProgram, top_level: bool) {
+        for stmt in &program.statements {
+            match stmt {
+                NodeStatements::Declare(declare_stmt) => {
+                    // the value can't refer to the variable it declares
+                    let value = match &declare_stmt.expression {
+                        Some(expression) => self.gen_expression(expression),
+                        None => "0".to_string(),
+                    };
+
+                    let name = &declare_stmt.identifier.info;
+                    let address = if top_level {
+                        self.globals.push(global(name));
+                        global(name)
+                    } else {
+                        self.current.locals.insert(name.clone());
+                        self.current.allocas.push(format!("{} = alloca i64", local(name)));
+                        local(name)
+                    };
+
+                    self.line(format!("store i64 {}, i64* {}", value, address));
+                },
+                NodeStatements::Set(set_stmt) => {
+                    let value = self.gen_expression(&set_stmt.expression);
+                    let address = self.variable(&set_stmt.identifier.info);
+                    self.line(format!("store i64 {}, i64* {}", value, address));
+                },
+                NodeStatements::Exit(exit_stmt) => {
+                    let value = self.gen_expression(&exit_stmt.expression);
+                    let code = self.temp();
+                    self.line(format!("{} = trunc i64 {} to i32", code, value));
+                    self.line(format!("call void @exit(i32 {})", code));
+                    self.terminate("unreachable".to_string());
+                },
+                NodeStatements::PutChar(putchar_stmt) => {
+                    let value = self.gen_expression(&putchar_stmt.expression);
+                    let char = self.temp();
+                    self.line(format!("{} = trunc i64 {} to i32", char, value));
+                    self.line(format!("call i32 @putchar(i32 {})", char));
+                },
+                NodeStatements::Function(func_stmt) => self.gen_function(func_stmt),
+                // the declarations were all made before anything else
+                NodeStatements::Extern(_) => (),
+                NodeStatements::FunctionCall(func_call_stmt) => {
+                    let call = self.gen_call(func_call_stmt);
+                    self.line(call);
+                },
+                NodeStatements::Return(return_stmt) => {
+                    let value = match &return_stmt.expression {
+                        Some(expression) => self.gen_expression(expression),
+                        None => "0".to_string(),
+                    };
+                    self.terminate(format!("ret i64 {}", value));
+                },
+            }
+        }
+    }
+
+    /// Adds the function to the module, its body is translated on its own so nested
+    /// functions don't end up inside the enclosing one
+    fn gen_function(&mut self, func_stmt: &NodeStmtFunction) {
+        let outer = std::mem::take(&mut self.current);
+
+        // the parameters are copied into allocas, so they can be set like other variables
+        let mut params = vec!();
+        for arg in &func_stmt.args {
+            let name = &arg.identifier.info;
+            params.push(format!("i64 {}", param(name)));
+
+            self.current.locals.insert(name.clone());
+            self.current.allocas.push(format!("{} = alloca i64", local(name)));
+            self.current.allocas.push(format!("store i64 {}, i64* {}", param(name), local(name)));
+        }
+
+        self.gen_scope(&func_stmt.scope, false);
+
+        // falling off the end returns 0
+        self.line("ret i64 0".to_string());
+
+        let function = std::mem::replace(&mut self.current, outer);
+        self.functions.push(format!(
+            "define internal i64 {}({}) {{\n{}}}\n",
+            function_name(&func_stmt.identifier.info), params.join(", "), body(&function),
+        ));
+    }
+
+    /// The value of the expression, a temp or a constant
+    fn gen_expression(&mut self, expr: &MathValue) -> String {
+        match expr {
+            MathValue::Integer(integer) => integer.value.expect("Integer literal without a value").to_string(),
+
+            MathValue::Identifier(ident) => {
+                let address = self.variable(&ident.info);
+                let temp = self.temp();
+                self.line(format!("{} = load i64, i64* {}", temp, address));
+                temp
+            },
+
+            // the operands are evaluated from left to right, like the native backends
+            MathValue::Operation(oper) => {
+                let operands: Vec<String> = oper.operands().into_iter()
+                    .map(|operand| self.gen_expression(operand))
+                    .collect();
+
+                let instruction = match oper.as_ref() {
+                    OperationType::Add(_) => format!("add i64 {}, {}", operands[0], operands[1]),
+                    OperationType::Sub(_) => format!("sub i64 {}, {}", operands[0], operands[1]),
+                    OperationType::Mult(_) => format!("mul i64 {}, {}", operands[0], operands[1]),
+                    OperationType::Div(_) => format!("sdiv i64 {}, {}", operands[0], operands[1]),
+                    OperationType::Negate(_) => format!("sub i64 0, {}", operands[0]),
+                };
+
+                let temp = self.temp();
+                self.line(format!("{} = {}", temp, instruction));
+                temp
+            },
+
+            MathValue::Call(call) => {
+                let call = self.gen_call(call);
+                let temp = self.temp();
+                self.line(format!("{} = {}", temp, call));
+                temp
+            },
+        }
+    }
+
+    /// The call instruction, after the arguments have been evaluated
+    fn gen_call(&mut self, call: &NodeStmtFunctionCall) -> String {
+        let args: Vec<String> = call.args.iter()
+            .map(|arg| format!("i64 {}", self.gen_expression(arg)))
+            .collect();
+
+        let name = &call.identifier.info;
+        match self.externs.get(name) {
+            Some(true) => return format!("call i64 {}({})", identifier('@', "", name), args.join(", ")),
+            Some(false) => return format!("call void {}({})", identifier('@', "", name), args.join(", ")),
+            None => return format!("call i64 {}({})", function_name(name), args.join(", ")),
+        }
+    }
+
+    /// Ends the current block. Anything after it goes in a new block, which is never
+    /// reached but still has to be valid
+    fn terminate(&mut self, terminator: String) {
+        self.line(terminator);
+
+        self.current.blocks += 1;
+        self.current.lines.push(format!("dead{}:", self.current.blocks));
+    }
+
+    /// A variable is a local if it has been declared in this function, otherwise it is a
+    /// global
+    fn variable(&self, name: &str) -> String {
+        if self.current.locals.contains(name) {
+            return local(name);
+        }
+
+        return global(name);
+    }
+
+    fn temp(&mut self) -> String {
+        self.current.temps += 1;
+        format!("%t{}", self.current.temps - 1)
+    }
+
+    fn line(&mut self, line: String) {
+        self.current.lines.push(format!("    {}", line));
+    }
+}
+
+/// The allocas come first, in the entry block
+fn body(function: &LlvmFunction) -> String {
+    let mut output = String::new();
+
+    for line in &function.allocas {
+        output.push_str(&format!("    {}\n", line));
+    }
+    for line in &function.lines {
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    return output;
+}
+
+fn function_name(name: &str) -> String {
+    identifier('@', "fn_", name)
+}
+
+fn global(name: &str) -> String {
+    identifier('@', "global_", name)
+}
+
+fn local(name: &str) -> String {
+    identifier('%', "local_", name)
+}
+
+fn param(name: &str) -> String {
+    identifier('%', "param_", name)
+}
+
+/// LLVM names can contain `-`, other characters atomic allows but LLVM doesn't need quotes
+fn identifier(sigil: char, prefix: &str, name: &str) -> String {
+    if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return format!("{}{}{}", sigil, prefix, name);
+    }
+
+    return format!("{}\"{}{}\"", sigil, prefix, name);
+}
@@ -0,0 +1,104 @@
+use super::asm::{Inst, Operand, Reg};
+
+/// Cleans up the instruction pairs the code generator leaves behind:
+///
+/// - `push X; pop X` is removed
+/// - `push X; pop Y` becomes `mov Y, X`
+/// - `add rsp, 0` and `sub rsp, 0` are removed
+/// - `mov rax, imm; push rax` becomes `push imm` when rax isn't read afterwards
+///
+/// Comments between the two instructions of a pair don't stop it from matching, labels do.
+/// It runs until nothing changes, since a rewrite can make a new pair
+pub fn optimise(insts: &mut Vec<Inst>) {
+    while rewrite(insts) {}
+}
+
+/// Makes one pass over the instructions, returns whether anything changed
+fn rewrite(insts: &mut Vec<Inst>) -> bool {
+    let mut changed = false;
+    let mut i = 0;
+
+    while i < insts.len() {
+        if let Inst::Add(Operand::Reg(Reg::Rsp), Operand::Imm(0)) | Inst::Sub(Operand::Reg(Reg::Rsp), Operand::Imm(0)) = insts[i] {
+            insts.remove(i);
+            changed = true;
+            continue;
+        }
+
+        let Some(next) = next_instruction(insts, i) else {
+            i += 1;
+            continue;
+        };
+
+        match (&insts[i], &insts[next]) {
+            (Inst::Push(source), Inst::Pop(dest)) if source == dest => {
+                insts.remove(next);
+                insts.remove(i);
+                changed = true;
+                continue;
+            },
+            (Inst::Push(source), Inst::Pop(dest)) if !(source.is_mem() && dest.is_mem()) => {
+                insts[i] = Inst::Mov(dest.clone(), source.clone());
+                insts.remove(next);
+                changed = true;
+            },
+            (Inst::Mov(Operand::Reg(Reg::Rax), Operand::Imm(value)), Inst::Push(Operand::Reg(Reg::Rax)))
+                if i32::try_from(*value).is_ok() && !reads_rax_after(insts, next) =>
+            {
+                insts[i] = Inst::Push(Operand::Imm(*value));
+                insts.remove(next);
+                changed = true;
+            },
+            _ => (),
+        }
+
+        i += 1;
+    }
+
+    return changed;
+}
+
+/// The index of the instruction that runs after the one at `index`, skipping comments
+fn next_instruction(insts: &[Inst], index: usize) -> Option<usize> {
+    for (offset, inst) in insts[index + 1..].iter().enumerate() {
+        match inst {
+            Inst::Comment(_) => continue,
+            inst if inst.is_instruction() => return Some(index + 1 + offset),
+            _ => return None,
+        }
+    }
+
+    return None;
+}
+
+/// Whether the value in rax after the instruction at `index` might be read. Looks at the
+/// following instructions until one of them overwrites rax, anything it can't follow,
+/// like a label or a `ret`, counts as a read
+fn reads_rax_after(insts: &[Inst], index: usize) -> bool {
+    for inst in &insts[index + 1..] {
+        match inst {
+            Inst::Comment(_) => continue,
+            Inst::Mov(Operand::Reg(Reg::Rax), source) => return source.uses(Reg::Rax),
+            // calls don't take anything in rax and don't keep it
+            Inst::Call(_) => return false,
+            Inst::Push(operand) | Inst::Pop(operand) | Inst::Neg(operand) | Inst::Idiv(operand) => {
+                if operand.uses(Reg::Rax) || matches!(inst, Inst::Idiv(_)) {
+                    return true;
+                }
+            },
+            Inst::Mov(dest, source) | Inst::Add(dest, source) | Inst::Sub(dest, source) => {
+                if dest.uses(Reg::Rax) || source.uses(Reg::Rax) {
+                    return true;
+                }
+            },
+            Inst::Imul(dest, source) | Inst::ImulImm(dest, source, _) => {
+                if *dest == Reg::Rax || source.uses(Reg::Rax) {
+                    return true;
+                }
+            },
+            _ => return true,
+        }
+    }
+
+    return true;
+}
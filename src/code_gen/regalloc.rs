@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use crate::ir::{Function, Instr, Operand, Temp, Var};
+
+use super::asm::Reg;
+
+/// Kept across calls and syscalls, a function has to save them before using them
+pub const CALLEE_SAVED: [Reg; 5] = [Reg::Rbx, Reg::R12, Reg::R13, Reg::R14, Reg::R15];
+
+/// Free to use between calls. rax, rcx, rdx, rsi and rdi are left out because the
+/// code generator uses them as scratch registers, and for division and syscalls
+pub const CALLER_SAVED: [Reg; 4] = [Reg::R8, Reg::R9, Reg::R10, Reg::R11];
+
+/// Something that can be kept in a register, local variables of a function are only
+/// used by that function, so they can live in registers the same as temps
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Value {
+    Temp(Temp),
+    Local(usize),
+}
+
+/// The registers picked for each value, anything missing is spilled to the stack
+#[derive(Debug)]
+pub struct Allocation {
+    pub registers: HashMap<Value, Reg>,
+}
+
+impl Allocation {
+    pub fn register(&self, value: Value) -> Option<Reg> {
+        self.registers.get(&value).copied()
+    }
+
+    /// The callee saved registers that were used, these need saving in the prologue
+    pub fn used_callee_saved(&self) -> Vec<Reg> {
+        CALLEE_SAVED.iter()
+            .filter(|reg| self.registers.values().any(|used| used == *reg))
+            .copied()
+            .collect()
+    }
+}
+
+/// The range of instructions a value is live for
+#[derive(Debug)]
+struct Interval {
+    value: Value,
+    start: usize,
+    end: usize,
+    /// Whether a call or syscall happens while the value is live
+    crosses_call: bool,
+}
+
+/// Linear scan register allocation over one function.
+///
+/// Instructions are numbered in order, a value is live from its first definition to its
+/// last use. There are no jumps backwards, so that order is the order they run in. Values
+/// live across a call can only get callee saved registers. When there are no registers
+/// left the value that is live the longest is spilled
+pub fn allocate(function: &Function) -> Allocation {
+    let mut allocation = Allocation { registers: HashMap::new() };
+
+    let intervals = live_intervals(function);
+
+    // the intervals that currently have a register, and the register
+    let mut active: Vec<(usize, Reg)> = vec!();
+
+    for i in 0..intervals.len() {
+        let start = intervals[i].start;
+
+        // free the registers of anything that ended
+        active.retain(|(index, _)| intervals[*index].end > start);
+
+        let pool: Vec<Reg> = if intervals[i].crosses_call {
+            CALLEE_SAVED.to_vec()
+        } else {
+            CALLER_SAVED.iter().chain(CALLEE_SAVED.iter()).copied().collect()
+        };
+
+        if let Some(&reg) = pool.iter().find(|reg| active.iter().all(|(_, used)| used != *reg)) {
+            active.push((i, reg));
+            allocation.registers.insert(intervals[i].value, reg);
+            continue;
+        }
+
+        // no registers left, steal one from the interval that ends last if it ends after
+        // this one, so the register is kept busy for the shortest time
+        let victim = active.iter()
+            .enumerate()
+            .filter(|(_, (_, reg))| pool.contains(reg))
+            .max_by_key(|(_, (index, _))| intervals[*index].end)
+            .map(|(position, (index, reg))| (position, *index, *reg));
+
+        if let Some((position, index, reg)) = victim {
+            if intervals[index].end > intervals[i].end {
+                allocation.registers.remove(&intervals[index].value);
+                active.remove(position);
+
+                active.push((i, reg));
+                allocation.registers.insert(intervals[i].value, reg);
+            }
+        }
+    }
+
+    return allocation;
+}
+
+fn live_intervals(function: &Function) -> Vec<Interval> {
+    let mut ranges: HashMap<Value, (usize, usize)> = HashMap::new();
+    let mut calls: Vec<usize> = vec!();
+
+    let mut touch = |value: Value, position: usize| {
+        let range = ranges.entry(value).or_insert((position, position));
+        range.0 = range.0.min(position);
+        range.1 = range.1.max(position);
+    };
+
+    // instructions are numbered from 1, 0 is the entry
+    let mut position = 0;
+    for block in &function.blocks {
+        for instr in &block.instrs {
+            position += 1;
+
+            for operand in instr.operands() {
+                if let Operand::Temp(temp) = operand {
+                    touch(Value::Temp(temp), position);
+                }
+            }
+
+            if let Some(dest) = instr.dest() {
+                touch(Value::Temp(dest), position);
+            }
+
+            if let Some(Var::Local(index)) = instr.var() {
+                touch(Value::Local(index), position);
+            }
+
+            if matches!(instr, Instr::Call { .. } | Instr::CallExtern { .. } | Instr::PutChar { .. }) {
+                calls.push(position);
+            }
+        }
+
+        position += 1;
+        for operand in block.terminator.operands() {
+            if let Operand::Temp(temp) = operand {
+                touch(Value::Temp(temp), position);
+            }
+        }
+    }
+
+    // parameters already have their value when the function starts, the ones that are
+    // never used don't need a register at all
+    for i in 0..function.params {
+        if let Some(range) = ranges.get_mut(&Value::Local(i)) {
+            range.0 = 0;
+        }
+    }
+
+    let mut intervals: Vec<Interval> = ranges.into_iter()
+        .map(|(value, (start, end))| Interval {
+            value,
+            start,
+            end,
+            crosses_call: calls.iter().any(|call| start < *call && *call < end),
+        })
+        .collect();
+
+    // sorted by start for the scan, the rest keeps the hash map order from changing the output
+    intervals.sort_by_key(|interval| (interval.start, interval.end, interval.value));
+
+    return intervals;
+}
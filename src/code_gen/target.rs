@@ -0,0 +1,51 @@
+/// What code can be generated for, picked with `--target`
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Target {
+    /// x86-64 Linux, assembled by the built-in encoder or nasm
+    X86_64,
+    /// AArch64 Linux, as GNU assembler source
+    Aarch64,
+    /// Portable C99 source
+    C,
+    /// Textual LLVM IR, for `llc` or `clang`
+    Llvm,
+    /// A WebAssembly text module, importing `putchar` and `exit` from the host
+    Wasm,
+    /// Bytecode for the virtual machine, run with `atomic-lang run`
+    Bytecode,
+}
+
+impl Target {
+    pub const ALL: [Target; 6] = [
+        Target::X86_64, Target::Aarch64, Target::C, Target::Llvm, Target::Wasm, Target::Bytecode,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Target::X86_64 => "x86-64",
+            Target::Aarch64 => "aarch64",
+            Target::C => "c",
+            Target::Llvm => "llvm",
+            Target::Wasm => "wasm",
+            Target::Bytecode => "bytecode",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Target> {
+        Target::ALL.into_iter().find(|target| target.name() == name)
+    }
+}
+
+/// The Linux system call numbers, which are different on every architecture. The C
+/// target uses the C library instead
+pub struct Syscalls {
+    pub write: i64,
+    pub exit: i64,
+}
+
+impl Syscalls {
+    pub const X86_64: Syscalls = Syscalls { write: 1, exit: 60 };
+    pub const AARCH64: Syscalls = Syscalls { write: 64, exit: 93 };
+}
@@ -0,0 +1,227 @@
+use crate::ir::{BinaryOp, Function, Instr, Module, Operand, Temp, Terminator, UnaryOp, Var};
+
+/// Where the imports end in the header, the ones for extern functions go after them
+const IMPORTS_END: usize = 3;
+
+/// 64 KiB pages of linear memory, the stack starts at the top
+const MEMORY_PAGES: usize = 16;
+
+/// Generates a WebAssembly text module from the ir.
+///
+/// `putchar` and `exit` are imported from the host as `env.putchar` and `env.exit`, both
+/// take an `i32`, and the top level code is exported as `main`. Extern functions are
+/// imported from `env` under their own names, with `i64` parameters and result. Top level variables are
+/// at the bottom of linear memory, 8 bytes each. Every function has a frame on a stack in
+/// linear memory that grows down from the top, with `$sp` pointing at the newest frame and
+/// every local, parameters first, in a slot of its frame. Temps are wasm locals
+pub struct WasmGen {
+    pub wat: Vec<String>,
+}
+
+impl WasmGen {
+    pub fn new() -> WasmGen {
+        WasmGen {
+            wat: vec!(
+                "(module".to_string(),
+                "  (import \"env\" \"putchar\" (func $putchar (param i32)))".to_string(),
+                "  (import \"env\" \"exit\" (func $exit (param i32)))".to_string(),
+                format!("  (memory (export \"memory\") {})", MEMORY_PAGES),
+                format!("  (global $sp (mut i32) (i32.const {}))", MEMORY_PAGES * 65536),
+            ),
+        }
+    }
+
+    /// The whole module
+    pub fn gen_wat(&mut self, module: &Module) -> String {
+        self.generate(module);
+        self.wat.push(")".to_string());
+
+        let mut output = String::new();
+        for line in &self.wat {
+            output.push_str(line);
+            output.push('\n');
+        }
+
+        return output;
+    }
+
+    pub fn generate(&mut self, module: &Module) {
+        for (i, external) in module.externs.iter().enumerate() {
+            let mut import = format!("  (import \"env\" \"{}\" (func $extern_{}", external.name, external.name);
+            for _ in &external.params {
+                import.push_str(" (param i64)");
+            }
+            if external.returns {
+                import.push_str(" (result i64)");
+            }
+            import.push_str("))");
+
+            self.wat.insert(IMPORTS_END + i, import);
+        }
+
+        for (i, global) in module.globals.iter().enumerate() {
+            self.wat.push(format!("  ;; global {} is at {}", global, i * 8));
+        }
+
+        self.gen_function(module, &module.main, true);
+
+        for function in &module.functions {
+            self.gen_function(module, function, false);
+        }
+    }
+
+    fn gen_function(&mut self, module: &Module, function: &Function, entry: bool) {
+        let mut signature = if entry {
+            "  (func $main (export \"main\")".to_string()
+        } else {
+            format!("  (func $fn_{}", function.name)
+        };
+
+        for i in 0..function.params {
+            signature.push_str(&format!(" (param $p{} i64)", i));
+        }
+        if !entry {
+            signature.push_str(" (result i64)");
+        }
+        self.wat.push(signature);
+
+        let frame_size = function.locals.len() * 8;
+        if frame_size > 0 {
+            self.wat.push("    (local $fp i32)".to_string());
+        }
+        for temp in 0..function.temps {
+            self.wat.push(format!("    (local $t{} i64)", temp));
+        }
+
+        if frame_size > 0 {
+            self.inst(format!("(global.set $sp (i32.sub (global.get $sp) (i32.const {})))", frame_size));
+            self.inst("(local.set $fp (global.get $sp))".to_string());
+        }
+
+        for i in 0..function.params {
+            self.inst(format!("(i64.store offset={} (local.get $fp) (local.get $p{}))", i * 8, i));
+        }
+
+        // the blocks after the first are never reached, wasm accepts anything that
+        // type checks after a `return` or `unreachable`
+        for block in &function.blocks {
+            for instr in &block.instrs {
+                self.gen_instr(module, instr);
+            }
+
+            match &block.terminator {
+                Terminator::Return(value) => {
+                    self.pop_frame(frame_size);
+                    self.inst(format!("(return {})", operand(value)));
+                },
+                Terminator::TailCall { func, args } => {
+                    // the arguments are all in temps, so the frame can go first
+                    self.pop_frame(frame_size);
+                    self.inst(format!("(return {})", call(&format!("fn_{}", func), args)));
+                },
+                Terminator::Exit(value) => {
+                    self.inst(format!("(call $exit (i32.wrap_i64 {}))", operand(value)));
+                    self.inst("(unreachable)".to_string());
+                },
+            }
+        }
+
+        self.wat.push("  )".to_string());
+    }
+
+    fn pop_frame(&mut self, frame_size: usize) {
+        if frame_size > 0 {
+            self.inst(format!("(global.set $sp (i32.add (local.get $fp) (i32.const {})))", frame_size));
+        }
+    }
+
+    fn gen_instr(&mut self, module: &Module, instr: &Instr) {
+        match instr {
+            Instr::Load { dest, var } => {
+                self.inst(format!("(local.set {} (i64.load {}))", temp(*dest), address(*var)));
+            },
+
+            Instr::Store { var, value } => {
+                self.inst(format!("(i64.store {} {})", address(*var), operand(value)));
+            },
+
+            Instr::Binary { dest, op, lhs, rhs } => {
+                let op = match op {
+                    BinaryOp::Add => "i64.add",
+                    BinaryOp::Sub => "i64.sub",
+                    BinaryOp::Mul => "i64.mul",
+                    BinaryOp::Div => "i64.div_s",
+                };
+
+                self.inst(format!("(local.set {} ({} {} {}))", temp(*dest), op, operand(lhs), operand(rhs)));
+            },
+
+            Instr::Unary { dest, op, value } => {
+                let value = match op {
+                    UnaryOp::Neg => format!("(i64.sub (i64.const 0) {})", operand(value)),
+                };
+
+                self.inst(format!("(local.set {} {})", temp(*dest), value));
+            },
+
+            Instr::Copy { dest, value } => {
+                self.inst(format!("(local.set {} {})", temp(*dest), operand(value)));
+            },
+
+            Instr::Call { dest, func, args } => match dest {
+                Some(dest) => self.inst(format!("(local.set {} {})", temp(*dest), call(&format!("fn_{}", func), args))),
+                None => self.inst(format!("(drop {})", call(&format!("fn_{}", func), args))),
+            },
+
+            Instr::CallExtern { dest, func, args } => {
+                let extern_call = call(&format!("extern_{}", func), args);
+                let returns = module.externs.iter().any(|external| external.name == *func && external.returns);
+
+                match dest {
+                    Some(dest) => self.inst(format!("(local.set {} {})", temp(*dest), extern_call)),
+                    None if returns => self.inst(format!("(drop {})", extern_call)),
+                    None => self.inst(extern_call),
+                }
+            },
+
+            Instr::PutChar { value } => {
+                self.inst(format!("(call $putchar (i32.wrap_i64 {}))", operand(value)));
+            },
+        }
+    }
+
+    fn inst(&mut self, inst: String) {
+        self.wat.push(format!("    {}", inst));
+    }
+}
+
+fn call(func: &str, args: &[Operand]) -> String {
+    let mut call = format!("(call ${}", func);
+
+    for arg in args {
+        call.push(' ');
+        call.push_str(&operand(arg));
+    }
+    call.push(')');
+
+    return call;
+}
+
+fn operand(operand: &Operand) -> String {
+    match operand {
+        Operand::Const(value) => format!("(i64.const {})", value),
+        Operand::Temp(t) => format!("(local.get {})", temp(*t)),
+    }
+}
+
+fn temp(temp: Temp) -> String {
+    format!("$t{}", temp.0)
+}
+
+/// The memory immediate and address of a load or store of the variable
+fn address(var: Var) -> String {
+    match var {
+        Var::Global(index) => format!("(i32.const {})", index * 8),
+        Var::Local(index) => format!("offset={} (local.get $fp)", index * 8),
+    }
+}
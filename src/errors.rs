@@ -19,8 +19,13 @@ pub fn external_error(error_msg: &str) -> ! {
 }
 
 pub fn inline_error(err: Error, settings: &Settings) -> ! {
-    let text = read_in(settings);
-    let text: Vec<&str> = text.split('\n').collect();
+    print_inline_error(&err, &read_in(settings));
+    exit(1);
+}
+
+/// Prints the error with the line of the source it is on, without exiting
+pub fn print_inline_error(err: &Error, source: &str) {
+    let text: Vec<&str> = source.split('\n').collect();
     let line = text[err.line - 1];
 
     eprintln!("{} in line {}:",
@@ -29,6 +34,5 @@ pub fn inline_error(err: Error, settings: &Settings) -> ! {
     );
     eprintln!("{}", line);
     eprintln!("\n{}", err.msg);
-    exit(1);
 }
 
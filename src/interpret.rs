@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::io::{BufWriter, Stdout, Write};
+use std::thread;
+
+use crate::{
+    errors::Error,
+    opt::fold::first_token,
+    parser::{*, math::OperationType},
+};
+
+/// How deeply calls can nest, the native code runs out of stack somewhere around here
+const MAX_DEPTH: usize = 100_000;
+
+/// Every nested call recurses in the interpreter, so it gets a stack big enough for them
+const STACK_SIZE: usize = 1 << 30;
+
+/// Runs the program straight from the ast, without generating any code, and returns its
+/// exit code. `putchar` writes to stdout.
+///
+/// The program must already have been checked by lowering it into the ir, so every
+/// variable and call here is known to be valid. Arithmetic wraps around like in the
+/// native code, and dividing by zero is an error instead of a crash
+pub fn interpret(program: &NodeProgram) -> Result<i32, Error> {
+    let exit_code = run_statements(program, 0, &mut Globals::new())?;
+
+    return Ok(exit_code.unwrap_or(0));
+}
+
+/// The values of the top level variables, by name
+pub type Globals = HashMap<String, i64>;
+
+/// Runs the top level statements from `start` on, the ones before it have already run
+/// and left their variables in `globals`. Returns the exit code if the program exited
+pub fn run_statements(program: &NodeProgram, start: usize, globals: &mut Globals) -> Result<Option<i32>, Error> {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                let mut interpreter = Interpreter::new(program, std::mem::take(globals));
+                let result = interpreter.run_scope(&program.statements[start..], &mut None);
+
+                interpreter.out.flush().expect("Could not write to stdout");
+                *globals = interpreter.globals;
+
+                match result {
+                    Ok(_) => Ok(None),
+                    // only the lowest byte of the exit code is kept, like the exit syscall does
+                    Err(Stop::Exit(code)) => Ok(Some(code as i32)),
+                    Err(Stop::Error(err)) => Err(err),
+                }
+            })
+            .expect("Could not start the interpreter")
+            .join()
+            .expect("The interpreter panicked")
+    })
+}
+
+/// What a statement does to the code that runs it
+enum Flow<'a> {
+    Next,
+    Return(i64),
+    /// `return f(...)`, the caller calls `f` instead of nesting another call
+    TailCall(&'a NodeStmtFunction, Vec<i64>),
+}
+
+/// Why the program stopped early
+enum Stop {
+    Exit(i64),
+    Error(Error),
+}
+
+/// The variables of the function that is running, the top level code has none
+type Frame<'a> = Option<HashMap<&'a str, i64>>;
+
+struct Interpreter<'a> {
+    /// Every function, nested or not, their names are unique
+    functions: HashMap<&'a str, &'a NodeStmtFunction>,
+    globals: Globals,
+    out: BufWriter<Stdout>,
+    depth: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(program: &'a NodeProgram, globals: Globals) -> Interpreter<'a> {
+        let mut interpreter = Interpreter {
+            functions: HashMap::new(),
+            globals,
+            out: BufWriter::new(std::io::stdout()),
+            depth: 0,
+        };
+        interpreter.collect_functions(program);
+
+        return interpreter;
+    }
+
+    fn collect_functions(&mut self, program: &'a NodeProgram) {
+        for stmt in &program.statements {
+            if let NodeStatements::Function(func_stmt) = stmt {
+                self.functions.insert(&func_stmt.identifier.info, func_stmt);
+                self.collect_functions(&func_stmt.scope);
+            }
+        }
+    }
+
+    fn run_scope(&mut self, statements: &'a [NodeStatements], frame: &mut Frame<'a>) -> Result<Flow<'a>, Stop> {
+        for stmt in statements {
+            match stmt {
+                NodeStatements::Declare(declare_stmt) => {
+                    let value = match &declare_stmt.expression {
+                        Some(expression) => self.eval(expression, frame)?,
+                        None => 0,
+                    };
+
+                    let name = declare_stmt.identifier.info.as_str();
+                    match frame {
+                        Some(locals) => locals.insert(name, value),
+                        None => self.globals.insert(name.to_string(), value),
+                    };
+                },
+                NodeStatements::Set(set_stmt) => {
+                    let value = self.eval(&set_stmt.expression, frame)?;
+                    self.set(&set_stmt.identifier.info, value, frame);
+                },
+                NodeStatements::Exit(exit_stmt) => {
+                    let value = self.eval(&exit_stmt.expression, frame)?;
+                    return Err(Stop::Exit(value));
+                },
+                NodeStatements::PutChar(putchar_stmt) => {
+                    let value = self.eval(&putchar_stmt.expression, frame)?;
+                    self.out.write_all(&[value as u8]).expect("Could not write to stdout");
+                },
+                // the functions were all found before running anything, and a program
+                // with extern functions is turned away by `check_no_externs`
+                NodeStatements::Function(_) | NodeStatements::Extern(_) => (),
+                NodeStatements::FunctionCall(func_call_stmt) => {
+                    self.eval_call(func_call_stmt, frame)?;
+                },
+                NodeStatements::Return(return_stmt) => {
+                    let value = match &return_stmt.expression {
+                        Some(MathValue::Call(call)) => {
+                            let args = self.eval_args(call, frame)?;
+                            return Ok(Flow::TailCall(self.functions[call.identifier.info.as_str()], args));
+                        },
+                        Some(expression) => self.eval(expression, frame)?,
+                        None => 0,
+                    };
+
+                    return Ok(Flow::Return(value));
+                },
+            }
+        }
+
+        return Ok(Flow::Next);
+    }
+
+    fn eval(&mut self, expr: &'a MathValue, frame: &mut Frame<'a>) -> Result<i64, Stop> {
+        match expr {
+            MathValue::Integer(integer) => Ok(integer.value.expect("Integer literal without a value")),
+            MathValue::Identifier(ident) => Ok(self.get(&ident.info, frame)),
+
+            // the operands are evaluated from left to right
+            MathValue::Operation(oper) => {
+                let mut operands = vec!();
+                for operand in oper.operands() {
+                    operands.push(self.eval(operand, frame)?);
+                }
+
+                let value = match oper.as_ref() {
+                    OperationType::Add(_) => operands[0].wrapping_add(operands[1]),
+                    OperationType::Sub(_) => operands[0].wrapping_sub(operands[1]),
+                    OperationType::Mult(_) => operands[0].wrapping_mul(operands[1]),
+                    OperationType::Div(div) => {
+                        if operands[1] == 0 {
+                            let line = first_token(&div.value_2).line;
+                            return Err(Stop::Error(Error { line, msg: "Division by zero".to_string() }));
+                        }
+
+                        operands[0].wrapping_div(operands[1])
+                    },
+                    OperationType::Negate(_) => operands[0].wrapping_neg(),
+                };
+
+                Ok(value)
+            },
+
+            MathValue::Call(call) => self.eval_call(call, frame),
+        }
+    }
+
+    fn eval_call(&mut self, call: &'a NodeStmtFunctionCall, frame: &mut Frame<'a>) -> Result<i64, Stop> {
+        let args = self.eval_args(call, frame)?;
+
+        if self.depth == MAX_DEPTH {
+            let msg = format!("Calls are nested more than {} deep, the stack would overflow", MAX_DEPTH);
+            return Err(Stop::Error(Error { line: call.identifier.line, msg }));
+        }
+
+        self.depth += 1;
+        let value = self.call(self.functions[call.identifier.info.as_str()], args);
+        self.depth -= 1;
+
+        return value;
+    }
+
+    fn eval_args(&mut self, call: &'a NodeStmtFunctionCall, frame: &mut Frame<'a>) -> Result<Vec<i64>, Stop> {
+        let mut args = vec!();
+        for arg in &call.args {
+            args.push(self.eval(arg, frame)?);
+        }
+
+        return Ok(args);
+    }
+
+    /// Runs the function, and any it tail calls, in a new frame
+    fn call(&mut self, mut function: &'a NodeStmtFunction, mut args: Vec<i64>) -> Result<i64, Stop> {
+        loop {
+            let locals = function.args.iter()
+                .map(|param| param.identifier.info.as_str())
+                .zip(args)
+                .collect();
+
+            match self.run_scope(&function.scope.statements, &mut Some(locals))? {
+                // falling off the end returns 0
+                Flow::Next => return Ok(0),
+                Flow::Return(value) => return Ok(value),
+                Flow::TailCall(next, next_args) => {
+                    function = next;
+                    args = next_args;
+                },
+            }
+        }
+    }
+
+    /// A global that hasn't been declared yet is still 0
+    fn get(&self, name: &str, frame: &Frame<'a>) -> i64 {
+        if let Some(value) = frame.as_ref().and_then(|locals| locals.get(name)) {
+            return *value;
+        }
+
+        return self.globals.get(name).copied().unwrap_or(0);
+    }
+
+    fn set(&mut self, name: &'a str, value: i64, frame: &mut Frame<'a>) {
+        if let Some(local) = frame.as_mut().and_then(|locals| locals.get_mut(name)) {
+            *local = value;
+            return;
+        }
+
+        match self.globals.get_mut(name) {
+            Some(global) => *global = value,
+            None => { self.globals.insert(name.to_string(), value); },
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::fmt;
+
+use crate::parser::Inline;
+
+pub mod lower;
+pub use lower::lower_program;
+
+/// A virtual register holding an intermediate value. Every temp is assigned exactly once,
+/// by the instruction that names it as its `dest`
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Temp(pub usize);
+
+/// Where a variable lives
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq, Hash)]
+pub enum Var {
+    /// A top level variable, visible to every function, an index into `Module::globals`
+    Global(usize),
+    /// A parameter or variable of the current function, an index into `Function::locals`
+    Local(usize),
+}
+
+/// The input of an instruction. Every value in the language is a 64 bit integer
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Operand {
+    Temp(Temp),
+    Const(i64),
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum UnaryOp {
+    Neg,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum Instr {
+    Load { dest: Temp, var: Var },
+    Store { var: Var, value: Operand },
+    Binary { dest: Temp, op: BinaryOp, lhs: Operand, rhs: Operand },
+    Unary { dest: Temp, op: UnaryOp, value: Operand },
+    Copy { dest: Temp, value: Operand },
+    /// `dest` is the return value, if the call is part of an expression
+    Call { dest: Option<Temp>, func: String, args: Vec<Operand> },
+    /// A call to an extern function, the way C calls functions
+    CallExtern { dest: Option<Temp>, func: String, args: Vec<Operand> },
+    PutChar { value: Operand },
+}
+
+/// How control leaves a block
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum Terminator {
+    Return(Operand),
+    Exit(Operand),
+    /// Calls a function and returns what it returns, reusing the frame of the caller
+    TailCall { func: String, args: Vec<Operand> },
+}
+
+/// A straight line of instructions, only the terminator can leave it
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Block {
+    pub instrs: Vec<Instr>,
+    pub terminator: Terminator,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Function {
+    pub name: String,
+    /// The first `params` locals are the parameters, in order
+    pub params: usize,
+    pub locals: Vec<String>,
+    /// The number of temps used, they are numbered from 0
+    pub temps: usize,
+    /// The first block is the entry, code after an `exit` starts a new block
+    pub blocks: Vec<Block>,
+    pub inline: Inline,
+}
+
+/// A function from outside the program, declared with `extern fn`
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Extern {
+    pub name: String,
+    pub params: Vec<String>,
+    /// Whether it was declared `-> int`
+    pub returns: bool,
+}
+
+/// The whole program, the top level code is lowered into `main`
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Module {
+    pub globals: Vec<String>,
+    pub main: Function,
+    pub functions: Vec<Function>,
+    pub externs: Vec<Extern>,
+}
+
+impl Instr {
+    /// The operands the instruction reads
+    pub fn operands(&self) -> Vec<Operand> {
+        match self {
+            Instr::Load { .. } => vec!(),
+            Instr::Store { value, .. } => vec!(*value),
+            Instr::Binary { lhs, rhs, .. } => vec!(*lhs, *rhs),
+            Instr::Unary { value, .. } | Instr::Copy { value, .. } => vec!(*value),
+            Instr::Call { args, .. } | Instr::CallExtern { args, .. } => args.clone(),
+            Instr::PutChar { value } => vec!(*value),
+        }
+    }
+
+    /// The temp the instruction assigns to, if any
+    pub fn dest(&self) -> Option<Temp> {
+        match self {
+            Instr::Load { dest, .. } | Instr::Binary { dest, .. } | Instr::Unary { dest, .. } | Instr::Copy { dest, .. } => Some(*dest),
+            Instr::Call { dest, .. } | Instr::CallExtern { dest, .. } => *dest,
+            Instr::Store { .. } | Instr::PutChar { .. } => None,
+        }
+    }
+
+    /// The variable the instruction reads or writes, if any
+    pub fn var(&self) -> Option<Var> {
+        match self {
+            Instr::Load { var, .. } | Instr::Store { var, .. } => Some(*var),
+            _ => None,
+        }
+    }
+}
+
+impl Terminator {
+    /// The operands the terminator reads
+    pub fn operands(&self) -> Vec<Operand> {
+        match self {
+            Terminator::Return(value) | Terminator::Exit(value) => vec!(*value),
+            Terminator::TailCall { args, .. } => args.clone(),
+        }
+    }
+}
+
+impl Function {
+    pub fn var_name<'a>(&'a self, module: &'a Module, var: Var) -> &'a str {
+        match var {
+            Var::Global(index) => &module.globals[index],
+            Var::Local(index) => &self.locals[index],
+        }
+    }
+}
+
+impl fmt::Display for Temp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "t{}", self.0)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Temp(temp) => write!(f, "{}", temp),
+            Operand::Const(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            BinaryOp::Add => "add",
+            BinaryOp::Sub => "sub",
+            BinaryOp::Mul => "mul",
+            BinaryOp::Div => "div",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnaryOp::Neg => write!(f, "neg"),
+        }
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for global in &self.globals {
+            writeln!(f, "global @{}", global)?;
+        }
+
+        for external in &self.externs {
+            let returns = if external.returns { " -> int" } else { "" };
+            writeln!(f, "extern fn {}({}){}", external.name, external.params.join(", "), returns)?;
+        }
+
+        self.fmt_function(f, &self.main)?;
+        for function in &self.functions {
+            self.fmt_function(f, function)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Module {
+    fn fmt_function(&self, f: &mut fmt::Formatter, function: &Function) -> fmt::Result {
+        let var = |var: Var| match var {
+            Var::Global(_) => format!("@{}", function.var_name(self, var)),
+            Var::Local(_) => format!("%{}", function.var_name(self, var)),
+        };
+
+        let params: Vec<String> = (0..function.params).map(|i| var(Var::Local(i))).collect();
+        writeln!(f, "\nfn {}({}) {{", function.name, params.join(", "))?;
+
+        for (i, block) in function.blocks.iter().enumerate() {
+            writeln!(f, "b{}:", i)?;
+
+            for instr in &block.instrs {
+                match instr {
+                    Instr::Load { dest, var: v } => writeln!(f, "    {} = load {}", dest, var(*v))?,
+                    Instr::Store { var: v, value } => writeln!(f, "    store {}, {}", var(*v), value)?,
+                    Instr::Binary { dest, op, lhs, rhs } => writeln!(f, "    {} = {} {}, {}", dest, op, lhs, rhs)?,
+                    Instr::Unary { dest, op, value } => writeln!(f, "    {} = {} {}", dest, op, value)?,
+                    Instr::Copy { dest, value } => writeln!(f, "    {} = copy {}", dest, value)?,
+                    Instr::Call { dest: Some(dest), func, args } => writeln!(f, "    {} = call {}({})", dest, func, join(args))?,
+                    Instr::Call { dest: None, func, args } => writeln!(f, "    call {}({})", func, join(args))?,
+                    Instr::CallExtern { dest: Some(dest), func, args } => writeln!(f, "    {} = call extern {}({})", dest, func, join(args))?,
+                    Instr::CallExtern { dest: None, func, args } => writeln!(f, "    call extern {}({})", func, join(args))?,
+                    Instr::PutChar { value } => writeln!(f, "    putchar {}", value)?,
+                }
+            }
+
+            match &block.terminator {
+                Terminator::Return(value) => writeln!(f, "    ret {}", value)?,
+                Terminator::Exit(value) => writeln!(f, "    exit {}", value)?,
+                Terminator::TailCall { func, args } => writeln!(f, "    tail call {}({})", func, join(args))?,
+            }
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+fn join(operands: &[Operand]) -> String {
+    let operands: Vec<String> = operands.iter().map(|operand| operand.to_string()).collect();
+
+    return operands.join(", ");
+}
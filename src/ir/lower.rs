@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+
+use crate::{
+    errors::Error,
+    parser::{*, math::OperationType},
+    tokenise::Token,
+};
+
+use super::{BinaryOp, Block, Extern, Function, Instr, Module, Operand, Temp, Terminator, UnaryOp, Var};
+
+/// Lowers the ast into the ir. Top level variables become globals, and every function,
+/// nested or not, becomes its own `Function` that can use globals and its own variables.
+/// Extern functions are collected wherever they are declared, their names are unique
+pub fn lower_program(program: &NodeProgram) -> Result<Module, Error> {
+    let externs = program.externs().iter()
+        .map(|extern_stmt| Extern {
+            name: extern_stmt.identifier.info.clone(),
+            params: extern_stmt.args.iter().map(|arg| arg.identifier.info.clone()).collect(),
+            returns: extern_stmt.returns,
+        })
+        .collect();
+
+    let mut lowerer = Lowerer { globals: vec!(), functions: vec!(), externs };
+
+    let mut main = FunctionBuilder::new("main".to_string(), HashMap::new(), HashMap::new());
+    main.top_level = true;
+    main.inline = Inline::Never;
+    lowerer.lower_scope(&mut main, program)?;
+
+    // falling off the end of the program exits with 0
+    let main = main.finish(Terminator::Exit(Operand::Const(0)));
+
+    Ok( Module { globals: lowerer.globals, main, functions: lowerer.functions, externs: lowerer.externs } )
+}
+
+struct Lowerer {
+    globals: Vec<String>,
+    functions: Vec<Function>,
+    externs: Vec<Extern>,
+}
+
+/// The state of the function that is currently being lowered
+struct FunctionBuilder {
+    name: String,
+    /// Whether this is the top level code, whose variables are globals
+    top_level: bool,
+    params: usize,
+    locals: Vec<String>,
+    temps: usize,
+    blocks: Vec<Block>,
+    instrs: Vec<Instr>,
+    /// The variables that can be used from this function
+    variables: HashMap<String, Var>,
+    /// Variables of enclosing functions, and the function they belong to. These can't be
+    /// used, they are only kept for a better error message
+    enclosing: HashMap<String, String>,
+    inline: Inline,
+}
+
+impl FunctionBuilder {
+    fn new(name: String, variables: HashMap<String, Var>, enclosing: HashMap<String, String>) -> FunctionBuilder {
+        FunctionBuilder {
+            name,
+            top_level: false,
+            params: 0,
+            locals: vec!(),
+            temps: 0,
+            blocks: vec!(),
+            instrs: vec!(),
+            variables,
+            enclosing,
+            inline: Inline::Auto,
+        }
+    }
+
+    fn new_temp(&mut self) -> Temp {
+        self.temps += 1;
+        Temp(self.temps - 1)
+    }
+
+    fn end_block(&mut self, terminator: Terminator) {
+        let instrs = std::mem::take(&mut self.instrs);
+        self.blocks.push(Block { instrs, terminator });
+    }
+
+    fn finish(mut self, terminator: Terminator) -> Function {
+        self.end_block(terminator);
+
+        Function {
+            name: self.name,
+            params: self.params,
+            locals: self.locals,
+            temps: self.temps,
+            blocks: self.blocks,
+            inline: self.inline,
+        }
+    }
+
+    fn get_var(&self, identifier: &Token) -> Result<Var, Error> {
+        if let Some(var) = self.variables.get(&identifier.info) {
+            return Ok(*var);
+        }
+
+        let msg = match self.enclosing.get(&identifier.info) {
+            Some(outer) => format!(
+                "Function {} can't use {}, it is a variable of the enclosing function {}",
+                self.name, identifier.info, outer,
+            ),
+            None => format!("Variable {} has not been declared", identifier.info),
+        };
+
+        Err( Error { line: identifier.line, msg } )
+    }
+}
+
+impl Lowerer {
+    fn lower_scope(&mut self, builder: &mut FunctionBuilder, program: &NodeProgram) -> Result<(), Error> {
+        for stmt in &program.statements {
+            match stmt {
+                NodeStatements::Declare(declare_stmt) => {
+                    self.lower_declare(builder, declare_stmt)?;
+                },
+                NodeStatements::Set(set_stmt) => {
+                    let var = builder.get_var(&set_stmt.identifier)?;
+                    let value = self.lower_expression(builder, &set_stmt.expression)?;
+
+                    builder.instrs.push(Instr::Store { var, value });
+                },
+                NodeStatements::Exit(exit_stmt) => {
+                    let value = self.lower_expression(builder, &exit_stmt.expression)?;
+
+                    // anything after the exit goes into a new block, which is never reached
+                    builder.end_block(Terminator::Exit(value));
+                },
+                NodeStatements::PutChar(putchar_stmt) => {
+                    let value = self.lower_expression(builder, &putchar_stmt.expression)?;
+
+                    builder.instrs.push(Instr::PutChar { value });
+                },
+                NodeStatements::Function(func_stmt) => {
+                    self.lower_function(builder, func_stmt)?;
+                },
+                // they were all collected before lowering anything
+                NodeStatements::Extern(_) => (),
+                NodeStatements::FunctionCall(func_call_stmt) => {
+                    self.lower_call(builder, func_call_stmt, None)?;
+                },
+                NodeStatements::Return(return_stmt) => {
+                    if builder.top_level {
+                        let msg = "`return` can only be used in a function, use `exit` to end the program".to_string();
+                        return Err( Error { line: return_stmt.token.line, msg } );
+                    }
+
+                    let value = match &return_stmt.expression {
+                        Some(expression) => self.lower_expression(builder, expression)?,
+                        None => Operand::Const(0),
+                    };
+
+                    // the same as exit, anything after it is never reached
+                    builder.end_block(Terminator::Return(value));
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lower_declare(&mut self, builder: &mut FunctionBuilder, declare_stmt: &NodeStmtDeclare) -> Result<(), Error> {
+        let identifier = &declare_stmt.identifier;
+
+        // a local shadows a global with the same name, the same as a parameter does
+        let declared = match builder.variables.get(&identifier.info) {
+            Some(Var::Global(_)) => builder.top_level,
+            Some(Var::Local(_)) => true,
+            None => false,
+        };
+
+        if declared {
+            return Err( Error { line: identifier.line, msg: format!("Variable {} has already been declared!", identifier.info) } );
+        }
+
+        // lowered before the variable exists, so it can't refer to itself
+        let value = match &declare_stmt.expression {
+            Some(expression) => self.lower_expression(builder, expression)?,
+            None => Operand::Const(0),
+        };
+
+        // variables in the top level code are globals, so functions can use them
+        let var = if builder.top_level {
+            self.globals.push(identifier.info.clone());
+            Var::Global(self.globals.len() - 1)
+        } else {
+            builder.locals.push(identifier.info.clone());
+            Var::Local(builder.locals.len() - 1)
+        };
+
+        builder.variables.insert(identifier.info.clone(), var);
+        builder.instrs.push(Instr::Store { var, value });
+
+        Ok(())
+    }
+
+    fn lower_function(&mut self, outer: &FunctionBuilder, func_stmt: &NodeStmtFunction) -> Result<(), Error> {
+        let mut variables = HashMap::new();
+        let mut enclosing = outer.enclosing.clone();
+
+        for (name, var) in &outer.variables {
+            match var {
+                Var::Global(_) => { variables.insert(name.clone(), *var); },
+                Var::Local(_) => { enclosing.insert(name.clone(), outer.name.clone()); },
+            }
+        }
+
+        let mut builder = FunctionBuilder::new(func_stmt.identifier.info.clone(), variables, enclosing);
+        builder.inline = func_stmt.inline;
+
+        for arg in &func_stmt.args {
+            if builder.locals.contains(&arg.identifier.info) {
+                return Err( Error { line: arg.identifier.line, msg: format!("Parameter {} has already been declared!", arg.identifier.info) } );
+            }
+
+            // parameters shadow globals with the same name
+            builder.locals.push(arg.identifier.info.clone());
+            builder.variables.insert(arg.identifier.info.clone(), Var::Local(builder.locals.len() - 1));
+        }
+        builder.params = func_stmt.args.len();
+
+        self.lower_scope(&mut builder, &func_stmt.scope)?;
+
+        // falling off the end of a function returns 0
+        let function = builder.finish(Terminator::Return(Operand::Const(0)));
+        self.functions.push(function);
+
+        Ok(())
+    }
+
+    fn lower_expression(&mut self, builder: &mut FunctionBuilder, expr: &MathValue) -> Result<Operand, Error> {
+        let operand = match expr {
+            MathValue::Integer(integer) => Operand::Const(integer.value.expect("Integer literal without a value")),
+
+            MathValue::Identifier(ident) => {
+                let var = builder.get_var(ident)?;
+                let dest = builder.new_temp();
+
+                builder.instrs.push(Instr::Load { dest, var });
+                Operand::Temp(dest)
+            },
+
+            MathValue::Operation(oper) => {
+                let (op, value_1, value_2) = match oper.as_ref() {
+                    OperationType::Add(add) => (BinaryOp::Add, &add.value_1, &add.value_2),
+                    OperationType::Sub(sub) => (BinaryOp::Sub, &sub.value_1, &sub.value_2),
+                    OperationType::Mult(mult) => (BinaryOp::Mul, &mult.value_1, &mult.value_2),
+                    OperationType::Div(div) => (BinaryOp::Div, &div.value_1, &div.value_2),
+
+                    OperationType::Negate(negate) => {
+                        let value = self.lower_expression(builder, &negate.value)?;
+                        let dest = builder.new_temp();
+
+                        builder.instrs.push(Instr::Unary { dest, op: UnaryOp::Neg, value });
+                        return Ok(Operand::Temp(dest));
+                    },
+                };
+
+                let lhs = self.lower_expression(builder, value_1)?;
+                let rhs = self.lower_expression(builder, value_2)?;
+                let dest = builder.new_temp();
+
+                builder.instrs.push(Instr::Binary { dest, op, lhs, rhs });
+                Operand::Temp(dest)
+            },
+
+            MathValue::Call(call) => {
+                let dest = builder.new_temp();
+
+                self.lower_call(builder, call, Some(dest))?;
+                Operand::Temp(dest)
+            },
+        };
+
+        Ok(operand)
+    }
+
+    /// The arguments are lowered from left to right, before the call
+    fn lower_call(&mut self, builder: &mut FunctionBuilder, call: &NodeStmtFunctionCall, dest: Option<Temp>) -> Result<(), Error> {
+        let mut args = vec!();
+        for arg in &call.args {
+            args.push(self.lower_expression(builder, arg)?);
+        }
+
+        let func = call.identifier.info.clone();
+        if self.externs.iter().any(|external| external.name == func) {
+            builder.instrs.push(Instr::CallExtern { dest, func, args });
+        } else {
+            builder.instrs.push(Instr::Call { dest, func, args });
+        }
+
+        Ok(())
+    }
+}
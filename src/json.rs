@@ -0,0 +1,280 @@
+use crate::{
+    parser::{*, math::OperationType},
+    tokenise::Token,
+};
+
+/// A JSON value. Objects keep their keys in insertion order, so the output is stable
+#[derive(Debug)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    /// Formats the value with two space indentation
+    pub fn pretty(&self) -> String {
+        let mut output = String::new();
+        self.write(&mut output, 0);
+
+        return output;
+    }
+
+    fn write(&self, output: &mut String, indent: usize) {
+        match self {
+            Json::Null => output.push_str("null"),
+            Json::Bool(value) => output.push_str(&value.to_string()),
+            Json::Int(value) => output.push_str(&value.to_string()),
+            Json::Str(string) => write_string(output, string),
+
+            Json::Array(values) => {
+                if values.is_empty() {
+                    output.push_str("[]");
+                    return;
+                }
+
+                output.push_str("[\n");
+                for (i, value) in values.iter().enumerate() {
+                    output.push_str(&"  ".repeat(indent + 1));
+                    value.write(output, indent + 1);
+
+                    if i + 1 < values.len() {
+                        output.push(',');
+                    }
+                    output.push('\n');
+                }
+                output.push_str(&"  ".repeat(indent));
+                output.push(']');
+            },
+
+            Json::Object(fields) => {
+                if fields.is_empty() {
+                    output.push_str("{}");
+                    return;
+                }
+
+                output.push_str("{\n");
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    output.push_str(&"  ".repeat(indent + 1));
+                    write_string(output, key);
+                    output.push_str(": ");
+                    value.write(output, indent + 1);
+
+                    if i + 1 < fields.len() {
+                        output.push(',');
+                    }
+                    output.push('\n');
+                }
+                output.push_str(&"  ".repeat(indent));
+                output.push('}');
+            },
+        }
+    }
+}
+
+fn write_string(output: &mut String, string: &str) {
+    output.push('"');
+
+    for c in string.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+
+    output.push('"');
+}
+
+fn optional_string(string: &Option<String>) -> Json {
+    match string {
+        Some(string) => Json::Str(string.clone()),
+        None => Json::Null,
+    }
+}
+
+/// Converts a compiler structure to JSON, for `--emit=tokens` and `--emit=ast`
+pub trait ToJson {
+    fn to_json(&self) -> Json;
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> Json {
+        Json::Array(self.iter().map(|item| item.to_json()).collect())
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> Json {
+        match self {
+            Some(item) => item.to_json(),
+            None => Json::Null,
+        }
+    }
+}
+
+/// The location of a token in the source code
+fn span(token: &Token) -> Json {
+    Json::Object(vec!(
+        ("line", Json::Int(token.line as i64)),
+        ("column", Json::Int(token.column as i64)),
+        ("length", Json::Int(token.info.chars().count() as i64)),
+    ))
+}
+
+impl ToJson for Token {
+    fn to_json(&self) -> Json {
+        let value = match self.value {
+            Some(value) => Json::Int(value),
+            None => Json::Null,
+        };
+
+        Json::Object(vec!(
+            ("kind", Json::Str(format!("{:?}", self.token))),
+            ("text", Json::Str(self.info.clone())),
+            ("value", value),
+            ("doc", optional_string(&self.doc)),
+            ("span", span(self)),
+        ))
+    }
+}
+
+impl ToJson for NodeProgram {
+    fn to_json(&self) -> Json {
+        Json::Object(vec!(
+            ("kind", Json::Str("Program".to_string())),
+            ("statements", self.statements.to_json()),
+        ))
+    }
+}
+
+impl ToJson for NodeStatements {
+    fn to_json(&self) -> Json {
+        match self {
+            NodeStatements::Exit(exit_stmt) => Json::Object(vec!(
+                ("kind", Json::Str("Exit".to_string())),
+                ("expression", exit_stmt.expression.to_json()),
+            )),
+
+            NodeStatements::PutChar(putchar_stmt) => Json::Object(vec!(
+                ("kind", Json::Str("PutChar".to_string())),
+                ("expression", putchar_stmt.expression.to_json()),
+            )),
+
+            NodeStatements::Declare(declare_stmt) => declare_stmt.to_json(),
+
+            NodeStatements::Set(set_stmt) => Json::Object(vec!(
+                ("kind", Json::Str("Set".to_string())),
+                ("name", Json::Str(set_stmt.identifier.info.clone())),
+                ("span", span(&set_stmt.identifier)),
+                ("expression", set_stmt.expression.to_json()),
+            )),
+
+            NodeStatements::Function(func_stmt) => Json::Object(vec!(
+                ("kind", Json::Str("Function".to_string())),
+                ("name", Json::Str(func_stmt.identifier.info.clone())),
+                ("span", span(&func_stmt.identifier)),
+                ("doc", optional_string(&func_stmt.doc)),
+                ("inline", Json::Str(match func_stmt.inline {
+                    Inline::Auto => "auto",
+                    Inline::Always => "always",
+                    Inline::Never => "never",
+                }.to_string())),
+                ("args", func_stmt.args.to_json()),
+                ("scope", func_stmt.scope.to_json()),
+            )),
+
+            NodeStatements::Extern(extern_stmt) => Json::Object(vec!(
+                ("kind", Json::Str("Extern".to_string())),
+                ("name", Json::Str(extern_stmt.identifier.info.clone())),
+                ("span", span(&extern_stmt.identifier)),
+                ("doc", optional_string(&extern_stmt.doc)),
+                ("args", extern_stmt.args.to_json()),
+                ("returns", Json::Bool(extern_stmt.returns)),
+            )),
+
+            NodeStatements::FunctionCall(func_call_stmt) => func_call_stmt.to_json(),
+
+            NodeStatements::Return(return_stmt) => Json::Object(vec!(
+                ("kind", Json::Str("Return".to_string())),
+                ("span", span(&return_stmt.token)),
+                ("expression", return_stmt.expression.to_json()),
+            )),
+        }
+    }
+}
+
+impl ToJson for NodeStmtFunctionCall {
+    fn to_json(&self) -> Json {
+        Json::Object(vec!(
+            ("kind", Json::Str("FunctionCall".to_string())),
+            ("name", Json::Str(self.identifier.info.clone())),
+            ("span", span(&self.identifier)),
+            ("args", self.args.to_json()),
+        ))
+    }
+}
+
+impl ToJson for NodeStmtDeclare {
+    fn to_json(&self) -> Json {
+        Json::Object(vec!(
+            ("kind", Json::Str("Declare".to_string())),
+            ("name", Json::Str(self.identifier.info.clone())),
+            ("span", span(&self.identifier)),
+            ("doc", optional_string(&self.doc)),
+            ("expression", self.expression.to_json()),
+        ))
+    }
+}
+
+impl ToJson for MathValue {
+    fn to_json(&self) -> Json {
+        match self {
+            MathValue::Integer(integer) => Json::Object(vec!(
+                ("kind", Json::Str("Integer".to_string())),
+                ("value", Json::Int(integer.value.expect("Integer literal without a value"))),
+                ("text", Json::Str(integer.info.clone())),
+                ("span", span(integer)),
+            )),
+
+            MathValue::Identifier(ident) => Json::Object(vec!(
+                ("kind", Json::Str("Identifier".to_string())),
+                ("name", Json::Str(ident.info.clone())),
+                ("span", span(ident)),
+            )),
+
+            MathValue::Operation(oper) => oper.to_json(),
+
+            MathValue::Call(call) => call.to_json(),
+        }
+    }
+}
+
+impl ToJson for OperationType {
+    fn to_json(&self) -> Json {
+        let binary = |kind: &str, value_1: &MathValue, value_2: &MathValue| Json::Object(vec!(
+            ("kind", Json::Str(kind.to_string())),
+            ("value_1", value_1.to_json()),
+            ("value_2", value_2.to_json()),
+        ));
+
+        match self {
+            OperationType::Add(add) => binary("Add", &add.value_1, &add.value_2),
+            OperationType::Sub(sub) => binary("Sub", &sub.value_1, &sub.value_2),
+            OperationType::Mult(mult) => binary("Mult", &mult.value_1, &mult.value_2),
+            OperationType::Div(div) => binary("Div", &div.value_1, &div.value_2),
+
+            OperationType::Negate(negate) => Json::Object(vec!(
+                ("kind", Json::Str("Negate".to_string())),
+                ("value", negate.value.to_json()),
+            )),
+        }
+    }
+}
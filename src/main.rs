@@ -1,6 +1,7 @@
 use std::env;
 use std::fs::File;
 use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 use std::process::Command;
 
@@ -11,22 +12,108 @@ pub mod parser;
 use parser::Parser;
 
 mod code_gen;
-use code_gen::CodeGen;
+use code_gen::{aarch64, asm, c, elf, encode, jit, llvm, wasm, CodeGen, Output};
+use code_gen::target::Target;
 
 mod errors;
 use errors::{external_error, inline_error};
 
+mod resolve;
+
+mod interpret;
+
+mod bytecode;
+
+mod repl;
+
+mod ir;
+
+mod opt;
+use opt::PassManager;
+
+mod json;
+use json::ToJson;
+
 #[derive(PartialEq)]
 enum Options {
     // delete the asm and object files
     Clean,
     Debug,
+    // print a stage of the compiler as json and stop
+    Emit(Emit),
+    // -O0, -O1 or -O2
+    OptLevel(u8),
+    // turn a single optimisation pass off, to find out which one breaks a program
+    DisablePass(String),
+    // list the passes in the order they run and stop
+    PrintPasses,
+    // how the instructions become machine code
+    Assembler(Assembler),
+    // make an object file that exports the functions and the top level code as init,
+    // instead of an executable
+    Object,
+    // what goes in front of the names of the exported functions
+    ExportPrefix(String),
+    // what to generate code for
+    Target(Target),
+    // run the program with the tree-walking interpreter instead of the bytecode vm
+    Interpret,
+    // run the program as machine code in memory
+    Jit,
+    // call a function after the top level code, with the jit
+    Call(String),
+}
+
+/// What to do with the program, the first argument picks it
+#[derive(PartialEq)]
+enum Subcommand {
+    Compile,
+    /// `run`, execute the program or a saved bytecode file without writing anything
+    Run,
+    /// `repl`, run statements as they are typed in
+    Repl,
+}
+
+/// What `--emit` prints
+#[derive(PartialEq)]
+enum Emit {
+    Tokens,
+    Ast,
+    Ir,
+    /// The disassembled bytecode
+    Bytecode,
+}
+
+/// What `--assembler` picks
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+enum Assembler {
+    /// The encoder in `code_gen::encode`, no tools are needed
+    Builtin,
+    Nasm,
+    /// The GNU assembler, `as`
+    Gas,
+    /// nasm or `as`, whichever is installed
+    Auto,
+}
+
+impl Assembler {
+    /// The extension of the asm file it reads
+    fn extension(&self) -> &'static str {
+        match self {
+            Assembler::Nasm => "asm",
+            _ => "s",
+        }
+    }
 }
 
 /// A struct with the io paths, and the command line options
 struct Settings {
+    subcommand: Subcommand,
     f_in: String,
     f_out: String,
+    /// What comes after the input file, `run --call` passes them to the function
+    args: Vec<String>,
     options: Vec<Options>,
 }
 
@@ -37,11 +124,36 @@ fn collect_settings() -> Settings {
     let mut options: Vec<Options> = vec!();
     let mut arguments: Vec<String> = vec!();
 
-    // loop but ignore first arg
-    for arg in args[1..].into_iter() {
+    let subcommand = match args.get(1).map(String::as_str) {
+        Some("run") => Subcommand::Run,
+        Some("repl") => Subcommand::Repl,
+        _ => Subcommand::Compile,
+    };
+    let first = if subcommand == Subcommand::Compile { 1 } else { 2 };
+
+    // loop but ignore first arg, and the subcommand
+    for arg in args[first..].iter() {
+        // long options like `--emit=ast`
+        if let Some(option) = arg.strip_prefix("--") {
+            options.push(parse_long_option(option));
+        }
+
+        // the optimisation level, `-O2`
+        else if let Some(level) = arg.strip_prefix("-O") {
+            match level.parse::<u8>() {
+                Ok(level) if level <= opt::pass_manager::MAX_LEVEL => options.push(Options::OptLevel(level)),
+                _ => external_error(&format!("Unknown optimisation level -O{}, expected 0 to {}", level, opt::pass_manager::MAX_LEVEL)),
+            }
+        }
+
+        // a negative number is an argument for `--call`, not a flag
+        else if arg.parse::<i64>().is_ok() {
+            arguments.push(arg.to_string());
+        }
+
         // if its an option
-        if arg.chars().nth(0).expect("Arg was empty") == '-' {
-            for c in arg[1..].chars().into_iter() {
+        else if let Some(flags) = arg.strip_prefix('-') {
+            for c in flags.chars() {
 
                 options.push(
                     match c {
@@ -62,7 +174,7 @@ fn collect_settings() -> Settings {
         }
     }
 
-    if arguments.len() < 1 {
+    if arguments.is_empty() {
         //external_error(&format!("Usage: {} path/to/file.at", args[0]));
         arguments.push("test.at".to_string());
     }
@@ -86,11 +198,122 @@ fn collect_settings() -> Settings {
         }
     }
 
-    let settings = Settings { f_in, f_out, options };
+    if subcommand == Subcommand::Compile && options.contains(&Options::Interpret) {
+        external_error("--interpret only works with `run`");
+    }
+
+    if subcommand == Subcommand::Compile && options.contains(&Options::Jit) {
+        external_error("--jit only works with `run`");
+    }
+
+    if options.iter().any(|option| matches!(option, Options::Call(_))) && !options.contains(&Options::Jit) {
+        external_error("--call only works with --jit");
+    }
+
+    let args = arguments[1..].to_vec();
+    let settings = Settings { subcommand, f_in, f_out, args, options };
 
     return settings;
 }
 
+/// Parses an option given as `--name=value`, without the leading dashes
+fn parse_long_option(option: &str) -> Options {
+    let (name, value) = option.split_once('=').unwrap_or((option, ""));
+
+    match name {
+        "emit" => match value {
+            "tokens" => Options::Emit(Emit::Tokens),
+            "ast" => Options::Emit(Emit::Ast),
+            "ir" => Options::Emit(Emit::Ir),
+            "bytecode" => Options::Emit(Emit::Bytecode),
+
+            _ => external_error(&format!("Unknown value for --emit: `{}`, expected `tokens`, `ast`, `ir` or `bytecode`", value)),
+        },
+
+        "disable-pass" if !value.is_empty() => Options::DisablePass(value.to_string()),
+        "print-passes" => Options::PrintPasses,
+        "assembler" => match value {
+            "builtin" => Options::Assembler(Assembler::Builtin),
+            "nasm" => Options::Assembler(Assembler::Nasm),
+            "gas" => Options::Assembler(Assembler::Gas),
+            "auto" => Options::Assembler(Assembler::Auto),
+
+            _ => external_error(&format!("Unknown value for --assembler: `{}`, expected `builtin`, `nasm`, `gas` or `auto`", value)),
+        },
+        // short for `--assembler=nasm`
+        "nasm" => Options::Assembler(Assembler::Nasm),
+        "object" => Options::Object,
+        "export-prefix" => Options::ExportPrefix(value.to_string()),
+        "interpret" => Options::Interpret,
+        "jit" => Options::Jit,
+        "call" if !value.is_empty() => Options::Call(value.to_string()),
+        "target" => match Target::from_name(value) {
+            Some(target) => Options::Target(target),
+            None => {
+                let names: Vec<String> = Target::ALL.iter().map(|target| format!("`{}`", target.name())).collect();
+                external_error(&format!("Unknown value for --target: `{}`, expected one of {}", value, names.join(", ")))
+            },
+        },
+
+        _ => external_error(&format!("Unknown option --{}", name)),
+    }
+}
+
+/// Picks the passes from the optimisation level and the disabled passes, the last level
+/// given wins and the default is -O1
+fn pass_manager(settings: &Settings) -> PassManager {
+    let mut level = 1;
+    let mut disabled: Vec<String> = vec!();
+
+    for option in &settings.options {
+        match option {
+            Options::OptLevel(given) => level = *given,
+            Options::DisablePass(name) => disabled.push(name.clone()),
+            _ => (),
+        }
+    }
+
+    let mut passes = match PassManager::new(level, disabled, settings.options.contains(&Options::Debug)) {
+        Ok(passes) => passes,
+        Err(msg) => external_error(&msg),
+    };
+
+    // nothing in the file calls the exported functions, but they are still needed
+    if settings.options.contains(&Options::Object) || call(settings).is_some() {
+        passes.keep_functions();
+    }
+
+    return passes;
+}
+
+/// An object file exports the functions under their own names, unless a prefix is given
+fn output(settings: &Settings) -> Output {
+    if !settings.options.contains(&Options::Object) {
+        return Output::Executable;
+    }
+
+    let mut prefix = String::new();
+    for option in &settings.options {
+        if let Options::ExportPrefix(given) = option {
+            prefix = given.clone();
+        }
+    }
+
+    return Output::Object { prefix };
+}
+
+/// The function `--call` names, the last one given
+fn call(settings: &Settings) -> Option<&str> {
+    let mut name = None;
+    for option in &settings.options {
+        if let Options::Call(given) = option {
+            name = Some(given.as_str());
+        }
+    }
+
+    return name;
+}
+
 fn read_in(settings: &Settings) -> String {
     let path = Path::new(&settings.f_in);
     
@@ -107,8 +330,67 @@ fn read_in(settings: &Settings) -> String {
     return contents;
 }
 
-fn write_out(settings: &Settings, asm: &str) {
-    let path = &format!("{}.asm", &settings.f_out);
+/// The target given last, x86-64 if there isn't one
+fn target(settings: &Settings) -> Target {
+    let mut target = Target::X86_64;
+
+    for option in &settings.options {
+        if let Options::Target(given) = option {
+            target = *given;
+        }
+    }
+
+    return target;
+}
+
+/// Writes source code for another tool next to where the executable would go, for the
+/// targets that aren't built into an executable
+fn write_source(settings: &Settings, extension: &str, source: &str) {
+    let path = &format!("{}.{}", &settings.f_out, extension);
+
+    let Ok(mut output_file) = File::create(path) else {
+        external_error("Could not create output file");
+    };
+
+    write!(output_file, "{}", source).expect("Could not write output source file!");
+
+    dbg_p(format!("Wrote {}", path), settings);
+}
+
+/// The assembler given last, the built-in one if there isn't one. `auto` is decided here
+fn assembler(settings: &Settings) -> Assembler {
+    let mut assembler = Assembler::Builtin;
+
+    for option in &settings.options {
+        if let Options::Assembler(given) = option {
+            assembler = *given;
+        }
+    }
+
+    if assembler != Assembler::Auto {
+        return assembler;
+    }
+
+    if is_installed("nasm") {
+        return Assembler::Nasm;
+    }
+    if is_installed("as") {
+        return Assembler::Gas;
+    }
+
+    external_error("Neither nasm nor as is installed, leave out --assembler to use the built-in one");
+}
+
+fn is_installed(tool: &str) -> bool {
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("command -v {}", tool))
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+fn write_out(settings: &Settings, asm: &str, assembler: Assembler) {
+    let path = &format!("{}.{}", &settings.f_out, assembler.extension());
 
     let Ok(mut output_file) = File::create(path) else {
         external_error("Could not create output file");
@@ -117,36 +399,81 @@ fn write_out(settings: &Settings, asm: &str) {
     write!(output_file, "{}", asm).expect("Could not write output asm file!");
 }
 
-/// Uses `nasm` to assemble the code into an object file
-fn assemble(settings: &Settings) {
+/// Encodes the instructions and writes them as an executable, without any external tools
+fn write_executable(settings: &Settings, instructions: &[asm::Inst]) {
+    let executable = match encode::encode(instructions).and_then(|object| elf::executable(&object)) {
+        Ok(executable) => executable,
+        Err(msg) => external_error(&msg),
+    };
+
+    let output_file = write_bytes(&settings.f_out, &executable);
+
+    let permissions = std::fs::Permissions::from_mode(0o755);
+    if let Err(err) = output_file.set_permissions(permissions) {
+        external_error(&format!("Could not make {} executable due to {}", &settings.f_out, err));
+    }
+
+    dbg_p("Wrote executable", settings);
+}
+
+/// Encodes the instructions into an object file, the same one `assemble` makes
+fn write_object(settings: &Settings, instructions: &[asm::Inst]) {
+    let relocatable = match encode::encode(instructions).and_then(|object| elf::relocatable(&object)) {
+        Ok(relocatable) => relocatable,
+        Err(msg) => external_error(&msg),
+    };
+
+    write_bytes(&format!("{}.o", &settings.f_out), &relocatable);
+
+    dbg_p("Wrote object file", settings);
+}
+
+fn write_bytes(path: &str, bytes: &[u8]) -> File {
+    let Ok(mut output_file) = File::create(path) else {
+        external_error("Could not create output file");
+    };
+
+    output_file.write_all(bytes).expect("Could not write output file!");
+
+    return output_file;
+}
+
+/// Uses `nasm` or `as` to assemble the code into an object file
+fn assemble(settings: &Settings, assembler: Assembler) {
     let out_path = &settings.f_out;
 
-    let nasm_output = Command::new("sh")
+    let command = match assembler {
+        Assembler::Nasm => format!("nasm -felf64 {}.asm -o {}.o", out_path, out_path),
+        _ => format!("as --64 {}.s -o {}.o", out_path, out_path),
+    };
+
+    let assembler_output = Command::new("sh")
         .arg("-c")
-        .arg(&format!("nasm -felf64 {}.asm -o {}.o", out_path, out_path))
+        .arg(command)
         .output()
-        .expect("Could not execute nasm command");
+        .expect("Could not execute the assembler");
 
-    if nasm_output.status.success() {
+    if assembler_output.status.success() {
         dbg_p("Assembled", settings);
     } else {
         external_error(
             &format!("Did not assemble:\n{}",
-                String::from_utf8(nasm_output.stderr).unwrap()
+                String::from_utf8(assembler_output.stderr).unwrap()
             )
         );
     }
 }
 
-/// Uses `ld` to link the object file and make an executable
-fn link(settings: &Settings) {
+/// Links the object file into an executable, with `cc` if it needs the C library
+fn link(settings: &Settings, libc: bool) {
     let out_path = &settings.f_out;
+    let linker = if libc { "cc" } else { "ld" };
 
     let linker_output = Command::new("sh")
         .arg("-c")
-        .arg(&format!("ld -o {} {}.o", out_path, out_path))
+        .arg(format!("{} -o {} {}.o", linker, out_path, out_path))
         .output()
-        .expect("Could not execute ld command");
+        .unwrap_or_else(|_| panic!("Could not execute {} command", linker));
 
     if linker_output.status.success() {
         dbg_p("Linked", settings);
@@ -159,12 +486,12 @@ fn link(settings: &Settings) {
     }
 }
 
-fn clean_asm(settings: &Settings) {
+fn clean_asm(settings: &Settings, assembler: Assembler) {
     let out_path = &settings.f_out;
 
     let _rm_asm_output = Command::new("sh")
         .arg("-c")
-        .arg(&format!("rm {}.asm", out_path))
+        .arg(format!("rm {}.{}", out_path, assembler.extension()))
         .output()
         .expect("Could not rm asm");
     
@@ -176,7 +503,7 @@ fn clean_object(settings: &Settings) {
 
     let _rm_obj_output = Command::new("sh")
         .arg("-c")
-        .arg(&format!("rm {}.o", out_path))
+        .arg(format!("rm {}.o", out_path))
         .output()
         .expect("Could not rm object file");
 
@@ -184,14 +511,30 @@ fn clean_object(settings: &Settings) {
 }
 
 /// Removes the intermediary files, the asm code and object file
-fn clean_files(settings: &Settings) {
-    clean_asm(settings);
+fn clean_files(settings: &Settings, assembler: Assembler) {
+    clean_asm(settings, assembler);
     clean_object(settings);
 }
 
 fn main() {
     let settings = collect_settings();
 
+    if settings.subcommand == Subcommand::Repl {
+        std::process::exit(repl::run());
+    }
+
+    let passes = pass_manager(&settings);
+
+    if settings.options.contains(&Options::PrintPasses) {
+        print!("{}", passes.describe());
+        return;
+    }
+
+    // a saved program is run as it is
+    if settings.subcommand == Subcommand::Run {
+        run_saved_bytecode(&settings);
+    }
+
     let source_code = read_in(&settings);
 
     // step one: tokenise the source code
@@ -206,6 +549,11 @@ fn main() {
 
     dbg_p(&tokenised, &settings);
 
+    if settings.options.contains(&Options::Emit(Emit::Tokens)) {
+        println!("{}", tokenised.to_json().pretty());
+        return;
+    }
+
 
     // step two: parse the tokens into an ast
     let mut parser = Parser { tokens: tokenised, index: 0 };
@@ -215,31 +563,272 @@ fn main() {
         inline_error(err, &settings);
     }
 
-    let parse_tree = parse_tree.unwrap();
+    let mut parse_tree = parse_tree.unwrap();
 
     dbg_p(&parse_tree, &settings);
 
+    if settings.options.contains(&Options::Emit(Emit::Ast)) {
+        println!("{}", parse_tree.to_json().pretty());
+        return;
+    }
+
+    // make sure every call refers to a function that exists
+    if let Err(err) = resolve::check_functions(&parse_tree) {
+        inline_error(err, &settings);
+    }
+
+    // lowering checks the variables, it has to see the whole program before the ast
+    // passes fold or remove anything, or whether a program compiles would depend on -O
+    if let Err(err) = ir::lower_program(&parse_tree) {
+        inline_error(err, &settings);
+    }
+
+    // the same goes for dividing by a constant zero, which folding would find
+    if let Err(err) = opt::fold::check_divisors(&parse_tree) {
+        inline_error(err, &settings);
+    }
+
 
-    // generate asm code from the ast
-    let mut generator = CodeGen::new();
-    let asm = &generator.gen_output(&parse_tree);
+    // optimise the ast. The interpreter runs the checked ast as it was written, so what
+    // a program does there can't depend on the optimisations
+    let interpreted = settings.subcommand == Subcommand::Run && settings.options.contains(&Options::Interpret);
+    if !interpreted {
+        if let Err(err) = passes.run_ast(&mut parse_tree) {
+            inline_error(err, &settings);
+        }
+    }
+
+
+    // step three: lower the ast into the ir
+    let module = ir::lower_program(&parse_tree);
+
+    if let Err(err) = module {
+        inline_error(err, &settings);
+    }
+
+    let mut module = module.unwrap();
+
+    if settings.options.contains(&Options::Object) {
+        if let Err(err) = resolve::check_no_init(&parse_tree) {
+            inline_error(err, &settings);
+        }
+    }
+
+    // the checked ast is run by the vm or the interpreter, nothing is written and no
+    // tools are needed. The jit needs the optimised ir, it runs further down
+    if settings.subcommand == Subcommand::Run && !settings.options.contains(&Options::Jit) {
+        if let Err(err) = resolve::check_no_externs(&parse_tree, resolve::NOT_NATIVE) {
+            inline_error(err, &settings);
+        }
+
+        let result = if interpreted {
+            interpret::interpret(&parse_tree)
+        } else {
+            bytecode::vm::run(&bytecode::compile_program(&parse_tree))
+        };
+
+        match result {
+            Ok(exit_code) => std::process::exit(exit_code),
+            Err(err) => inline_error(err, &settings),
+        }
+    }
+
+    if target(&settings) == Target::Bytecode || settings.options.contains(&Options::Emit(Emit::Bytecode)) {
+        if let Err(err) = resolve::check_no_externs(&parse_tree, resolve::NOT_NATIVE) {
+            inline_error(err, &settings);
+        }
+
+        let program = bytecode::compile_program(&parse_tree);
+
+        if settings.options.contains(&Options::Emit(Emit::Bytecode)) {
+            print!("{}", program);
+            return;
+        }
+
+        write_bytes(&format!("{}.atbc", &settings.f_out), &program.to_bytes());
+        dbg_p("Wrote bytecode", &settings);
+        return;
+    }
+
+    // the c and llvm backends work from the ast, lowering it has checked the variables
+    if target(&settings) == Target::C {
+        let c_code = c::CGen::new().generate(&parse_tree);
+        write_source(&settings, "c", &c_code);
+        return;
+    }
+
+    if target(&settings) == Target::Llvm {
+        let llvm_ir = llvm::LlvmGen::new().generate(&parse_tree);
+        write_source(&settings, "ll", &llvm_ir);
+        return;
+    }
+
+    passes.run_ir(&mut module);
+
+    dbg_m(&module, settings.options.contains(&Options::Debug));
+
+    if settings.options.contains(&Options::Emit(Emit::Ir)) {
+        print!("{}", module);
+        return;
+    }
+
+    if settings.subcommand == Subcommand::Run {
+        run_jit(&settings, &passes, &module);
+    }
+
+
+    if target(&settings) == Target::Aarch64 {
+        if let Err(err) = resolve::check_no_externs(&parse_tree, resolve::NO_LIBC) {
+            inline_error(err, &settings);
+        }
+
+        let asm = aarch64::Aarch64Gen::new().gen_asm(&module);
+        write_source(&settings, "s", &asm);
+        return;
+    }
+
+    if target(&settings) == Target::Wasm {
+        let wat = wasm::WasmGen::new().gen_wat(&module);
+        write_source(&settings, "wat", &wat);
+        return;
+    }
+
+
+    // generate asm code from the ir
+    let mut generator = CodeGen::new(passes.enabled("regalloc"), output(&settings));
+    let mut instructions = generator.gen_instructions(&module);
+
+    passes.run_asm(&mut instructions);
+
+    let object_only = settings.options.contains(&Options::Object);
+    let assembler = assembler(&settings);
+    // extern functions need the C library, which only a linker can add
+    let libc = !module.externs.is_empty();
+
+    if assembler == Assembler::Builtin {
+        if object_only {
+            write_object(&settings, &instructions);
+        } else if libc {
+            write_object(&settings, &instructions);
+            link(&settings, libc);
+
+            if settings.options.contains(&Options::Clean) {
+                clean_object(&settings);
+            }
+        } else {
+            write_executable(&settings, &instructions);
+        }
+        return;
+    }
+
+    let syntax = if assembler == Assembler::Nasm { asm::Syntax::Nasm } else { asm::Syntax::Gas };
+    let asm = &asm::render(&instructions, syntax);
 
     
-    // output and generate executable
-    write_out(&settings, asm);
+    // output and generate executable with the assembler and ld
+    write_out(&settings, asm, assembler);
 
-    assemble(&settings);
+    assemble(&settings, assembler);
 
-    link(&settings);
+    // the object file is the output, only the asm can be cleaned up
+    if object_only {
+        if settings.options.contains(&Options::Clean) {
+            clean_asm(&settings, assembler);
+        }
+        return;
+    }
+
+    link(&settings, libc);
 
     if settings.options.contains(&Options::Clean) {
-        clean_files(&settings);
+        clean_files(&settings, assembler);
+    }
+}
+
+/// Runs the input with the vm if it is a bytecode file, otherwise it is source code and
+/// this does nothing
+fn run_saved_bytecode(settings: &Settings) {
+    let Ok(bytes) = std::fs::read(&settings.f_in) else {
+        external_error(&format!("Could not open {}", &settings.f_in))
+    };
+
+    if !bytecode::is_bytecode(&bytes) {
+        return;
+    }
+
+    if settings.options.contains(&Options::Interpret) {
+        external_error("A bytecode file can't be interpreted, run it without --interpret");
+    }
+
+    if settings.options.contains(&Options::Jit) {
+        external_error("A bytecode file can't be run with the jit, run it without --jit");
+    }
+
+    let program = match bytecode::Program::from_bytes(&bytes) {
+        Ok(program) => program,
+        Err(msg) => external_error(&format!("Could not load {}: {}", &settings.f_in, msg)),
+    };
+
+    // there is no source to show the line from
+    match bytecode::vm::run(&program) {
+        Ok(exit_code) => std::process::exit(exit_code),
+        Err(err) => external_error(&format!("{} in line {}", err.msg, err.line)),
     }
 }
 
-fn exit_message(message: &str) {
-    println!("{}", message);
-    std::process::exit(127)
+/// The `jit` host of `run --jit`, which writes to stdout
+struct StdoutHost {
+    out: std::io::BufWriter<std::io::Stdout>,
+}
+
+impl jit::Host for StdoutHost {
+    fn putchar(&mut self, byte: u8) {
+        self.out.write_all(&[byte]).expect("Could not write to stdout");
+    }
+}
+
+/// Compiles the module to machine code in memory and runs it. With `--call` the top
+/// level code runs first, its `exit` doesn't end anything, and then the function is
+/// called and what it returns is printed
+fn run_jit(settings: &Settings, passes: &PassManager, module: &ir::Module) -> ! {
+    let mut generator = CodeGen::new(passes.enabled("regalloc"), Output::Jit);
+    let mut instructions = generator.gen_instructions(module);
+
+    passes.run_asm(&mut instructions);
+
+    let mut jit = match encode::encode(&instructions).and_then(|object| jit::Jit::load(&object, module)) {
+        Ok(jit) => jit,
+        Err(msg) => external_error(&msg),
+    };
+
+    let mut host = StdoutHost { out: std::io::BufWriter::new(std::io::stdout()) };
+
+    let mut finish = jit.run_main(&mut host);
+
+    if let (Ok(_), Some(name)) = (&finish, call(settings)) {
+        let mut args = vec!();
+        for arg in &settings.args {
+            match arg.parse::<i64>() {
+                Ok(value) => args.push(value),
+                Err(_) => external_error(&format!("The argument {} isn't an integer", arg)),
+            }
+        }
+
+        finish = jit.call(name, &args, &mut host);
+
+        if let Ok(jit::Finish::Returned(value)) = finish {
+            writeln!(host.out, "{}", value).expect("Could not write to stdout");
+        }
+    }
+
+    host.out.flush().expect("Could not write to stdout");
+
+    match finish {
+        // only the lowest byte of the exit code is kept, like the exit syscall does
+        Ok(jit::Finish::Exited(code)) => std::process::exit(code as i32),
+        Ok(jit::Finish::Returned(_)) => std::process::exit(0),
+        Err(msg) => external_error(&msg),
+    }
 }
 
 /// Calls `dbg!` if the options contain `Options::Debug`
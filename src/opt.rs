@@ -0,0 +1,9 @@
+//! Optimisation passes, and the pass manager that picks which of them run
+
+pub mod dce;
+pub mod fold;
+pub mod inline;
+pub mod tail_calls;
+
+pub mod pass_manager;
+pub use pass_manager::PassManager;
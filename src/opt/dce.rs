@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+
+use crate::{errors::Error, parser::*};
+
+use super::pass_manager::PassOptions;
+
+/// Removes code that can never run: statements after an `exit` or `return`, or after a
+/// call to a function that always exits, and functions that aren't called from code that
+/// runs.
+///
+/// Functions defined after an exit are kept if they are called earlier, and top level
+/// declarations after an exit are kept without their value, since functions defined
+/// after them can still use them. Every function is kept when they are all exported.
+/// The whole program has been checked before this runs, so an error in removed code is
+/// still reported
+pub fn eliminate_dead_code(program: &mut NodeProgram, options: &PassOptions) -> Result<(), Error> {
+    let debug = options.debug;
+    let exiting = exiting_functions(program);
+
+    remove_unreachable_statements(program, &exiting, None, debug);
+
+    if options.keep_functions {
+        return Ok(());
+    }
+
+    // walk the call graph from the top level code
+    let mut functions = vec!();
+    collect_functions(program, &mut functions);
+
+    let mut reachable: HashSet<String> = HashSet::new();
+    let mut queue: Vec<&str> = calls(program);
+
+    while let Some(name) = queue.pop() {
+        if !reachable.insert(name.to_string()) {
+            continue;
+        }
+
+        if let Some(function) = functions.iter().find(|function| function.identifier.info == name) {
+            queue.extend(calls(&function.scope));
+        }
+    }
+
+    remove_functions(program, &reachable, debug);
+
+    return Ok(());
+}
+
+/// The functions that never return because every call to them reaches an `exit`
+fn exiting_functions(program: &NodeProgram) -> HashSet<String> {
+    let mut functions = vec!();
+    collect_functions(program, &mut functions);
+
+    let mut exiting: HashSet<String> = HashSet::new();
+
+    // a function exits if it calls one that exits, so keep going until nothing new is found
+    loop {
+        let found: Vec<String> = functions.iter()
+            .filter(|function| !exiting.contains(&function.identifier.info))
+            .filter(|function| {
+                // the first statement that stops the function decides, it could be a return
+                let first = function.scope.statements.iter().find(|stmt| stops(stmt, &exiting));
+                matches!(first, Some(stmt) if !matches!(stmt, NodeStatements::Return(_)))
+            })
+            .map(|function| function.identifier.info.clone())
+            .collect();
+
+        if found.is_empty() {
+            return exiting;
+        }
+
+        exiting.extend(found);
+    }
+}
+
+/// Whether nothing after the statement runs, because it exits or returns
+fn stops(stmt: &NodeStatements, exiting: &HashSet<String>) -> bool {
+    match stmt {
+        NodeStatements::Exit(_) | NodeStatements::Return(_) => true,
+        _ => stmt.calls().iter().any(|call| exiting.contains(&call.identifier.info)),
+    }
+}
+
+/// `function` is the name of the function the scope belongs to, `None` for the top level
+fn remove_unreachable_statements(program: &mut NodeProgram, exiting: &HashSet<String>, function: Option<&str>, debug: bool) {
+    let mut reachable = true;
+    let mut removed = 0;
+
+    let statements = std::mem::take(&mut program.statements);
+
+    for stmt in statements {
+        match stmt {
+            NodeStatements::Function(mut func_stmt) => {
+                let name = func_stmt.identifier.info.clone();
+                remove_unreachable_statements(&mut func_stmt.scope, exiting, Some(&name), debug);
+                program.statements.push(NodeStatements::Function(func_stmt));
+            },
+
+            NodeStatements::Declare(mut declare_stmt) if !reachable && function.is_none() => {
+                declare_stmt.expression = None;
+                program.statements.push(NodeStatements::Declare(declare_stmt));
+            },
+
+            _ if !reachable => removed += 1,
+
+            stmt => {
+                reachable = !stops(&stmt, exiting);
+                program.statements.push(stmt);
+            },
+        }
+    }
+
+    if debug && removed > 0 {
+        match function {
+            Some(name) => eprintln!("dce: removed {} statement(s) after an exit or return in function {}", removed, name),
+            None => eprintln!("dce: removed {} statement(s) after an exit in the top level code", removed),
+        }
+    }
+}
+
+fn remove_functions(program: &mut NodeProgram, reachable: &HashSet<String>, debug: bool) {
+    program.statements.retain_mut(|stmt| {
+        let NodeStatements::Function(func_stmt) = stmt else {
+            return true;
+        };
+
+        if !reachable.contains(&func_stmt.identifier.info) {
+            if debug {
+                eprintln!("dce: removed function {} defined on line {}, it is never called",
+                    func_stmt.identifier.info, func_stmt.identifier.line);
+            }
+
+            return false;
+        }
+
+        remove_functions(&mut func_stmt.scope, reachable, debug);
+        return true;
+    });
+}
+
+/// Every function, including the ones defined inside other functions
+fn collect_functions<'a>(program: &'a NodeProgram, functions: &mut Vec<&'a NodeStmtFunction>) {
+    for stmt in &program.statements {
+        if let NodeStatements::Function(func_stmt) = stmt {
+            functions.push(func_stmt);
+            collect_functions(&func_stmt.scope, functions);
+        }
+    }
+}
+
+/// The functions called directly by a scope, not by the functions defined in it
+fn calls(program: &NodeProgram) -> Vec<&str> {
+    program.statements.iter()
+        .flat_map(NodeStatements::calls)
+        .map(|call| call.identifier.info.as_str())
+        .collect()
+}
@@ -0,0 +1,276 @@
+use crate::{
+    errors::Error,
+    parser::{*, math::*},
+    tokenise::{Token, TokenType},
+};
+
+/// Evaluates constant subexpressions and simplifies `x + 0`, `x - 0`, `x * 1`, `x / 1`
+/// and `x * 0`, as long as `x` doesn't call a function or divide by something that could
+/// trap. Arithmetic wraps around at 64 bits, the same as the generated code, and dividing
+/// by a constant zero is an error
+pub fn fold_program(program: &mut NodeProgram) -> Result<(), Error> {
+    for stmt in &mut program.statements {
+        match stmt {
+            NodeStatements::Exit(exit_stmt) => fold_in_place(&mut exit_stmt.expression)?,
+            NodeStatements::PutChar(putchar_stmt) => fold_in_place(&mut putchar_stmt.expression)?,
+            NodeStatements::Declare(declare_stmt) => {
+                if let Some(expression) = &mut declare_stmt.expression {
+                    fold_in_place(expression)?;
+                }
+            },
+            NodeStatements::Set(set_stmt) => fold_in_place(&mut set_stmt.expression)?,
+            NodeStatements::Function(func_stmt) => fold_program(&mut func_stmt.scope)?,
+            NodeStatements::Extern(_) => (),
+            NodeStatements::FunctionCall(func_call_stmt) => fold_call(func_call_stmt)?,
+            NodeStatements::Return(return_stmt) => {
+                if let Some(expression) = &mut return_stmt.expression {
+                    fold_in_place(expression)?;
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+fn fold_call(call: &mut NodeStmtFunctionCall) -> Result<(), Error> {
+    for arg in &mut call.args {
+        fold_in_place(arg)?;
+    }
+
+    Ok(())
+}
+
+fn fold_in_place(expr: &mut MathValue) -> Result<(), Error> {
+    // the placeholder is only there while the real expression is being folded
+    let placeholder = MathValue::Integer(constant_token(first_token(expr), 0));
+    let value = std::mem::replace(expr, placeholder);
+
+    *expr = fold_expression(value)?;
+
+    Ok(())
+}
+
+fn fold_expression(expr: MathValue) -> Result<MathValue, Error> {
+    let oper = match expr {
+        MathValue::Operation(oper) => oper,
+        MathValue::Call(mut call) => {
+            fold_call(&mut call)?;
+            return Ok(MathValue::Call(call));
+        },
+        _ => return Ok(expr),
+    };
+
+    let (value_1, value_2, build): (MathValue, MathValue, fn(MathValue, MathValue) -> OperationType) = match *oper {
+        OperationType::Add(add) => (add.value_1, add.value_2, |value_1, value_2| OperationType::Add(NodeMathAdd { value_1, value_2 })),
+        OperationType::Sub(sub) => (sub.value_1, sub.value_2, |value_1, value_2| OperationType::Sub(NodeMathSub { value_1, value_2 })),
+        OperationType::Mult(mult) => (mult.value_1, mult.value_2, |value_1, value_2| OperationType::Mult(NodeMathMult { value_1, value_2 })),
+        OperationType::Div(div) => (div.value_1, div.value_2, |value_1, value_2| OperationType::Div(NodeMathDiv { value_1, value_2 })),
+
+        OperationType::Negate(negate) => {
+            let value = fold_expression(negate.value)?;
+
+            if let Some(constant) = constant_value(&value) {
+                return Ok(MathValue::Integer(constant_token(first_token(&value), constant.wrapping_neg())));
+            }
+
+            return Ok(MathValue::Operation(Box::new(OperationType::Negate(NodeMathNegate { value }))));
+        },
+    };
+
+    let value_1 = fold_expression(value_1)?;
+    let value_2 = fold_expression(value_2)?;
+
+    let operation = build(value_1, value_2);
+
+    // both sides are known, so the whole operation can be worked out now
+    if let (Some(constant_1), Some(constant_2)) = operands(&operation) {
+        let token = first_token_of_operation(&operation);
+
+        let result = match &operation {
+            OperationType::Add(_) => constant_1.wrapping_add(constant_2),
+            OperationType::Sub(_) => constant_1.wrapping_sub(constant_2),
+            OperationType::Mult(_) => constant_1.wrapping_mul(constant_2),
+            OperationType::Div(_) => {
+                if constant_2 == 0 {
+                    return Err(division_by_zero(token));
+                }
+
+                // the smallest value divided by -1 overflows, which traps in the native
+                // code, so it is left for the program to do
+                match constant_1.checked_div(constant_2) {
+                    Some(result) => result,
+                    None => return Ok(MathValue::Operation(Box::new(operation))),
+                }
+            },
+            OperationType::Negate(_) => unreachable!("Negation only has one operand"),
+        };
+
+        return Ok(MathValue::Integer(constant_token(token, result)));
+    }
+
+    return simplify(operation);
+}
+
+/// Algebraic simplifications where only one side is a constant
+fn simplify(operation: OperationType) -> Result<MathValue, Error> {
+    let (constant_1, constant_2) = operands(&operation);
+
+    let simplified = match operation {
+        OperationType::Add(add) if constant_1 == Some(0) => add.value_2,
+        OperationType::Add(add) if constant_2 == Some(0) => add.value_1,
+        OperationType::Sub(sub) if constant_2 == Some(0) => sub.value_1,
+
+        OperationType::Mult(mult) if constant_1 == Some(1) => mult.value_2,
+        OperationType::Mult(mult) if constant_2 == Some(1) => mult.value_1,
+        OperationType::Mult(mult) if constant_1 == Some(0) && can_drop(&mult.value_2) => mult.value_1,
+        OperationType::Mult(mult) if constant_2 == Some(0) && can_drop(&mult.value_1) => mult.value_2,
+
+        OperationType::Div(div) if constant_2 == Some(0) => {
+            return Err(division_by_zero(first_token(&div.value_1)));
+        },
+        OperationType::Div(div) if constant_2 == Some(1) => div.value_1,
+
+        operation => MathValue::Operation(Box::new(operation)),
+    };
+
+    Ok(simplified)
+}
+
+/// Checks that no divisor is always zero. It runs at every level, so whether a program
+/// compiles doesn't depend on folding it
+pub fn check_divisors(program: &NodeProgram) -> Result<(), Error> {
+    for stmt in &program.statements {
+        if let NodeStatements::Function(func_stmt) = stmt {
+            check_divisors(&func_stmt.scope)?;
+        }
+
+        for expression in stmt.expressions() {
+            check_expression(expression)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn check_expression(expr: &MathValue) -> Result<(), Error> {
+    match expr {
+        MathValue::Integer(_) | MathValue::Identifier(_) => (),
+        MathValue::Call(call) => {
+            for arg in &call.args {
+                check_expression(arg)?;
+            }
+        },
+        MathValue::Operation(oper) => {
+            for operand in oper.operands() {
+                check_expression(operand)?;
+            }
+
+            if let OperationType::Div(div) = oper.as_ref() {
+                if evaluate(&div.value_2) == Some(0) {
+                    return Err(division_by_zero(first_token(&div.value_1)));
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// The value folding gives the expression, if it becomes a constant
+fn evaluate(expr: &MathValue) -> Option<i64> {
+    let oper = match expr {
+        MathValue::Integer(integer) => return integer.value,
+        MathValue::Identifier(_) | MathValue::Call(_) => return None,
+        MathValue::Operation(oper) => oper,
+    };
+
+    match oper.as_ref() {
+        OperationType::Add(add) => Some(evaluate(&add.value_1)?.wrapping_add(evaluate(&add.value_2)?)),
+        OperationType::Sub(sub) => Some(evaluate(&sub.value_1)?.wrapping_sub(evaluate(&sub.value_2)?)),
+        OperationType::Mult(mult) => {
+            let (value_1, value_2) = (evaluate(&mult.value_1), evaluate(&mult.value_2));
+
+            match (value_1, value_2) {
+                (Some(value_1), Some(value_2)) => Some(value_1.wrapping_mul(value_2)),
+                (Some(0), None) if can_drop(&mult.value_2) => Some(0),
+                (None, Some(0)) if can_drop(&mult.value_1) => Some(0),
+                _ => None,
+            }
+        },
+        OperationType::Div(div) => evaluate(&div.value_1)?.checked_div(evaluate(&div.value_2)?),
+        OperationType::Negate(negate) => Some(evaluate(&negate.value)?.wrapping_neg()),
+    }
+}
+
+/// Whether the expression can be left out without changing what the program does, it
+/// can't call anything or trap. A division traps unless its divisor is a constant other
+/// than 0 and -1
+fn can_drop(expr: &MathValue) -> bool {
+    match expr {
+        MathValue::Integer(_) | MathValue::Identifier(_) => true,
+        MathValue::Call(_) => false,
+        MathValue::Operation(oper) => {
+            if let OperationType::Div(div) = oper.as_ref() {
+                if matches!(evaluate(&div.value_2), None | Some(0) | Some(-1)) {
+                    return false;
+                }
+            }
+
+            oper.operands().into_iter().all(can_drop)
+        },
+    }
+}
+
+/// The constant values of both sides of a binary operation
+fn operands(operation: &OperationType) -> (Option<i64>, Option<i64>) {
+    match operation {
+        OperationType::Add(add) => (constant_value(&add.value_1), constant_value(&add.value_2)),
+        OperationType::Sub(sub) => (constant_value(&sub.value_1), constant_value(&sub.value_2)),
+        OperationType::Mult(mult) => (constant_value(&mult.value_1), constant_value(&mult.value_2)),
+        OperationType::Div(div) => (constant_value(&div.value_1), constant_value(&div.value_2)),
+        OperationType::Negate(negate) => (constant_value(&negate.value), None),
+    }
+}
+
+fn constant_value(expr: &MathValue) -> Option<i64> {
+    match expr {
+        MathValue::Integer(integer) => integer.value,
+        _ => None,
+    }
+}
+
+fn division_by_zero(token: &Token) -> Error {
+    Error { line: token.line, msg: "Division by zero, the divisor is always 0".to_string() }
+}
+
+/// Makes an integer literal token for a folded value, placed where the expression started
+fn constant_token(position: &Token, value: i64) -> Token {
+    Token {
+        token: TokenType::IntegerLit,
+        info: value.to_string(),
+        line: position.line,
+        column: position.column,
+        value: Some(value),
+        doc: None,
+    }
+}
+
+/// The leftmost token of an expression
+pub fn first_token(expr: &MathValue) -> &Token {
+    match expr {
+        MathValue::Integer(token) | MathValue::Identifier(token) => token,
+        MathValue::Operation(oper) => first_token_of_operation(oper),
+        MathValue::Call(call) => &call.identifier,
+    }
+}
+
+fn first_token_of_operation(operation: &OperationType) -> &Token {
+    match operation {
+        OperationType::Add(add) => first_token(&add.value_1),
+        OperationType::Sub(sub) => first_token(&sub.value_1),
+        OperationType::Mult(mult) => first_token(&mult.value_1),
+        OperationType::Div(div) => first_token(&div.value_1),
+        OperationType::Negate(negate) => first_token(&negate.value),
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ir::{Block, Function, Instr, Module, Operand, Temp, Terminator, Var};
+use crate::parser::Inline;
+
+use super::pass_manager::PassOptions;
+
+/// Functions with at most this many instructions are inlined without `#[inline]`
+const INLINE_SIZE: usize = 12;
+
+/// Replaces calls to small functions, and ones marked `#[inline]`, with a copy of their
+/// body. Recursive functions and ones marked `#[noinline]` are never inlined.
+///
+/// The parameters and variables of the copy become new locals of the caller, named
+/// `callee.n.name` where `n` counts the copies of that callee in that caller, and its
+/// temps are moved after the caller's. Every round inlines one level of calls, copying
+/// the functions as they were at the start of the round, and the rounds go on until
+/// nothing changes, so a chain of small calls collapses. Functions whose calls were all
+/// inlined are removed, unless every function is exported
+pub fn inline_functions(module: &mut Module, options: &PassOptions) {
+    let debug = options.debug;
+    let recursive = recursive_functions(module);
+    let mut inlined: HashSet<String> = HashSet::new();
+    // how many copies of each callee every caller has, kept across the rounds so the
+    // names of the copies stay unique
+    let mut copies: HashMap<String, HashMap<String, usize>> = HashMap::new();
+
+    // every round inlines one level of calls, a chain without recursion ends eventually
+    loop {
+        let candidates: HashMap<String, Function> = module.functions.iter()
+            .filter(|function| !recursive.contains(&function.name) && should_inline(function))
+            .map(|function| (function.name.clone(), function.clone()))
+            .collect();
+
+        let mut changed = false;
+
+        for caller in std::iter::once(&mut module.main).chain(module.functions.iter_mut()) {
+            let copies = copies.entry(caller.name.clone()).or_default();
+
+            for name in inline_calls(caller, &candidates, copies) {
+                if debug {
+                    eprintln!("inline: inlined {} into {}", name, caller.name);
+                }
+
+                inlined.insert(name);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    if options.keep_functions {
+        return;
+    }
+
+    // the functions that were inlined everywhere aren't needed any more
+    let called = called_functions(module);
+    module.functions.retain(|function| {
+        let keep = called.contains(&function.name) || !inlined.contains(&function.name);
+
+        if debug && !keep {
+            eprintln!("inline: removed function {}, every call to it was inlined", function.name);
+        }
+
+        return keep;
+    });
+}
+
+fn should_inline(function: &Function) -> bool {
+    match function.inline {
+        Inline::Always => true,
+        Inline::Never => false,
+        Inline::Auto => function.blocks.iter().map(|block| block.instrs.len()).sum::<usize>() <= INLINE_SIZE,
+    }
+}
+
+/// Inlines the calls to the candidates in `caller`, returns the names of the functions
+/// that were inlined, once for every call. `copies` counts the copies of each callee
+/// already in the caller
+fn inline_calls(caller: &mut Function, candidates: &HashMap<String, Function>, copies: &mut HashMap<String, usize>) -> Vec<String> {
+    let mut inlined = vec!();
+    let mut blocks: Vec<Block> = vec!();
+    let mut instrs: Vec<Instr> = vec!();
+
+    for block in std::mem::take(&mut caller.blocks) {
+        for instr in block.instrs {
+            let Instr::Call { dest, func, args } = &instr else {
+                instrs.push(instr);
+                continue;
+            };
+
+            // a function can't be inlined into itself
+            let Some(callee) = candidates.get(func).filter(|callee| callee.name != caller.name) else {
+                instrs.push(instr);
+                continue;
+            };
+
+            let count = copies.entry(func.clone()).or_insert(0);
+            let copy = *count;
+            *count += 1;
+            let local_offset = caller.locals.len();
+            let temp_offset = caller.temps;
+
+            caller.locals.extend(callee.locals.iter().map(|local| format!("{}.{}.{}", callee.name, copy, local)));
+            caller.temps += callee.temps;
+
+            // the arguments are stored into the parameters, which are now locals
+            for (i, arg) in args.iter().enumerate() {
+                instrs.push(Instr::Store { var: Var::Local(local_offset + i), value: *arg });
+            }
+
+            for callee_block in &callee.blocks {
+                for callee_instr in &callee_block.instrs {
+                    instrs.push(rename(callee_instr, local_offset, temp_offset));
+                }
+
+                // the rest of the callee can't be reached after a return, the caller carries on
+                match &callee_block.terminator {
+                    Terminator::Return(value) => {
+                        if let Some(dest) = dest {
+                            instrs.push(Instr::Copy { dest: *dest, value: rename_operand(*value, temp_offset) });
+                        }
+
+                        break;
+                    },
+                    Terminator::TailCall { func, args } => {
+                        let args = args.iter().map(|arg| rename_operand(*arg, temp_offset)).collect();
+                        instrs.push(Instr::Call { dest: *dest, func: func.clone(), args });
+
+                        break;
+                    },
+                    Terminator::Exit(value) => {
+                        let value = rename_operand(*value, temp_offset);
+                        blocks.push(Block { instrs: std::mem::take(&mut instrs), terminator: Terminator::Exit(value) });
+                    },
+                }
+            }
+
+            inlined.push(func.clone());
+        }
+
+        blocks.push(Block { instrs: std::mem::take(&mut instrs), terminator: block.terminator });
+    }
+
+    caller.blocks = blocks;
+
+    return inlined;
+}
+
+/// Moves the locals and temps of an instruction of the callee to where they are in the caller
+fn rename(instr: &Instr, local_offset: usize, temp_offset: usize) -> Instr {
+    let var = |var: Var| match var {
+        Var::Global(_) => var,
+        Var::Local(index) => Var::Local(index + local_offset),
+    };
+    let temp = |temp: Temp| Temp(temp.0 + temp_offset);
+    let operand = |operand: Operand| rename_operand(operand, temp_offset);
+
+    match instr {
+        Instr::Load { dest, var: v } => Instr::Load { dest: temp(*dest), var: var(*v) },
+        Instr::Store { var: v, value } => Instr::Store { var: var(*v), value: operand(*value) },
+        Instr::Binary { dest, op, lhs, rhs } => Instr::Binary { dest: temp(*dest), op: *op, lhs: operand(*lhs), rhs: operand(*rhs) },
+        Instr::Unary { dest, op, value } => Instr::Unary { dest: temp(*dest), op: *op, value: operand(*value) },
+        Instr::Copy { dest, value } => Instr::Copy { dest: temp(*dest), value: operand(*value) },
+        Instr::Call { dest, func, args } => Instr::Call {
+            dest: dest.map(temp),
+            func: func.clone(),
+            args: args.iter().map(|arg| operand(*arg)).collect(),
+        },
+        Instr::CallExtern { dest, func, args } => Instr::CallExtern {
+            dest: dest.map(temp),
+            func: func.clone(),
+            args: args.iter().map(|arg| operand(*arg)).collect(),
+        },
+        Instr::PutChar { value } => Instr::PutChar { value: operand(*value) },
+    }
+}
+
+fn rename_operand(operand: Operand, temp_offset: usize) -> Operand {
+    match operand {
+        Operand::Temp(temp) => Operand::Temp(Temp(temp.0 + temp_offset)),
+        Operand::Const(_) => operand,
+    }
+}
+
+/// The functions called directly by a function
+fn callees(function: &Function) -> Vec<&str> {
+    let mut callees = vec!();
+
+    for block in &function.blocks {
+        for instr in &block.instrs {
+            if let Instr::Call { func, .. } = instr {
+                callees.push(func.as_str());
+            }
+        }
+
+        if let Terminator::TailCall { func, .. } = &block.terminator {
+            callees.push(func.as_str());
+        }
+    }
+
+    return callees;
+}
+
+fn called_functions(module: &Module) -> HashSet<String> {
+    std::iter::once(&module.main).chain(&module.functions)
+        .flat_map(callees)
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// The functions that can end up calling themselves
+fn recursive_functions(module: &Module) -> HashSet<String> {
+    let graph: HashMap<&str, Vec<&str>> = module.functions.iter()
+        .map(|function| (function.name.as_str(), callees(function)))
+        .collect();
+
+    let mut recursive = HashSet::new();
+
+    for function in &module.functions {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut queue: Vec<&str> = graph[function.name.as_str()].clone();
+
+        while let Some(name) = queue.pop() {
+            if name == function.name {
+                recursive.insert(function.name.clone());
+                break;
+            }
+
+            if seen.insert(name) {
+                queue.extend(graph.get(name).into_iter().flatten());
+            }
+        }
+    }
+
+    return recursive;
+}
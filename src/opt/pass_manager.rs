@@ -0,0 +1,171 @@
+use crate::code_gen::{asm::Inst, peephole};
+use crate::errors::Error;
+use crate::ir::Module;
+use crate::parser::NodeProgram;
+
+use super::{dce, fold, inline, tail_calls};
+
+/// What a pass works on, which decides where in the compiler it runs
+enum Run {
+    Ast(fn(&mut NodeProgram, &PassOptions) -> Result<(), Error>),
+    Ir(fn(&mut Module, &PassOptions)),
+    /// Changes how the code generator works rather than rewriting anything, the code
+    /// generator asks the pass manager whether it is enabled
+    CodeGen,
+    Asm(fn(&mut Vec<Inst>)),
+}
+
+/// Settings every pass over the ast or the ir is given
+pub struct PassOptions {
+    /// Report what the pass changed
+    pub debug: bool,
+    /// Every function is exported, so none of them can be removed even if nothing calls it
+    pub keep_functions: bool,
+}
+
+pub struct Pass {
+    pub name: &'static str,
+    /// The lowest optimisation level the pass runs at
+    pub level: u8,
+    pub description: &'static str,
+    run: Run,
+}
+
+pub const MAX_LEVEL: u8 = 2;
+
+/// Every pass, in the order they run
+const PASSES: [Pass; 6] = [
+    Pass {
+        name: "fold",
+        level: 1,
+        description: "evaluate constant expressions and simplify identities",
+        run: Run::Ast(|program, _| fold::fold_program(program)),
+    },
+    Pass {
+        name: "dce",
+        level: 1,
+        description: "remove code after an exit and functions that are never called",
+        run: Run::Ast(dce::eliminate_dead_code),
+    },
+    Pass {
+        name: "inline",
+        level: 2,
+        description: "replace calls to small functions with their body",
+        run: Run::Ir(inline::inline_functions),
+    },
+    Pass {
+        name: "tail-calls",
+        level: 2,
+        description: "jump to functions called by a return instead of calling them",
+        run: Run::Ir(tail_calls::optimise_tail_calls),
+    },
+    Pass {
+        name: "regalloc",
+        level: 1,
+        description: "keep values in registers instead of stack slots",
+        run: Run::CodeGen,
+    },
+    Pass {
+        name: "peephole",
+        level: 1,
+        description: "clean up instruction pairs in the generated assembly",
+        run: Run::Asm(peephole::optimise),
+    },
+];
+
+/// Decides which passes run from the optimisation level and the passes disabled on the
+/// command line, and runs them in order
+pub struct PassManager {
+    level: u8,
+    disabled: Vec<String>,
+    options: PassOptions,
+}
+
+impl PassManager {
+    /// Fails if a disabled pass doesn't exist
+    pub fn new(level: u8, disabled: Vec<String>, debug: bool) -> Result<PassManager, String> {
+        for name in &disabled {
+            if !PASSES.iter().any(|pass| pass.name == name) {
+                let names: Vec<&str> = PASSES.iter().map(|pass| pass.name).collect();
+                return Err(format!("Unknown pass `{}`, the passes are {}", name, names.join(", ")));
+            }
+        }
+
+        return Ok(PassManager { level, disabled, options: PassOptions { debug, keep_functions: false } });
+    }
+
+    /// Stops the passes from removing functions, for object files where every function
+    /// is exported
+    pub fn keep_functions(&mut self) {
+        self.options.keep_functions = true;
+    }
+
+    pub fn enabled(&self, name: &str) -> bool {
+        PASSES.iter().any(|pass| pass.name == name && self.runs(pass))
+    }
+
+    fn runs(&self, pass: &Pass) -> bool {
+        self.level >= pass.level && !self.disabled.iter().any(|name| name == pass.name)
+    }
+
+    pub fn run_ast(&self, program: &mut NodeProgram) -> Result<(), Error> {
+        for pass in PASSES.iter().filter(|pass| self.runs(pass)) {
+            if let Run::Ast(run) = pass.run {
+                self.report(pass);
+                run(program, &self.options)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub fn run_ir(&self, module: &mut Module) {
+        for pass in PASSES.iter().filter(|pass| self.runs(pass)) {
+            if let Run::Ir(run) = pass.run {
+                self.report(pass);
+                run(module, &self.options);
+            }
+        }
+    }
+
+    pub fn run_asm(&self, insts: &mut Vec<Inst>) {
+        for pass in PASSES.iter().filter(|pass| self.runs(pass)) {
+            if let Run::Asm(run) = pass.run {
+                self.report(pass);
+                run(insts);
+            }
+        }
+    }
+
+    fn report(&self, pass: &Pass) {
+        if self.options.debug {
+            eprintln!("running pass {}", pass.name);
+        }
+    }
+
+    /// Lists every pass in order, and whether it runs
+    pub fn describe(&self) -> String {
+        let mut output = format!("-O{}\n", self.level);
+
+        for pass in &PASSES {
+            let status = if self.disabled.iter().any(|name| name == pass.name) {
+                "disabled".to_string()
+            } else if self.level < pass.level {
+                format!("needs -O{}", pass.level)
+            } else {
+                "runs".to_string()
+            };
+
+            let stage = match pass.run {
+                Run::Ast(_) => "ast",
+                Run::Ir(_) => "ir",
+                Run::CodeGen => "codegen",
+                Run::Asm(_) => "asm",
+            };
+
+            output.push_str(&format!("{:<12}{:<9}{:<11}{}\n", pass.name, stage, status, pass.description));
+        }
+
+        return output;
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use crate::ir::{Instr, Module, Operand, Terminator};
+
+use super::pass_manager::PassOptions;
+
+/// Turns `return f(...);` into a jump to `f` that reuses the frame of the caller, so
+/// recursion through tail calls doesn't grow the stack.
+///
+/// The arguments replace the parameters of the caller on the stack, so only calls with at
+/// most as many arguments as the caller has parameters can be turned into jumps. That
+/// covers a function calling itself, and siblings with the same parameters
+pub fn optimise_tail_calls(module: &mut Module, options: &PassOptions) {
+    let params: HashMap<String, usize> = module.functions.iter()
+        .map(|function| (function.name.clone(), function.params))
+        .collect();
+
+    // the top level code never returns, so it has no tail calls
+    for function in &mut module.functions {
+        for block in &mut function.blocks {
+            let Terminator::Return(Operand::Temp(value)) = block.terminator else {
+                continue;
+            };
+
+            let Some(Instr::Call { dest: Some(dest), func, .. }) = block.instrs.last() else {
+                continue;
+            };
+
+            if *dest != value || params[func] > function.params {
+                continue;
+            }
+
+            let Some(Instr::Call { func, args, .. }) = block.instrs.pop() else {
+                unreachable!();
+            };
+
+            if options.debug {
+                eprintln!("tail-calls: the call to {} in {} is now a jump", func, function.name);
+            }
+
+            block.terminator = Terminator::TailCall { func, args };
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{errors::Error, exit_message, Token, TokenType};
+use crate::{errors::Error, Token, TokenType};
 
 mod expression_parser;
 
@@ -16,7 +16,9 @@ pub enum NodeStatements {
     Set(NodeStmtSet),
     
     Function(NodeStmtFunction),
+    Extern(NodeStmtExtern),
     FunctionCall(NodeStmtFunctionCall),
+    Return(NodeStmtReturn),
 }
 
 #[derive(Debug)]
@@ -39,6 +41,8 @@ pub struct NodeStmtPutChar {
 pub struct NodeStmtDeclare {
     pub identifier: Token,
     pub expression: Option<MathValue>,
+    /// The doc comment written above the declaration
+    pub doc: Option<String>,
 }
 
 #[derive(Debug)]
@@ -52,12 +56,108 @@ pub struct NodeStmtFunction {
     pub identifier: Token,
     pub args: Vec<NodeStmtDeclare>,
     pub scope: NodeProgram,
+    /// The doc comment written above the function
+    pub doc: Option<String>,
+    pub inline: Inline,
 }
 
+/// `extern fn name(int a) -> int;`, a function from outside the program, like one in the
+/// C library, that is called the way C calls functions
 #[derive(Debug)]
-pub struct NodeStmtFunctionCall {
+pub struct NodeStmtExtern {
     pub identifier: Token,
     pub args: Vec<NodeStmtDeclare>,
+    /// Whether it is declared `-> int`, without it the call can't be used as a value
+    pub returns: bool,
+    /// The doc comment written above the declaration
+    pub doc: Option<String>,
+}
+
+/// Whether calls to a function should be replaced with its body, set with the
+/// `#[inline]` and `#[noinline]` attributes
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Inline {
+    /// Left to the size of the function
+    Auto,
+    Always,
+    Never,
+}
+
+/// A call as a statement, or as part of an expression where it gives the return value
+#[derive(Debug)]
+pub struct NodeStmtFunctionCall {
+    pub identifier: Token,
+    pub args: Vec<MathValue>,
+}
+
+#[derive(Debug)]
+pub struct NodeStmtReturn {
+    /// The `return` keyword
+    pub token: Token,
+    pub expression: Option<MathValue>,
+}
+
+impl NodeStatements {
+    /// The expressions the statement evaluates, the body of a function isn't included
+    pub fn expressions(&self) -> Vec<&MathValue> {
+        match self {
+            NodeStatements::Exit(exit_stmt) => vec!(&exit_stmt.expression),
+            NodeStatements::PutChar(putchar_stmt) => vec!(&putchar_stmt.expression),
+            NodeStatements::Declare(declare_stmt) => declare_stmt.expression.iter().collect(),
+            NodeStatements::Set(set_stmt) => vec!(&set_stmt.expression),
+            NodeStatements::Function(_) | NodeStatements::Extern(_) => vec!(),
+            NodeStatements::FunctionCall(func_call_stmt) => func_call_stmt.args.iter().collect(),
+            NodeStatements::Return(return_stmt) => return_stmt.expression.iter().collect(),
+        }
+    }
+
+    /// Every call the statement makes, including the ones in its expressions
+    pub fn calls(&self) -> Vec<&NodeStmtFunctionCall> {
+        let mut calls = vec!();
+
+        if let NodeStatements::FunctionCall(func_call_stmt) = self {
+            calls.push(func_call_stmt);
+        }
+
+        for expression in self.expressions() {
+            calls.extend(expression.calls());
+        }
+
+        return calls;
+    }
+}
+
+impl NodeProgram {
+    /// Every extern function, including the ones declared inside functions
+    pub fn externs(&self) -> Vec<&NodeStmtExtern> {
+        let mut externs = vec!();
+
+        for stmt in &self.statements {
+            match stmt {
+                NodeStatements::Extern(extern_stmt) => externs.push(extern_stmt),
+                NodeStatements::Function(func_stmt) => externs.extend(func_stmt.scope.externs()),
+                _ => (),
+            }
+        }
+
+        return externs;
+    }
+
+    /// Every function, including the ones defined inside functions
+    pub fn functions(&self) -> Vec<&NodeStmtFunction> {
+        let mut functions = vec!();
+
+        for stmt in &self.statements {
+            if let NodeStatements::Function(func_stmt) = stmt {
+                functions.push(func_stmt);
+                functions.extend(func_stmt.scope.functions());
+            }
+        }
+
+        return functions;
+    }
 }
 
 pub struct Parser {
@@ -74,6 +174,7 @@ impl Parser {
 
             let statement = match token.token {
                 TokenType::Exit => NodeStatements::Exit(self.parse_exit()?),
+                TokenType::Return => NodeStatements::Return(self.parse_return()?),
                 TokenType::PutChar => NodeStatements::PutChar(self.parse_putchar()?),
                 TokenType::IntType => NodeStatements::Declare(self.parse_int_assign()?),
                 TokenType::Identifier => {
@@ -84,6 +185,8 @@ impl Parser {
                     }
                 },
                 TokenType::Function => NodeStatements::Function(self.parse_function()?),
+                TokenType::Extern => NodeStatements::Extern(self.parse_extern()?),
+                TokenType::Hash => NodeStatements::Function(self.parse_attributed_function()?),
                 _ => { 
                     return Err ( Error { line: token.line, msg: format!("Expected a valid statement, found {}", token.info) })
                 }
@@ -134,7 +237,8 @@ impl Parser {
     }
 
     fn parse_function(&mut self) -> Result<NodeStmtFunction, Error> {
-        let identifier = self.require_token(1, TokenType::NoToken)?;
+        let doc = self.tokens[self.index].doc.clone();
+        let identifier = self.require_token(1, TokenType::Identifier)?;
         let _paren = self.require_token(2, TokenType::ParenOpen)?;
         
         // account for: fn test(
@@ -142,7 +246,12 @@ impl Parser {
 
         let mut args: Vec<NodeStmtDeclare> = vec!();
         while self.require_token(0, TokenType::ParenClose).is_err() {
-            args.push(self.parse_int_assign()?);
+            if !args.is_empty() {
+                let _comma = self.require_token(0, TokenType::Comma)?;
+                self.index += 1;
+            }
+
+            args.push(self.parse_param()?);
         }
 
         // now we finished all the args
@@ -150,27 +259,161 @@ impl Parser {
         self.index += 1;
         let scope = self.parse_scope()?;
 
-        let function_stmt = NodeStmtFunction { identifier, args, scope };
+        let function_stmt = NodeStmtFunction { identifier, args, scope, doc, inline: Inline::Auto };
 
         Ok( function_stmt )
     }
 
-    fn parse_func_call(&mut self) -> Result<NodeStmtFunctionCall, Error> {
-        let _paren = self.require_token(1, TokenType::ParenOpen)?;
-        let _paren = self.require_token(2, TokenType::ParenClose)?;
-        let _semi  = self.require_token(3, TokenType::Semicolon)?;
+    /// `extern fn name(int a, int b) -> int;`, the return type can be left out
+    fn parse_extern(&mut self) -> Result<NodeStmtExtern, Error> {
+        let doc = self.tokens[self.index].doc.clone();
+        let _fn = self.require_token(1, TokenType::Function)?;
 
-        let identifier = self.tokens[self.index].clone();
-        let args = vec!();
+        // the c library's putchar and exit are statements in atomic
+        if let Some(builtin) = self.tokens.get(self.index + 2).filter(|token| matches!(token.token, TokenType::PutChar | TokenType::Exit)) {
+            return Err(Error { line: builtin.line, msg: format!("{} is a builtin, it can't be declared as an extern function", builtin.info) });
+        }
 
-        // account for: test();
+        let identifier = self.require_token(2, TokenType::Identifier)?;
+        let _paren = self.require_token(3, TokenType::ParenOpen)?;
+
+        // the name is the one the linker looks for, so it has to be a valid C name
+        if identifier.info.contains('-') {
+            return Err(Error { line: identifier.line, msg: format!("Extern function {} can't have a `-` in its name", identifier.info) });
+        }
+
+        // account for: extern fn name(
         self.index += 4;
 
-        let function_call_stmt = NodeStmtFunctionCall { identifier, args };
+        let mut args: Vec<NodeStmtDeclare> = vec!();
+        while self.require_token(0, TokenType::ParenClose).is_err() {
+            if !args.is_empty() {
+                let _comma = self.require_token(0, TokenType::Comma)?;
+                self.index += 1;
+            }
+
+            args.push(self.parse_param()?);
+        }
+
+        // account for )
+        self.index += 1;
+
+        let returns = self.require_token(0, TokenType::Arrow).is_ok();
+        if returns {
+            let _int = self.require_token(1, TokenType::IntType)?;
+
+            // account for -> int
+            self.index += 2;
+        }
+
+        let _semi = self.require_token(0, TokenType::Semicolon)?;
+        self.index += 1;
+
+        Ok( NodeStmtExtern { identifier, args, returns, doc } )
+    }
+
+    /// Parses the attributes in front of a function, like `#[inline]`, then the function
+    fn parse_attributed_function(&mut self) -> Result<NodeStmtFunction, Error> {
+        // the doc comment is above the attributes
+        let doc = self.tokens[self.index].doc.clone();
+        let mut inline: Option<Inline> = None;
+
+        while self.require_token(0, TokenType::Hash).is_ok() {
+            let _bracket = self.require_token(1, TokenType::BracketOpen)?;
+            let name = self.require_token(2, TokenType::Identifier)?;
+            let _bracket = self.require_token(3, TokenType::BracketClose)?;
+
+            // account for #[name]
+            self.index += 4;
+
+            let attribute = match name.info.as_str() {
+                "inline" => Inline::Always,
+                "noinline" => Inline::Never,
+                _ => return Err(Error { line: name.line, msg: format!("Unknown attribute `{}`, expected `inline` or `noinline`", name.info) }),
+            };
+
+            if let Some(previous) = inline {
+                if previous != attribute {
+                    return Err(Error { line: name.line, msg: "A function can't be both `inline` and `noinline`".to_string() });
+                }
+            }
+
+            inline = Some(attribute);
+        }
+
+        let _fn = self.require_token(0, TokenType::Function)?;
+        let mut function_stmt = self.parse_function()?;
+
+        function_stmt.doc = doc;
+        if let Some(attribute) = inline {
+            function_stmt.inline = attribute;
+        }
+
+        Ok( function_stmt )
+    }
+
+    /// Parses a single parameter of a function definition, `int name`
+    fn parse_param(&mut self) -> Result<NodeStmtDeclare, Error> {
+        let _int = self.require_token(0, TokenType::IntType)?;
+        let doc = self.tokens[self.index].doc.clone();
+        let identifier = self.require_token(1, TokenType::Identifier)?;
+
+        // account for int name
+        self.index += 2;
+
+        Ok( NodeStmtDeclare { identifier, expression: None, doc } )
+    }
+
+    fn parse_func_call(&mut self) -> Result<NodeStmtFunctionCall, Error> {
+        let function_call_stmt = self.parse_call()?;
+
+        let _semi = self.require_token(0, TokenType::Semicolon)?;
+        self.index += 1;
 
         Ok( function_call_stmt )
     }
 
+    /// Parses `name(args)`, without the semicolon, so it can also be part of an expression
+    fn parse_call(&mut self) -> Result<NodeStmtFunctionCall, Error> {
+        let identifier = self.tokens[self.index].clone();
+        let _paren = self.require_token(1, TokenType::ParenOpen)?;
+
+        // account for: test(
+        self.index += 2;
+
+        let mut args: Vec<MathValue> = vec!();
+        while self.require_token(0, TokenType::ParenClose).is_err() {
+            if !args.is_empty() {
+                let _comma = self.require_token(0, TokenType::Comma)?;
+                self.index += 1;
+            }
+
+            args.push(self.parse_expr()?);
+        }
+
+        // account for )
+        self.index += 1;
+
+        Ok( NodeStmtFunctionCall { identifier, args } )
+    }
+
+    /// `return expr;`, or `return;` which returns 0
+    fn parse_return(&mut self) -> Result<NodeStmtReturn, Error> {
+        let token = self.tokens[self.index].clone();
+        self.index += 1;
+
+        let expression = if self.require_token(0, TokenType::Semicolon).is_ok() {
+            None
+        } else {
+            Some(self.parse_expr()?)
+        };
+
+        let _semi = self.require_token(0, TokenType::Semicolon)?;
+        self.index += 1;
+
+        Ok( NodeStmtReturn { token, expression } )
+    }
+
     fn parse_exit(&mut self) -> Result<NodeStmtExit, Error> {
         let _paren = self.require_token(1, TokenType::ParenOpen)?;
 
@@ -207,6 +450,7 @@ impl Parser {
     
     
     fn parse_int_assign(&mut self) -> Result<NodeStmtDeclare, Error> {
+        let doc = self.tokens[self.index].doc.clone();
         let identifier = self.require_token(1, TokenType::Identifier)?;
 
         // initial value is optional
@@ -220,7 +464,7 @@ impl Parser {
             let _semi_colon = self.require_token(0, TokenType::Semicolon)?;
 
             self.index += 1;
-            return Ok( NodeStmtDeclare { identifier, expression: None } );
+            return Ok( NodeStmtDeclare { identifier, expression: None, doc } );
         }
 
 
@@ -235,12 +479,12 @@ impl Parser {
         // account for ;
         self.index += 1;
 
-        Ok( NodeStmtDeclare { identifier, expression: Some(expr) } )
+        Ok( NodeStmtDeclare { identifier, expression: Some(expr), doc } )
     }
 
     fn parse_set_var(&mut self) -> Result<NodeStmtSet, Error> {
-        let _equal_sign = self.require_token(0, TokenType::AssignEq)?;
-        let identifier = self.require_token(1, TokenType::NoToken)?;
+        let identifier = self.require_token(0, TokenType::Identifier)?;
+        let _equal_sign = self.require_token(1, TokenType::AssignEq)?;
 
         // account for name =
         self.index += 2;
@@ -255,35 +499,6 @@ impl Parser {
     }
 
 
-    fn parse_expr(&mut self) -> Result<MathValue, Error> {
-        let min_index = self.index;
-
-        let mut parens = 0;
-        while self.index < self.tokens.len() 
-          && TOKENS_MATH.contains(&self.tokens[self.index].token) {
-
-            if self.tokens[self.index].token == TokenType::ParenOpen { parens += 1; }
-            if self.tokens[self.index].token == TokenType::ParenClose { parens -= 1; }
-
-            if parens < 0 { break; }
-
-            self.index += 1;
-        }
-
-
-        let max_index = self.index;
-
-        let expression_slice = &self.tokens[min_index..max_index];
-
-        if expression_slice.len() == 0 {
-            return Err ( Error { line: self.tokens[self.index].line, msg: "Expression is empty".to_string() } )
-        }
-        
-        let math_value = expression_parser::parse_expression(expression_slice);
-        
-        return math_value;
-    }
-
     /// Returns the token at an offset and makes sure it is of a certain type, use
     /// `TokenType::NoToken` to allow for any type
     fn require_token(&self, offset: usize, token_type: TokenType) -> Result<Token, Error> {
@@ -297,7 +512,7 @@ impl Parser {
             
             Ok(token.clone())
         } else {
-            Err( Error { line: self.tokens.last().expect("Empty file").line, msg: format!("Expected another token") })
+            Err( Error { line: self.tokens.last().expect("Empty file").line, msg: "Expected another token".to_string() })
         }
     }
 }
@@ -1,130 +1,119 @@
-use crate::tokenise::{Token, TokenType};
+use crate::tokenise::TokenType;
 use crate::errors::Error;
 
 
-use super::{MathValue, NodeMathAdd, NodeMathSub, NodeMathMult, NodeMathDiv, OperationType};
+use super::{MathValue, NodeMathAdd, NodeMathSub, NodeMathMult, NodeMathDiv, NodeMathNegate, OperationType, Parser};
 
 
-struct ExpressionParser {
-    index: usize,
+/// An operator that goes between two values. The left and right binding powers decide
+/// precedence and associativity, a higher power binds tighter, and a right power above
+/// the left power makes the operator left associative
+struct InfixOperator {
+    token: TokenType,
+    left_power: u8,
+    right_power: u8,
+    build: fn(MathValue, MathValue) -> OperationType,
 }
 
-impl ExpressionParser {
-    fn parse_sum(&mut self, tokens: &[Token]) -> Result<MathValue, Error> {
-        let mut value_1 = self.parse_product(tokens)?;
+/// An operator that goes before a value, like negation
+struct PrefixOperator {
+    token: TokenType,
+    power: u8,
+    build: fn(MathValue) -> OperationType,
+}
 
-        while self.index < tokens.len() 
-        && (tokens[self.index].token == TokenType::Plus || tokens[self.index].token == TokenType::Minus) {
+const INFIX_OPERATORS: [InfixOperator; 4] = [
+    InfixOperator {
+        token: TokenType::Plus, left_power: 1, right_power: 2,
+        build: |value_1, value_2| OperationType::Add(NodeMathAdd { value_1, value_2 }),
+    },
+    InfixOperator {
+        token: TokenType::Minus, left_power: 1, right_power: 2,
+        build: |value_1, value_2| OperationType::Sub(NodeMathSub { value_1, value_2 }),
+    },
+    InfixOperator {
+        token: TokenType::Star, left_power: 3, right_power: 4,
+        build: |value_1, value_2| OperationType::Mult(NodeMathMult { value_1, value_2 }),
+    },
+    InfixOperator {
+        token: TokenType::ForwardsSlash, left_power: 3, right_power: 4,
+        build: |value_1, value_2| OperationType::Div(NodeMathDiv { value_1, value_2 }),
+    },
+];
+
+const PREFIX_OPERATORS: [PrefixOperator; 1] = [
+    PrefixOperator {
+        token: TokenType::Minus, power: 5,
+        build: |value| OperationType::Negate(NodeMathNegate { value }),
+    },
+];
+
+
+impl Parser {
+    /// Parses an expression starting at the current token, and stops at the first token
+    /// that can't continue it, such as `)` or `;`
+    pub(super) fn parse_expr(&mut self) -> Result<MathValue, Error> {
+        self.parse_expr_power(0)
+    }
 
-            let first_token = &tokens[self.index];
-            let first_token_type = &first_token.token;
+    /// Parses operators that bind at least as tightly as `min_power`
+    fn parse_expr_power(&mut self, min_power: u8) -> Result<MathValue, Error> {
+        let mut value_1 = self.parse_factor()?;
 
-            self.index += 1;
-
-            let value_2 = self.parse_product(tokens)?;
+        while let Some(token) = self.tokens.get(self.index) {
+            let Some(operator) = INFIX_OPERATORS.iter().find(|op| op.token == token.token) else {
+                // not an operator, the expression is finished
+                break;
+            };
 
+            if operator.left_power < min_power {
+                break;
+            }
 
-            let operation = match first_token_type { 
-                TokenType::Plus => { 
-                    let add_node = NodeMathAdd { value_1, value_2 };
-                    Box::new(OperationType::Add(add_node))
-                },
-                TokenType::Minus => { 
-                    let sub_node = NodeMathSub { value_1, value_2 };
-                    Box::new(OperationType::Sub(sub_node))
-                },
-                
-                _ => return Err( Error { line: first_token.line, msg: format!("Expected operand, found {}", first_token.info) } ),
-            };
+            self.index += 1;
 
-            let math_operation = MathValue::Operation(operation);
+            let value_2 = self.parse_expr_power(operator.right_power)?;
 
-            value_1 = math_operation;
+            value_1 = MathValue::Operation(Box::new((operator.build)(value_1, value_2)));
         }
 
         return Ok(value_1);
     }
 
-    fn parse_product(&mut self, tokens: &[Token]) -> Result<MathValue, Error> {
-        let mut value_1 = self.parse_factor(tokens)?;
-
-        while self.index < tokens.len()
-        && (tokens[self.index].token == TokenType::Star || tokens[self.index].token == TokenType::ForwardsSlash) {
-
-            let first_token = &tokens[self.index];
-            let first_token_type = &first_token.token;
-
-            self.index += 1;
-
-            let value_2 = self.parse_factor(tokens)?;
-
-            let operation = match first_token_type {
-                TokenType::Star => {
-                    let add_node = NodeMathMult { value_1, value_2 };
-
-                    Box::new(OperationType::Mult(add_node))
-                }
+    /// Parses a factor of an operation, a literal, a variable, a parenthesised expression
+    /// or a prefix operator applied to a factor
+    fn parse_factor(&mut self) -> Result<MathValue, Error> {
+        let token = self.require_token(0, TokenType::NoToken)?;
+        self.index += 1;
 
-                TokenType::ForwardsSlash => {
-                    let add_node = NodeMathDiv { value_1, value_2 };
+        match token.token {
+            TokenType::IntegerLit => return Ok(MathValue::Integer(token)),
+            TokenType::Identifier if self.require_token(0, TokenType::ParenOpen).is_ok() => {
+                // go back to the name, the call is parsed the same as a statement
+                self.index -= 1;
 
-                    Box::new(OperationType::Div(add_node))
-                }
+                return Ok(MathValue::Call(self.parse_call()?));
+            },
+            TokenType::Identifier => return Ok(MathValue::Identifier(token)),
 
-                _ => return Err(
-                    Error { line: first_token.line, msg: format!("Expected operand, found {}", first_token.info) }
-                ),
-            };
-            let math_operation = MathValue::Operation(operation);
+            TokenType::ParenOpen => {
+                let math_value = self.parse_expr_power(0)?;
 
-            value_1 = math_operation;
-        }
+                let _paren = self.require_token(0, TokenType::ParenClose)?;
+                self.index += 1;
 
-        return Ok(value_1);
-    }
+                return Ok(math_value);
+            },
 
-    /// Parses a factor of an operation
-    fn parse_factor(&mut self, tokens: &[Token]) -> Result<MathValue, Error> {
-        // Make sure there is something there
-        if self.index >= tokens.len() {
-            return Err(Error { line: tokens[tokens.len() - 1].line, msg: String::from("Expected a factor") })
+            _ => {},
         }
 
-        let token = &tokens[self.index];
-        self.index += 1;
+        if let Some(operator) = PREFIX_OPERATORS.iter().find(|op| op.token == token.token) {
+            let value = self.parse_expr_power(operator.power)?;
 
-        if token.token == TokenType::IntegerLit {
-            return Ok(MathValue::Integer(token.clone()));
-        }
-        else if token.token == TokenType::Identifier {
-            return Ok(MathValue::Identifier(token.clone()));
+            return Ok(MathValue::Operation(Box::new((operator.build)(value))));
         }
-        else if token.token == TokenType::ParenOpen {
-            let math_value = self.parse_sum(tokens)?;
 
-            if self.index < tokens.len() && tokens[self.index].token == TokenType::ParenClose {
-                self.index += 1;
-                return Ok(math_value);
-            } else {
-                return Err( Error { line: tokens[tokens.len()].line, msg: "Expected a closing paren".to_string() } );
-            }
-        } else {
-            return Err ( Error { line: token.line, msg: format!("Expected a factor, found: {}", token.info) } )
-        }
-    }
-}
-
-
-pub fn parse_expression(tokens: &[Token]) -> Result<MathValue, Error> {
-    let mut expr_parser = ExpressionParser { index: 0 };
-    let math_value = expr_parser.parse_sum(tokens);
-    
-    if expr_parser.index != tokens.len() {
-        dbg!(&tokens);
-        dbg!(&tokens[expr_parser.index..]);
-        panic!("Internal error, not all of the tokens for the expression were used!");
+        return Err( Error { line: token.line, msg: format!("Expected an expression, found {}", token.info) } );
     }
-
-    return math_value;
 }
-
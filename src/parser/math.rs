@@ -1,10 +1,13 @@
 use crate::tokenise::{Token, TokenType};
 
+use super::NodeStmtFunctionCall;
+
 #[derive(Debug)]
 pub enum MathValue {
     Integer(Token),
     Identifier(Token),
     Operation(Box<OperationType>),
+    Call(NodeStmtFunctionCall),
 }
 
 #[derive(Debug)]
@@ -13,6 +16,7 @@ pub enum OperationType {
     Sub(NodeMathSub),
     Mult(NodeMathMult),
     Div(NodeMathDiv),
+    Negate(NodeMathNegate),
 }
 
 #[derive(Debug)]
@@ -44,19 +48,33 @@ pub struct NodeMathNegate {
     pub value: MathValue,
 }
 
-pub const TOKENS_MATH: [TokenType; 8] = [
-    TokenType::ParenOpen,
-    TokenType::ParenClose,
-
-    TokenType::Plus,
-    TokenType::Minus,
-    TokenType::Star,
-    TokenType::ForwardsSlash,
-
-    TokenType::IntegerLit,
+impl MathValue {
+    /// Every call in the expression, including the ones in the arguments of other calls
+    pub fn calls(&self) -> Vec<&NodeStmtFunctionCall> {
+        match self {
+            MathValue::Integer(_) | MathValue::Identifier(_) => vec!(),
+            MathValue::Operation(oper) => oper.operands().into_iter().flat_map(MathValue::calls).collect(),
+            MathValue::Call(call) => {
+                let mut calls = vec!(call);
+                calls.extend(call.args.iter().flat_map(MathValue::calls));
+
+                calls
+            },
+        }
+    }
+}
 
-    TokenType::Identifier,
-];
+impl OperationType {
+    pub fn operands(&self) -> Vec<&MathValue> {
+        match self {
+            OperationType::Add(add) => vec!(&add.value_1, &add.value_2),
+            OperationType::Sub(sub) => vec!(&sub.value_1, &sub.value_2),
+            OperationType::Mult(mult) => vec!(&mult.value_1, &mult.value_2),
+            OperationType::Div(div) => vec!(&div.value_1, &div.value_2),
+            OperationType::Negate(negate) => vec!(&negate.value),
+        }
+    }
+}
 
 pub const TOKENS_OPERANDS: [TokenType; 2] = [
     TokenType::IntegerLit,
@@ -0,0 +1,114 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crate::{
+    errors::{print_inline_error, Error},
+    interpret::{self, Globals},
+    ir,
+    opt::fold,
+    parser::Parser,
+    resolve,
+    tokenise::{Token, TokenType, Tokeniser},
+};
+
+/// Everything entered so far. Each input is parsed and checked together with the ones
+/// before it, so it can use their variables and functions, then only its own
+/// statements are run. The line numbers of errors count from the start of the session
+struct Session {
+    /// The inputs that were accepted, one after another
+    source: String,
+    /// How many top level statements have run
+    statements: usize,
+    globals: Globals,
+}
+
+/// Reads statements from stdin and runs them with the tree-walking interpreter as soon
+/// as an input is complete, an input continues over several lines until its braces are
+/// balanced. Returns the exit code, of an `exit` or 0 at the end of the input
+pub fn run() -> i32 {
+    let mut session = Session { source: String::new(), statements: 0, globals: Globals::new() };
+    let mut input = String::new();
+
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+
+    loop {
+        if interactive {
+            let line = session.source.matches('\n').count() + input.matches('\n').count() + 1;
+            let marker = if input.is_empty() { '>' } else { '|' };
+
+            print!("{:>3}{} ", line, marker);
+            io::stdout().flush().expect("Could not write to stdout");
+        }
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).expect("Could not read from stdin") == 0 {
+            if interactive {
+                println!();
+            }
+            return 0;
+        }
+
+        // the accepted source always ends with a new line, so the line numbers carry on
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        input.push_str(&line);
+
+        let source = format!("{}{}", session.source, input);
+        let tokens = match Tokeniser::new(source.clone(), false).tokenise() {
+            Ok(tokens) => tokens,
+            Err(err) => {
+                print_inline_error(&err, &source);
+                input.clear();
+                continue;
+            },
+        };
+
+        // the same count `parse_scope` uses, the input goes on until the braces are
+        // balanced and the last statement has ended. Outside of braces an empty line ends
+        // it anyway, to get the error if it is missing something, inside them it can be
+        // part of a function
+        let mut brace_count = 0;
+        for token in &tokens {
+            if token.token == TokenType::BraceOpen {
+                brace_count += 1;
+            } else if token.token == TokenType::BraceClose {
+                brace_count -= 1;
+            }
+        }
+
+        let ended = matches!(tokens.last(), None | Some(Token { token: TokenType::Semicolon | TokenType::BraceClose, .. }));
+        if brace_count > 0 || (!ended && !line.trim().is_empty()) {
+            continue;
+        }
+
+        input.clear();
+
+        match session.run_input(source.clone(), tokens) {
+            Ok(Some(exit_code)) => return exit_code,
+            Ok(None) => (),
+            Err(err) => print_inline_error(&err, &source),
+        }
+    }
+}
+
+impl Session {
+    /// Runs the statements that are new in `source`. An input with an error in it is
+    /// dropped, but one that fails while running is kept, it may have defined something
+    /// before it failed
+    fn run_input(&mut self, source: String, tokens: Vec<Token>) -> Result<Option<i32>, Error> {
+        let mut parser = Parser { tokens, index: 0 };
+        let program = parser.parse()?;
+
+        resolve::check_functions(&program)?;
+        resolve::check_no_externs(&program, resolve::NOT_NATIVE)?;
+        ir::lower_program(&program)?;
+        fold::check_divisors(&program)?;
+
+        let start = self.statements;
+        self.source = source;
+        self.statements = program.statements.len();
+
+        return interpret::run_statements(&program, start, &mut self.globals);
+    }
+}
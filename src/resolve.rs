@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use crate::{
+    errors::Error,
+    parser::*,
+    tokenise::Token,
+};
+
+/// What a call needs to know about a function before its body has been seen
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Signature {
+    pub identifier: Token,
+    pub params: usize,
+    /// Only extern functions declared without `-> int` don't return a value
+    pub returns: bool,
+}
+
+/// Checks every function call in the program against the declared functions.
+///
+/// The signatures of all the functions in a scope are collected before any of its
+/// statements are checked, so a function can be called before it is defined and
+/// functions can call each other. A function is visible in the scope it is defined in,
+/// and every scope nested inside that one. Extern functions are declared the same way
+pub fn check_functions(program: &NodeProgram) -> Result<(), Error> {
+    let mut resolver = Resolver { scopes: vec!(), labels: HashMap::new() };
+
+    resolver.check_scope(program)
+}
+
+struct Resolver {
+    /// The functions visible at each level of nesting, innermost last
+    scopes: Vec<HashMap<String, Signature>>,
+    /// Every function in the program, all of them share one label namespace in the asm
+    labels: HashMap<String, Token>,
+}
+
+impl Resolver {
+    fn check_scope(&mut self, program: &NodeProgram) -> Result<(), Error> {
+        let signatures = self.collect_signatures(program)?;
+        self.scopes.push(signatures);
+
+        for stmt in &program.statements {
+            if let NodeStatements::Function(func_stmt) = stmt {
+                self.check_scope(&func_stmt.scope)?;
+            }
+
+            // a call on its own is the only one whose value isn't used
+            if let NodeStatements::FunctionCall(func_call_stmt) = stmt {
+                self.check_call(func_call_stmt, false)?;
+            }
+
+            for expression in stmt.expressions() {
+                for call in expression.calls() {
+                    self.check_call(call, true)?;
+                }
+            }
+        }
+
+        self.scopes.pop();
+
+        Ok(())
+    }
+
+    /// The declaration collection pass, registers the functions of one scope
+    fn collect_signatures(&mut self, program: &NodeProgram) -> Result<HashMap<String, Signature>, Error> {
+        let mut signatures = HashMap::new();
+
+        for stmt in &program.statements {
+            let (identifier, params, returns) = match stmt {
+                NodeStatements::Function(func_stmt) => (&func_stmt.identifier, func_stmt.args.len(), true),
+                NodeStatements::Extern(extern_stmt) => (&extern_stmt.identifier, extern_stmt.args.len(), extern_stmt.returns),
+                _ => continue,
+            };
+
+            if let Some(previous) = self.labels.get(&identifier.info) {
+                return Err( Error {
+                    line: identifier.line,
+                    msg: format!("Function {} has already been declared on line {}", identifier.info, previous.line),
+                } );
+            }
+
+            self.labels.insert(identifier.info.clone(), identifier.clone());
+            signatures.insert(
+                identifier.info.clone(),
+                Signature { identifier: identifier.clone(), params, returns },
+            );
+        }
+
+        return Ok(signatures);
+    }
+
+    /// `needs_value` is whether the call is part of an expression
+    fn check_call(&self, func_call_stmt: &NodeStmtFunctionCall, needs_value: bool) -> Result<(), Error> {
+        let identifier = &func_call_stmt.identifier;
+
+        let Some(signature) = self.lookup(&identifier.info) else {
+            return Err( Error { line: identifier.line, msg: format!("Call to unknown function {}", identifier.info) } );
+        };
+
+        if signature.params != func_call_stmt.args.len() {
+            return Err( Error {
+                line: identifier.line,
+                msg: format!(
+                    "Function {} takes {} argument(s) but {} were given, it is defined on line {}",
+                    identifier.info, signature.params, func_call_stmt.args.len(), signature.identifier.line,
+                ),
+            } );
+        }
+
+        if needs_value && !signature.returns {
+            return Err( Error {
+                line: identifier.line,
+                msg: format!(
+                    "Function {} doesn't return a value, declare it with `-> int` on line {} to use what it returns",
+                    identifier.info, signature.identifier.line,
+                ),
+            } );
+        }
+
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Signature> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+}
+
+/// Why the interpreter and the bytecode vm turn extern functions away
+pub const NOT_NATIVE: &str = "can only be called from native code, compile the program or use `run --jit`";
+
+/// Why the AArch64 backend turns extern functions away
+pub const NO_LIBC: &str = "can't be called from AArch64 code, it is linked without the C library";
+
+/// Checks that the program doesn't declare extern functions, for the ways of running it
+/// that can't call the C library. The reason goes at the end of the error
+pub fn check_no_externs(program: &NodeProgram, reason: &str) -> Result<(), Error> {
+    if let Some(extern_stmt) = program.externs().first() {
+        return Err( Error {
+            line: extern_stmt.identifier.line,
+            msg: format!("Extern function {} {}", extern_stmt.identifier.info, reason),
+        } );
+    }
+
+    Ok(())
+}
+
+/// Checks that no function is called `init`, an object file exports the top level code
+/// under that name
+pub fn check_no_init(program: &NodeProgram) -> Result<(), Error> {
+    if let Some(func_stmt) = program.functions().into_iter().find(|func_stmt| func_stmt.identifier.info == "init") {
+        return Err( Error {
+            line: func_stmt.identifier.line,
+            msg: "Function init can't be exported, an object file exports the top level code as init".to_string(),
+        } );
+    }
+
+    Ok(())
+}
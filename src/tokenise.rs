@@ -12,6 +12,8 @@ pub enum TokenType {
     Exit,
     PutChar,
 
+    Return,
+
     AssignEq,
 
     // math
@@ -30,10 +32,20 @@ pub enum TokenType {
     IntegerLit,
 
     Function,
+    Extern,
+    // `->`, before the return type of an extern function
+    Arrow,
 
     Identifier,
 
     Semicolon,
+    Comma,
+
+    // attributes, `#[inline]`
+    Hash,
+    BracketOpen,
+    BracketClose,
+
     NoToken,
 }
 
@@ -44,6 +56,12 @@ pub struct Token {
     pub token: TokenType,
     pub info: String,
     pub line: usize,
+    /// The column of the first character, starting from 1
+    pub column: usize,
+    /// The value of an `IntegerLit`, stored as a 64 bit two's complement number
+    pub value: Option<i64>,
+    /// The `///` doc comment lines directly before this token, joined with newlines
+    pub doc: Option<String>,
 }
 
 pub struct Tokeniser {
@@ -51,6 +69,12 @@ pub struct Tokeniser {
     debug: bool,
     index: usize,
     line_num: usize,
+    /// The index the current line starts at, for working out columns
+    line_start: usize,
+    /// The index the last word started at
+    word_start: usize,
+    /// Doc comment lines waiting to be attached to the next token
+    doc_lines: Vec<String>,
 }
 
 impl Tokeniser {
@@ -60,6 +84,9 @@ impl Tokeniser {
             debug,
             index: 0,
             line_num: 1,
+            line_start: 0,
+            word_start: 0,
+            doc_lines: vec!(),
         }
     }
 
@@ -67,17 +94,22 @@ impl Tokeniser {
         let mut tokens: Vec<Token> = vec!();
 
         while self.index < self.source.len() {
-            let Some(current_word) = self.get_next_word() else { self.index += 1; continue; };
-            
+            // comments and trailing whitespace don't give us a word
+            let Some(current_word) = self.get_next_word()? else { continue; };
+
             dbg_m(&current_word, self.debug);
 
             let mut token_type = match current_word.as_str() {
                 ";" => TokenType::Semicolon,
+                "," => TokenType::Comma,
                 "(" => TokenType::ParenOpen,
                 ")" => TokenType::ParenClose,
                 "{" => TokenType::BraceOpen,
                 "}" => TokenType::BraceClose,
                 "=" => TokenType::AssignEq,
+                "#" => TokenType::Hash,
+                "[" => TokenType::BracketOpen,
+                "]" => TokenType::BracketClose,
 
                 "+" => TokenType::Plus,
                 "-" => TokenType::Minus,
@@ -87,17 +119,25 @@ impl Tokeniser {
 
                 "exit" => TokenType::Exit,
                 "putchar" => TokenType::PutChar,
+                "return" => TokenType::Return,
 
                 "int" => TokenType::IntType,
                 "fn" => TokenType::Function,
+                "extern" => TokenType::Extern,
+                "->" => TokenType::Arrow,
 
                 _ => TokenType::NoToken,
             };
 
+            let mut value = None;
+
             if token_type == TokenType::NoToken {
-                if current_word.chars().nth(0).expect("Word was empty").is_numeric() {
+                let first = current_word.chars().next().expect("Word was empty");
+
+                if first.is_numeric() {
                     token_type = TokenType::IntegerLit;
-                } else if current_word.chars().nth(0).expect("Word was empty").is_alphabetic() {
+                    value = Some(self.parse_integer(&current_word)?);
+                } else if first.is_alphabetic() {
                     token_type = TokenType::Identifier;
                 }
                 else {
@@ -107,12 +147,65 @@ impl Tokeniser {
                 }
             }
 
-            tokens.push(Token { token: token_type, info: current_word, line: self.line_num });
+            let doc = if self.doc_lines.is_empty() {
+                None
+            } else {
+                Some(self.doc_lines.drain(..).collect::<Vec<String>>().join("\n"))
+            };
+
+            // the parser only keeps the doc comments of declarations, anywhere else one
+            // would be lost
+            if doc.is_some() && !documents_declaration(&token_type, tokens.last()) {
+                return Err(self.create_err(format!("Doc comments have to be above a declaration, found {}", current_word)));
+            }
+
+            let column = self.word_start - self.line_start + 1;
+
+            tokens.push(Token { token: token_type, info: current_word, line: self.line_num, column, value, doc });
+        }
+
+        if !self.doc_lines.is_empty() {
+            return Err(self.create_err("Doc comments have to be above a declaration, found the end of the file".to_string()));
         }
 
         return Ok(tokens);
     }
 
+    /// Parses an integer literal, which can be written in decimal, or in hex, binary or
+    /// octal with a `0x`, `0b` or `0o` prefix. Underscores can be used as separators
+    /// between the digits
+    fn parse_integer(&self, word: &str) -> Result<i64, Error> {
+        let (radix, digits, kind) = match word.get(..2) {
+            Some("0x") | Some("0X") => (16, &word[2..], "hex"),
+            Some("0b") | Some("0B") => (2, &word[2..], "binary"),
+            Some("0o") | Some("0O") => (8, &word[2..], "octal"),
+            _ => (10, word, "decimal"),
+        };
+
+        let separated = digits;
+        let digits: String = digits.chars().filter(|c| *c != '_').collect();
+
+        if digits.is_empty() {
+            return Err(self.create_err(format!("Expected digits after the prefix in {}", word)));
+        }
+
+        if separated.starts_with('_') || separated.ends_with('_') {
+            return Err(self.create_err(format!("Underscores in {} have to be between digits", word)));
+        }
+
+        if let Some(invalid) = digits.chars().find(|c| !c.is_digit(radix)) {
+            return Err(self.create_err(format!("Invalid digit `{}` in {} literal {}", invalid, kind, word)));
+        }
+
+        // the only error left is overflow, anything above 64 bits can't be represented
+        let Ok(value) = u64::from_str_radix(&digits, radix) else {
+            return Err(self.create_err(format!("Integer literal {} does not fit in 64 bits", word)));
+        };
+
+        // values above `i64::MAX` wrap around, the same as the 64 bit arithmetic
+        return Ok(value as i64);
+    }
+
     fn create_err(&self, msg: String) -> Error {
         Error { line: self.line_num, msg }
     }
@@ -123,17 +216,69 @@ impl Tokeniser {
         {
             if self.source.chars().nth(self.index).unwrap() == '\n' {
                 self.line_num += 1;
+                self.line_start = self.index + 1;
             }
 
             self.index += 1;
         }
     }
 
-    fn get_next_word(&mut self) -> Option<String> {
+    /// Skips a `//` comment until the new line, keeping the text if it is a `///` doc comment
+    fn skip_line_comment(&mut self) {
+        let start = self.index;
+
+        while self.index < self.source.len() && self.source.chars().nth(self.index).expect("error") != '\n' {
+            self.index += 1;
+        }
+
+        let comment: String = self.source.chars().skip(start).take(self.index - start).collect();
+
+        // `////` is just a normal comment, the same as in rust
+        if let Some(doc) = comment.strip_prefix("///") {
+            if !doc.starts_with('/') {
+                let doc = doc.strip_prefix(' ').unwrap_or(doc);
+                self.doc_lines.push(doc.trim_end().to_string());
+            }
+        }
+    }
+
+    /// Skips a `/* */` comment, these can be nested
+    fn skip_block_comment(&mut self) -> Result<(), Error> {
+        let start_line = self.line_num;
+
+        // account for /*
+        self.index += 2;
+        let mut depth = 1;
+
+        while depth > 0 {
+            let Some(c) = self.source.chars().nth(self.index) else {
+                return Err( Error { line: start_line, msg: "Unterminated block comment, expected `*/`".to_string() } );
+            };
+            let next = self.source.chars().nth(self.index + 1);
+
+            if c == '/' && next == Some('*') {
+                depth += 1;
+                self.index += 2;
+            } else if c == '*' && next == Some('/') {
+                depth -= 1;
+                self.index += 2;
+            } else {
+                if c == '\n' {
+                    self.line_num += 1;
+                    self.line_start = self.index + 1;
+                }
+                self.index += 1;
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn get_next_word(&mut self) -> Result<Option<String>, Error> {
         self.skip_whitespace();
 
         if self.index >= self.source.len() {
-            return None;
+            return Ok(None);
         }
 
         let first_char = self.source.chars().nth(self.index).expect("Could not index string!");
@@ -141,16 +286,25 @@ impl Tokeniser {
         let second_char = self.source.chars().nth(self.index + 1);
 
         // `//` comment testing
-        if second_char.is_some() {
-            if second_char.expect("Internal error") == '/' && first_char == '/' {
-                // skip the comment until new line
-                while self.index < self.source.len() && self.source.chars().nth(self.index).expect("error") != '\n' {
-                    self.index += 1;
-                }
-                
-                // we didn't get any tokens, we just skipped comment
-                return None;
-            }
+        if first_char == '/' && second_char == Some('/') {
+            self.skip_line_comment();
+
+            // we didn't get any tokens, we just skipped comment
+            return Ok(None);
+        }
+
+        if first_char == '/' && second_char == Some('*') {
+            self.skip_block_comment()?;
+
+            return Ok(None);
+        }
+
+        self.word_start = self.index;
+
+        // the only symbol that is two characters
+        if first_char == '-' && second_char == Some('>') {
+            self.index += 2;
+            return Ok(Some("->".to_string()));
         }
 
         let mut word = String::from(first_char);
@@ -164,13 +318,13 @@ impl Tokeniser {
             }
 
             else if first_char.is_numeric() {
-                if !c.is_numeric() {
+                // letters are included for prefixes and hex digits, `parse_integer` checks them
+                if !(c.is_alphanumeric() || c == '_') {
                     break;
                 }
             } else {
-                if c != first_char {
-                    break;
-                }
+                // symbols are always one character, so `((` or `--` are two tokens
+                break;
             }
 
             self.index += 1;
@@ -179,11 +333,25 @@ impl Tokeniser {
 
 
         if word == String::new() {
-            return None;
+            return Ok(None);
         }
 
 
-        return Some(word);
+        return Ok(Some(word));
     }
 }
 
+/// Whether the token starts a declaration a doc comment can be written above: a
+/// variable, a parameter, a function or an extern function. The comment of a function
+/// with attributes goes above them
+fn documents_declaration(token_type: &TokenType, previous: Option<&Token>) -> bool {
+    let previous = previous.map(|token| &token.token);
+
+    match token_type {
+        // the return type of an extern function isn't a declaration
+        TokenType::IntType => previous != Some(&TokenType::Arrow),
+        TokenType::Function => previous != Some(&TokenType::BracketClose),
+        TokenType::Extern | TokenType::Hash => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,8 @@
+//! Golden file tests of the AArch64 backend
+
+mod common;
+
+#[test]
+fn aarch64_matches_golden_files() {
+    common::check_golden_files("aarch64", "s");
+}
@@ -0,0 +1,56 @@
+//! Assignment statements, `name = expression;`, which used to fail to parse
+
+mod common;
+
+use std::process::Command;
+
+use common::{atomic, write_program};
+
+#[test]
+fn assignments_parse() {
+    let path = write_program("assignment_parse", "int x = 1;\nx = 2;\nexit(x);\n");
+    let run = atomic(&["--emit=ir", &path]);
+
+    assert_eq!(run.status.code(), Some(0), "{}", String::from_utf8_lossy(&run.stderr));
+    assert!(String::from_utf8_lossy(&run.stdout).contains("    store @x, 1\n    store @x, 2\n"));
+}
+
+#[test]
+fn assignments_change_variables() {
+    let path = write_program(
+        "assignment_change",
+        "int total = 60;\n\nfn bump(int n) {\n    int local = n;\n    local = local + 1;\n    total = total + local;\n}\n\ntotal = total + 4;\nbump(0);\nbump(1);\nputchar(total);\nexit(total);\n",
+    );
+
+    let run = atomic(&["run", "--interpret", &path]);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "C");
+    assert_eq!(run.status.code(), Some(67));
+
+    let out_path = path.trim_end_matches(".at").to_string();
+    for level in ["-O0", "-O2"] {
+        let compiled = atomic(&[level, &path, &out_path]);
+        assert!(compiled.status.success(), "did not compile at {}:\n{}", level, String::from_utf8_lossy(&compiled.stderr));
+
+        let run = Command::new(&out_path).output().expect("Could not run the program");
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "C", "output at {}", level);
+        assert_eq!(run.status.code(), Some(67), "exit code at {}", level);
+    }
+}
+
+#[test]
+fn assignments_need_a_semicolon() {
+    let path = write_program("assignment_semicolon", "int x = 1;\nx = 2\nexit(x);\n");
+    let run = atomic(&["run", "--interpret", &path]);
+
+    assert_eq!(run.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("Expected Semicolon"));
+}
+
+#[test]
+fn assignments_to_undeclared_variables_are_errors() {
+    let path = write_program("assignment_undeclared", "x = 2;\n");
+    let run = atomic(&["run", "--interpret", &path]);
+
+    assert_eq!(run.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("Variable x has not been declared"));
+}
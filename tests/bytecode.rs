@@ -0,0 +1,136 @@
+//! Runs the golden programs with the bytecode vm, straight from the source and after
+//! saving the bytecode to a file, and checks that malformed files are rejected
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+use common::atomic;
+
+fn check_run(run: &Output, case: &common::Case, how: &str) {
+    assert_eq!(String::from_utf8_lossy(&run.stdout), case.output, "output of {} {}", case.name, how);
+    assert_eq!(run.status.code(), Some(case.exit_code), "exit code of {} {}", case.name, how);
+}
+
+#[test]
+fn vm_runs_golden_programs() {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+
+    for case in &common::CASES {
+        let source = golden_dir.join(format!("{}.at", case.name));
+        let source = source.to_str().unwrap();
+
+        let mut args = vec!("run");
+        args.extend(case.options);
+        args.push(source);
+        check_run(&atomic(&args), case, "from source");
+    }
+}
+
+#[test]
+fn vm_runs_saved_bytecode() {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+
+    for case in &common::CASES {
+        let source = golden_dir.join(format!("{}.at", case.name));
+        let out_path = out_dir.join(format!("bytecode_{}", case.name));
+
+        let mut args = vec!("--target=bytecode");
+        args.extend(case.options);
+        args.push(source.to_str().unwrap());
+        args.push(out_path.to_str().unwrap());
+
+        let compiled = atomic(&args);
+        assert!(compiled.status.success(), "{} did not compile:\n{}", case.name, String::from_utf8_lossy(&compiled.stderr));
+
+        let saved = format!("{}.atbc", out_path.display());
+        check_run(&atomic(&["run", &saved]), case, "from the saved bytecode");
+    }
+}
+
+/// A bytecode file with no globals, and functions made of their name, params, locals and
+/// the bytes of their ops
+fn bytecode_file(version: u8, functions: &[(&str, u32, u32, &[&[u8]])]) -> Vec<u8> {
+    let mut bytes = b"ATBC".to_vec();
+    bytes.push(version);
+    bytes.extend_from_slice(&0_u32.to_le_bytes());
+
+    bytes.extend_from_slice(&(functions.len() as u32).to_le_bytes());
+    for (name, params, locals, ops) in functions {
+        bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&params.to_le_bytes());
+        bytes.extend_from_slice(&locals.to_le_bytes());
+
+        bytes.extend_from_slice(&(ops.len() as u32).to_le_bytes());
+        for op in *ops {
+            bytes.extend_from_slice(op);
+            // the line
+            bytes.extend_from_slice(&1_u32.to_le_bytes());
+        }
+    }
+
+    bytes
+}
+
+/// `Const` with its value
+fn constant(value: i64) -> Vec<u8> {
+    let mut op = vec!(0);
+    op.extend_from_slice(&value.to_le_bytes());
+    op
+}
+
+/// An op with an index, like `LoadGlobal` or `Call`
+fn indexed(opcode: u8, index: u32) -> Vec<u8> {
+    let mut op = vec!(opcode);
+    op.extend_from_slice(&index.to_le_bytes());
+    op
+}
+
+const ADD: &[u8] = &[5];
+const EXIT: &[u8] = &[13];
+const RETURN: &[u8] = &[12];
+
+#[test]
+fn malformed_bytecode_files_are_rejected() {
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let exit_7 = constant(7);
+
+    let mut truncated = bytecode_file(1, &[("main", 0, 0, &[&exit_7, EXIT])]);
+    truncated.truncate(truncated.len() - 3);
+    let mut trailing = bytecode_file(1, &[("main", 0, 0, &[&exit_7, EXIT])]);
+    trailing.push(0);
+
+    let cases = [
+        ("version", bytecode_file(9, &[("main", 0, 0, &[&exit_7, EXIT])]), "Bytecode version 9 isn't supported"),
+        ("truncated", truncated, "The bytecode file ends too early"),
+        ("trailing", trailing, "Unexpected bytes after the program"),
+        ("opcode", bytecode_file(1, &[("main", 0, 0, &[&[99]])]), "Unknown opcode 99"),
+        ("empty", bytecode_file(1, &[]), "The program has no top level code"),
+        ("top_level_params", bytecode_file(1, &[("main", 1, 1, &[&exit_7, EXIT])]), "The top level code has parameters"),
+        ("locals", bytecode_file(1, &[("main", 0, u32::MAX, &[&exit_7, EXIT])]), "Function main has more than 65536 locals"),
+        ("params", bytecode_file(1, &[("main", 0, 0, &[&exit_7, EXIT]), ("f", 2, 1, &[&exit_7, RETURN])]), "Function f has more parameters than locals"),
+        ("global", bytecode_file(1, &[("main", 0, 0, &[&indexed(1, 0), EXIT])]), "LoadGlobal(0) in function main is out of range"),
+        ("local", bytecode_file(1, &[("main", 0, 1, &[&indexed(3, 1), EXIT])]), "LoadLocal(1) in function main is out of range"),
+        ("call", bytecode_file(1, &[("main", 0, 0, &[&indexed(10, 1), EXIT])]), "Call(1) in function main is out of range"),
+        ("underflow", bytecode_file(1, &[("main", 0, 0, &[&exit_7, ADD, EXIT])]), "Add in function main pops more values than there are"),
+        ("end", bytecode_file(1, &[("main", 0, 0, &[&exit_7])]), "Function main doesn't end with a return or exit"),
+    ];
+
+    for (name, bytes, message) in cases {
+        let path = out_dir.join(format!("malformed_{}.atbc", name));
+        fs::write(&path, bytes).expect("Could not write the bytecode");
+
+        let run = atomic(&["run", path.to_str().unwrap()]);
+        assert_eq!(run.status.code(), Some(2), "exit code of {}:\n{}", name, String::from_utf8_lossy(&run.stderr));
+        assert!(String::from_utf8_lossy(&run.stderr).contains(message), "error of {}:\n{}", name, String::from_utf8_lossy(&run.stderr));
+    }
+
+    // the same file put together properly runs
+    let path = out_dir.join("malformed_none.atbc");
+    fs::write(&path, bytecode_file(1, &[("main", 0, 0, &[&exit_7, EXIT])])).expect("Could not write the bytecode");
+    assert_eq!(atomic(&["run", path.to_str().unwrap()]).status.code(), Some(7));
+}
@@ -0,0 +1,97 @@
+//! Golden file tests of the C backend, and running the golden files compiled with `cc`
+
+mod common;
+
+use std::fs;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+/// Compiles the C file with `cc` and runs it
+fn compile_and_run(c_path: &Path, out_path: &Path) -> (String, ExitStatus) {
+    let compiled = Command::new("cc")
+        .args(["-std=c99", "-Wall", "-Werror", "-fsanitize=undefined", "-fno-sanitize-recover"])
+        .arg(c_path)
+        .arg("-o")
+        .arg(out_path)
+        .output()
+        .expect("Could not run cc");
+    assert!(compiled.status.success(), "{} did not compile:\n{}", c_path.display(), String::from_utf8_lossy(&compiled.stderr));
+
+    let run = Command::new(out_path).output().expect("Could not run the program");
+
+    (String::from_utf8_lossy(&run.stdout).to_string(), run.status)
+}
+
+#[test]
+fn c_matches_golden_files() {
+    common::check_golden_files("c", "c");
+}
+
+#[test]
+fn c_golden_files_run() {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/c");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+
+    for common::Case { name, output, exit_code, .. } in common::CASES {
+        let (stdout, status) = compile_and_run(&golden_dir.join(format!("{}.c", name)), &out_dir.join(format!("c_run_{}", name)));
+
+        assert_eq!(stdout, output, "output of {}", name);
+        assert_eq!(status.code(), Some(exit_code), "exit code of {}", name);
+    }
+}
+
+#[test]
+fn division_traps_like_native_code() {
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+
+    // the divisors are only known when the program runs
+    let cases = [
+        ("zero", "int zero = 0;\nexit(5 / zero);\n"),
+        ("overflow", "int smallest = -9223372036854775807 - 1;\nint minus_one = -1;\nexit(smallest / minus_one);\n"),
+    ];
+
+    for (name, source) in cases {
+        let source_path = common::write_program(&format!("c_divide_{}", name), source);
+        let out_path = out_dir.join(format!("c_divide_{}", name));
+
+        let native = common::atomic(&["-O0", &source_path, out_path.to_str().unwrap()]);
+        assert!(native.status.success(), "{} did not compile:\n{}", name, String::from_utf8_lossy(&native.stderr));
+        let native = Command::new(&out_path).status().expect("Could not run the program");
+
+        let translated = common::atomic(&["-O0", "--target=c", &source_path, out_path.to_str().unwrap()]);
+        assert!(translated.status.success(), "{} did not translate:\n{}", name, String::from_utf8_lossy(&translated.stderr));
+        let (_, status) = compile_and_run(&out_dir.join(format!("c_divide_{}.c", name)), &out_path);
+
+        // SIGFPE
+        assert_eq!(native.signal(), Some(8), "native signal of {}", name);
+        assert_eq!(status.signal(), Some(8), "signal of {} in C", name);
+    }
+}
+
+#[test]
+fn externs_declared_by_the_c_library_headers_compile() {
+    let source = "\
+extern fn abs(int n) -> int;
+extern fn labs(int n) -> int;
+extern fn toupper(int c) -> int;
+
+putchar(toupper(abs(-97)));
+putchar(48 + labs(3 - 10));
+exit(abs(-4) * labs(-5));
+";
+    let source_path = common::write_program("c_externs", source);
+    let out_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("c_externs");
+
+    let translated = common::atomic(&["--target=c", &source_path, out_path.to_str().unwrap()]);
+    assert!(translated.status.success(), "did not translate:\n{}", String::from_utf8_lossy(&translated.stderr));
+
+    let c = fs::read_to_string(out_path.with_extension("c")).expect("Could not read the C file");
+    assert!(c.contains("#include <ctype.h>\n"), "{}", c);
+    // the headers declare them, a prototype of their own would conflict
+    assert!(!c.contains("int64_t abs(") && !c.contains("int64_t labs("), "{}", c);
+
+    let (stdout, status) = compile_and_run(&out_path.with_extension("c"), &out_path);
+    assert_eq!(stdout, "A7");
+    assert_eq!(status.code(), Some(20));
+}
@@ -0,0 +1,93 @@
+//! Line, block and doc comments: nesting, the lines they take up, and where doc comments
+//! can go
+
+mod common;
+
+use std::process::Output;
+
+use common::{atomic, write_program};
+
+fn interpret(name: &str, source: &str) -> Output {
+    let path = write_program(&format!("comments_{}", name), source);
+
+    atomic(&["run", "--interpret", &path])
+}
+
+#[test]
+fn block_comments_nest() {
+    let run = interpret("nested", "/* outer /* inner */ still a comment */\nputchar(65); // exit(1);\n/* /* */ exit(2); */\nexit(3);\n");
+
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "A");
+    assert_eq!(run.status.code(), Some(3));
+}
+
+#[test]
+fn lines_are_counted_through_block_comments() {
+    let run = interpret("lines", "/* one\n   two /* three\n   four */\n   five */ int x = 1;\n// six\nexit(y);\n");
+
+    assert_eq!(run.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("Error in line 6:"), "{}", String::from_utf8_lossy(&run.stderr));
+}
+
+#[test]
+fn unterminated_block_comments_are_errors() {
+    let run = interpret("unterminated", "exit(0);\n/* one /* two */\nstill open\n");
+    let stderr = String::from_utf8_lossy(&run.stderr);
+
+    // the error is where the comment starts
+    assert_eq!(run.status.code(), Some(1));
+    assert!(stderr.contains("Error in line 2:"), "{}", stderr);
+    assert!(stderr.contains("Unterminated block comment, expected `*/`"), "{}", stderr);
+}
+
+#[test]
+fn doc_comments_are_kept_on_declarations() {
+    let source = "\
+/// a variable
+int x = 1;
+/// a function
+#[inline]
+fn f(
+    /// a parameter
+    int a
+) {
+    /// a local
+    int b = a;
+    return b;
+}
+/// an extern function
+extern fn labs(int n) -> int;
+exit(f(x));
+";
+    let path = write_program("comments_docs", source);
+    let run = atomic(&["--emit=ast", &path]);
+    let ast = String::from_utf8_lossy(&run.stdout);
+
+    assert_eq!(run.status.code(), Some(0), "{}", String::from_utf8_lossy(&run.stderr));
+    for doc in ["a variable", "a function", "a parameter", "a local", "an extern function"] {
+        assert!(ast.contains(&format!("\"doc\": \"{}\"", doc)), "{} is missing:\n{}", doc, ast);
+    }
+}
+
+#[test]
+fn doc_comments_anywhere_else_are_errors() {
+    let cases = [
+        ("statement", "int x = 1;\n/// exits\nexit(x);\n", "found exit"),
+        ("call", "fn f() {\n    return 1;\n}\n/// calls\nf();\n", "found f"),
+        ("expression", "exit(1 +\n/// two\n2);\n", "found 2"),
+        ("return_type", "extern fn labs(int n) ->\n/// returns\nint;\n", "found int"),
+        ("after_attributes", "#[inline]\n/// between\nfn f() {\n    return 1;\n}\n", "found fn"),
+        ("end_of_file", "exit(0);\n/// nothing\n", "found the end of the file"),
+    ];
+
+    for (name, source, found) in cases {
+        let run = interpret(name, source);
+        let stderr = String::from_utf8_lossy(&run.stderr);
+
+        assert_eq!(run.status.code(), Some(1), "exit code of {}", name);
+        assert!(stderr.contains(&format!("Doc comments have to be above a declaration, {}", found)), "error of {}:\n{}", name, stderr);
+    }
+
+    // four slashes are a normal comment
+    assert_eq!(interpret("four_slashes", "//// not a doc comment\nexit(4);\n").status.code(), Some(4));
+}
@@ -0,0 +1,88 @@
+//! Golden file testing shared by the backends. The programs are in `tests/golden`, and
+//! the expected output of each target is in a directory named after it. After an
+//! intended change, run with `UPDATE_GOLDEN=1` to write the new expected files
+
+// every test crate includes this, and not all of them use everything
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// A program in `tests/golden`, the options it is compiled with, and what it prints and
+/// exits with when it runs
+pub struct Case {
+    pub name: &'static str,
+    pub options: &'static [&'static str],
+    pub output: &'static str,
+    pub exit_code: i32,
+}
+
+pub const CASES: [Case; 4] = [
+    Case { name: "hello", options: &[], output: "iffiffiffiff\n", exit_code: 0 },
+    Case { name: "arithmetic", options: &["-O0"], output: "FD", exit_code: 65 },
+    Case { name: "calls", options: &["-O0"], output: "164", exit_code: 15 },
+    Case { name: "tail_calls", options: &["-O2"], output: "5", exit_code: 5 },
+];
+
+/// Runs the compiler with the arguments
+pub fn atomic(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_atomic-lang"))
+        .args(args)
+        .output()
+        .expect("Could not run atomic-lang")
+}
+
+/// Writes the program to `<name>.at` in the temp directory, and returns its path
+pub fn write_program(name: &str, source: &str) -> String {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.at", name));
+    fs::write(&path, source).expect("Could not write the program");
+
+    path.to_str().unwrap().to_string()
+}
+
+/// Whether the tool is installed, for the tests that use it if it is
+pub fn installed(tool: &str) -> bool {
+    Command::new(tool).arg("--version").output().is_ok_and(|output| output.status.success())
+}
+
+/// Compiles every case with `--target=<target>`, and checks the file it writes with
+/// `extension` against `tests/golden/<target>`
+pub fn check_golden_files(target: &str, extension: &str) {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut different = vec!();
+
+    for Case { name, options, .. } in CASES {
+        let source = golden_dir.join(format!("{}.at", name));
+        let out_path = out_dir.join(format!("{}_{}", target, name));
+
+        let output = Command::new(env!("CARGO_BIN_EXE_atomic-lang"))
+            .arg(format!("--target={}", target))
+            .args(options)
+            .arg(&source)
+            .arg(&out_path)
+            .output()
+            .expect("Could not run atomic-lang");
+
+        assert!(output.status.success(), "{} did not compile:\n{}", name, String::from_utf8_lossy(&output.stderr));
+
+        let generated = fs::read_to_string(format!("{}.{}", out_path.display(), extension)).expect("Nothing was written");
+        let golden_path = golden_dir.join(target).join(format!("{}.{}", name, extension));
+
+        if update {
+            fs::write(&golden_path, &generated).expect("Could not write the golden file");
+            continue;
+        }
+
+        let golden = fs::read_to_string(&golden_path).unwrap_or_default();
+        if generated != golden {
+            different.push(name);
+        }
+    }
+
+    assert!(different.is_empty(), "the {} output of {:?} doesn't match the golden files, rerun with UPDATE_GOLDEN=1 if that is intended", target, different);
+}
@@ -0,0 +1,59 @@
+//! Dead code elimination: what it removes, what it reports with `-d`, and that removed
+//! code is still checked
+
+mod common;
+
+use std::process::Output;
+
+/// Compiles the program with the options
+fn compile(name: &str, source: &str, options: &[&str]) -> Output {
+    let path = common::write_program(&format!("dce_{}", name), source);
+
+    let mut args = options.to_vec();
+    args.push(&path);
+    common::atomic(&args)
+}
+
+const PROGRAM: &str = "fn unused() {\n    return 1;\n}\n\nfn used() {\n    return 66;\n}\n\nputchar(used());\nexit(0);\nputchar(67);\n";
+
+#[test]
+fn unused_functions_and_code_after_an_exit_are_removed() {
+    let run = compile("removed", PROGRAM, &["-O1", "--emit=ir"]);
+    let ir = String::from_utf8_lossy(&run.stdout);
+
+    assert!(ir.contains("fn used()"));
+    assert!(!ir.contains("fn unused()"));
+    assert!(!ir.contains("putchar 67"));
+
+    let run = compile("kept", PROGRAM, &["-O0", "--emit=ir"]);
+    let ir = String::from_utf8_lossy(&run.stdout);
+
+    assert!(ir.contains("fn unused()"));
+    assert!(ir.contains("putchar 67"));
+}
+
+#[test]
+fn removed_code_is_reported_in_debug_mode() {
+    let run = compile("debug", PROGRAM, &["-O1", "-d", "--emit=ir"]);
+    let output = String::from_utf8_lossy(&run.stderr);
+
+    assert!(output.contains("dce: removed 1 statement(s) after an exit in the top level code"));
+    assert!(output.contains("dce: removed function unused defined on line 1, it is never called"));
+}
+
+#[test]
+fn errors_in_removed_code_are_reported_at_every_level() {
+    let cases = [
+        ("statement", "exit(0);\nputchar(missing);\n"),
+        ("function", "fn unused() {\n    return missing;\n}\n\nexit(0);\n"),
+    ];
+
+    for (name, source) in cases {
+        for level in ["-O0", "-O1", "-O2"] {
+            let run = compile(name, source, &[level, "--emit=ir"]);
+
+            assert_eq!(run.status.code(), Some(1), "exit code of the {} at {}", name, level);
+            assert!(String::from_utf8_lossy(&run.stderr).contains("Variable missing has not been declared"), "error of the {} at {}", name, level);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Checks the JSON printed by `--emit=tokens` and `--emit=ast`
+
+mod common;
+
+use std::process::Output;
+
+fn emit(what: &str, name: &str, source: &str) -> Output {
+    let path = common::write_program(&format!("emit_{}", name), source);
+
+    common::atomic(&[&format!("--emit={}", what), &path])
+}
+
+#[test]
+fn tokens_have_their_value_and_span() {
+    let run = emit("tokens", "tokens", "exit(0b11);");
+
+    let expected = r#"[
+  {
+    "kind": "Exit",
+    "text": "exit",
+    "value": null,
+    "doc": null,
+    "span": {
+      "line": 1,
+      "column": 1,
+      "length": 4
+    }
+  },
+  {
+    "kind": "ParenOpen",
+    "text": "(",
+    "value": null,
+    "doc": null,
+    "span": {
+      "line": 1,
+      "column": 5,
+      "length": 1
+    }
+  },
+  {
+    "kind": "IntegerLit",
+    "text": "0b11",
+    "value": 3,
+    "doc": null,
+    "span": {
+      "line": 1,
+      "column": 6,
+      "length": 4
+    }
+  },
+  {
+    "kind": "ParenClose",
+    "text": ")",
+    "value": null,
+    "doc": null,
+    "span": {
+      "line": 1,
+      "column": 10,
+      "length": 1
+    }
+  },
+  {
+    "kind": "Semicolon",
+    "text": ";",
+    "value": null,
+    "doc": null,
+    "span": {
+      "line": 1,
+      "column": 11,
+      "length": 1
+    }
+  }
+]
+"#;
+
+    assert_eq!(String::from_utf8_lossy(&run.stdout), expected);
+    assert_eq!(run.status.code(), Some(0));
+}
+
+#[test]
+fn ast_keeps_doc_comments_and_literal_text() {
+    let run = emit("ast", "ast", "/// The answer\nint x = 0x2A;\nexit(x + 1);\n");
+
+    let expected = r#"{
+  "kind": "Program",
+  "statements": [
+    {
+      "kind": "Declare",
+      "name": "x",
+      "span": {
+        "line": 2,
+        "column": 5,
+        "length": 1
+      },
+      "doc": "The answer",
+      "expression": {
+        "kind": "Integer",
+        "value": 42,
+        "text": "0x2A",
+        "span": {
+          "line": 2,
+          "column": 9,
+          "length": 4
+        }
+      }
+    },
+    {
+      "kind": "Exit",
+      "expression": {
+        "kind": "Add",
+        "value_1": {
+          "kind": "Identifier",
+          "name": "x",
+          "span": {
+            "line": 3,
+            "column": 6,
+            "length": 1
+          }
+        },
+        "value_2": {
+          "kind": "Integer",
+          "value": 1,
+          "text": "1",
+          "span": {
+            "line": 3,
+            "column": 10,
+            "length": 1
+          }
+        }
+      }
+    }
+  ]
+}
+"#;
+
+    assert_eq!(String::from_utf8_lossy(&run.stdout), expected);
+    assert_eq!(run.status.code(), Some(0));
+}
+
+#[test]
+fn ast_is_not_printed_for_a_program_that_does_not_parse() {
+    let run = emit("ast", "bad", "int x = ;\n");
+
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "");
+    assert_eq!(run.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("Expected an expression, found ;"));
+}
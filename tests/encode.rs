@@ -0,0 +1,276 @@
+//! Tests of the built in x86-64 encoder and ELF writer. Every form of every instruction
+//! is encoded and compared with what GNU as makes of the same line, and the ELF files
+//! are checked with `readelf`, linked with `ld` and run
+
+// the encoder is part of the binary, so its modules are compiled into the test as well
+#![allow(dead_code)]
+
+#[path = "../src/code_gen/asm.rs"]
+mod asm;
+#[path = "../src/code_gen/elf.rs"]
+mod elf;
+#[path = "../src/code_gen/encode.rs"]
+mod encode;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use asm::{Inst, Mem, Operand, Reg, Syntax};
+
+const REGISTERS: [Reg; 16] = [
+    Reg::Rax, Reg::Rcx, Reg::Rdx, Reg::Rbx, Reg::Rsp, Reg::Rbp, Reg::Rsi, Reg::Rdi,
+    Reg::R8, Reg::R9, Reg::R10, Reg::R11, Reg::R12, Reg::R13, Reg::R14, Reg::R15,
+];
+
+fn out_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+fn run_tool(command: &mut Command) -> String {
+    let output = command.output().expect("Could not run the tool");
+    assert!(output.status.success(), "{:?} failed:\n{}", command, String::from_utf8_lossy(&output.stderr));
+
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+/// The text section GNU as makes from the instructions
+fn assemble_with_as(name: &str, insts: &[Inst]) -> Vec<u8> {
+    let source = out_path(&format!("encode_{}.s", name));
+    let object = out_path(&format!("encode_{}.o", name));
+    let text = out_path(&format!("encode_{}.bin", name));
+
+    let mut listing = vec!(Inst::Section(".text".to_string()));
+    listing.extend_from_slice(insts);
+    fs::write(&source, asm::render(&listing, Syntax::Gas)).expect("Could not write the assembly");
+
+    run_tool(Command::new("as").arg(&source).arg("-o").arg(&object));
+    run_tool(Command::new("objcopy").args(["-O", "binary", "--only-section=.text"]).arg(&object).arg(&text));
+
+    fs::read(&text).expect("Could not read the text section")
+}
+
+/// Every operand an instruction that takes a register or memory can be given
+fn register_and_memory_operands() -> Vec<Operand> {
+    let mut operands: Vec<Operand> = REGISTERS.iter().map(|reg| Operand::Reg(*reg)).collect();
+
+    for base in REGISTERS {
+        // no displacement, an 8 bit one and a 32 bit one
+        for offset in [0, -8, 16, 200, -4096] {
+            operands.push(Operand::Mem(Mem::Base(base, offset)));
+        }
+    }
+    operands.push(Operand::Mem(Mem::Label("somewhere".to_string())));
+
+    return operands;
+}
+
+/// One instruction of every form the encoder has
+fn every_form() -> Vec<Inst> {
+    let operands = register_and_memory_operands();
+    let immediates = [0, 1, -1, 127, -128, 128, -129, 0x7fff_ffff, -0x8000_0000];
+
+    let mut insts = vec!();
+
+    for reg in REGISTERS {
+        for value in immediates.iter().chain(&[0x1_0000_0000, -0x8000_0001, i64::MAX, i64::MIN]) {
+            insts.push(Inst::Mov(Operand::Reg(reg), Operand::Imm(*value)));
+        }
+        for operand in &operands {
+            insts.push(Inst::Mov(Operand::Reg(reg), operand.clone()));
+            insts.push(Inst::Mov(operand.clone(), Operand::Reg(reg)));
+            insts.push(Inst::Imul(reg, operand.clone()));
+            insts.push(Inst::ImulImm(reg, operand.clone(), 3));
+            insts.push(Inst::ImulImm(reg, operand.clone(), -1000));
+
+            for (dest, source) in [(Operand::Reg(reg), operand.clone()), (operand.clone(), Operand::Reg(reg))] {
+                insts.push(Inst::Add(dest.clone(), source.clone()));
+                insts.push(Inst::Sub(dest.clone(), source.clone()));
+                insts.push(Inst::And(dest, source));
+            }
+        }
+    }
+
+    for operand in &operands {
+        for value in immediates {
+            insts.push(Inst::Add(operand.clone(), Operand::Imm(value)));
+            insts.push(Inst::Sub(operand.clone(), Operand::Imm(value)));
+            insts.push(Inst::And(operand.clone(), Operand::Imm(value)));
+        }
+        if operand.is_mem() {
+            insts.push(Inst::Mov(operand.clone(), Operand::Imm(-5)));
+            insts.push(Inst::Mov(operand.clone(), Operand::Imm(0x1234_5678)));
+        }
+
+        insts.push(Inst::Push(operand.clone()));
+        insts.push(Inst::Pop(operand.clone()));
+        insts.push(Inst::Idiv(operand.clone()));
+        insts.push(Inst::Neg(operand.clone()));
+        insts.push(Inst::CallIndirect(operand.clone()));
+    }
+
+    for value in immediates {
+        insts.push(Inst::Push(Operand::Imm(value)));
+    }
+
+    insts.extend([
+        Inst::Cqo,
+        Inst::Call("elsewhere".to_string()),
+        Inst::Jmp("elsewhere".to_string()),
+        Inst::Ret,
+        Inst::Syscall,
+    ]);
+
+    return insts;
+}
+
+#[test]
+fn every_instruction_form_matches_gnu_as() {
+    let insts = every_form();
+    let expected = assemble_with_as("every_form", &insts);
+
+    // encoded one at a time, so a difference can be pinned to an instruction
+    let mut offset = 0;
+    for inst in &insts {
+        let encoded = encode::encode(std::slice::from_ref(inst)).unwrap_or_else(|msg| panic!("{}", msg)).text;
+        let end = offset + encoded.len();
+
+        assert_eq!(
+            format!("{:02x?}", encoded),
+            format!("{:02x?}", &expected[offset..end.min(expected.len())]),
+            "encoding of `{}`", inst.format(Syntax::Gas).trim(),
+        );
+
+        offset = end;
+    }
+
+    assert_eq!(offset, expected.len());
+}
+
+#[test]
+fn forms_without_an_encoding_are_errors() {
+    let somewhere = || Operand::Mem(Mem::Label("somewhere".to_string()));
+    let on_stack = || Operand::Mem(Mem::Base(Reg::Rbp, -8));
+
+    let cases = [
+        (Inst::Mov(on_stack(), somewhere()), "Can't encode `mov QWORD [rbp - 8], QWORD [rel somewhere]`, the operands can't be used together"),
+        (Inst::Add(somewhere(), on_stack()), "Can't encode `add QWORD [rel somewhere], QWORD [rbp - 8]`, the operands can't be used together"),
+        (Inst::Mov(Operand::Imm(1), Operand::Reg(Reg::Rax)), "Can't encode `mov 1, rax`, an immediate can't be used there"),
+        (Inst::Mov(on_stack(), Operand::Imm(0x1_0000_0000)), "Can't encode `mov QWORD [rbp - 8], 4294967296`, the immediate 4294967296 doesn't fit in 32 bits"),
+        (Inst::Sub(Operand::Reg(Reg::Rsp), Operand::Imm(-0x8000_0001)), "Can't encode `sub rsp, -2147483649`, the immediate -2147483649 doesn't fit in 32 bits"),
+        (Inst::Imul(Reg::Rax, Operand::Imm(3)), "Can't encode `imul rax, 3`, an immediate can't be used there"),
+        (Inst::Idiv(Operand::Imm(3)), "Can't encode `idiv 3`, an immediate can't be used there"),
+        (Inst::Pop(Operand::Imm(3)), "Can't encode `pop 3`, an immediate can't be used there"),
+    ];
+
+    for (inst, message) in cases {
+        let insts = [Inst::Label("_start".to_string()), Inst::Ret, inst];
+
+        match encode::encode(&insts) {
+            Ok(_) => panic!("`{}` was encoded", insts[2].to_string().trim()),
+            Err(msg) => assert_eq!(msg, message),
+        }
+    }
+}
+
+/// A program that adds one to a number in the bss through a call, and exits with it
+fn exit_program() -> Vec<Inst> {
+    vec!(
+        Inst::Global("_start".to_string()),
+        Inst::Section(".text".to_string()),
+        Inst::Label("_start".to_string()),
+        Inst::Mov(Operand::Mem(Mem::Label("number".to_string())), Operand::Imm(41)),
+        Inst::Call("increment".to_string()),
+        Inst::Mov(Operand::Reg(Reg::Rdi), Operand::Mem(Mem::Label("number".to_string()))),
+        Inst::Mov(Operand::Reg(Reg::Rax), Operand::Imm(60)),
+        Inst::Syscall,
+        Inst::Label("increment".to_string()),
+        Inst::Add(Operand::Mem(Mem::Label("number".to_string())), Operand::Imm(1)),
+        Inst::Ret,
+        Inst::Section(".bss".to_string()),
+        Inst::Resq("number".to_string(), 1),
+    )
+}
+
+fn run_executable(path: &Path) -> Option<i32> {
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).expect("Could not make the file executable");
+
+    Command::new(path).status().expect("Could not run the executable").code()
+}
+
+#[test]
+fn executables_run() {
+    let object = encode::encode(&exit_program()).expect("Could not encode the program");
+    let executable = elf::executable(&object).expect("Could not make the executable");
+
+    let path = out_path("encode_executable");
+    fs::write(&path, executable).expect("Could not write the executable");
+
+    let headers = run_tool(Command::new("readelf").args(["-h", "-l"]).arg(&path));
+    assert!(headers.contains("EXEC (Executable file)"), "{}", headers);
+    assert!(headers.contains("Entry point address:               0x4000b0"), "{}", headers);
+
+    assert_eq!(run_executable(&path), Some(42));
+}
+
+#[test]
+fn object_files_link_with_ld() {
+    let object = encode::encode(&exit_program()).expect("Could not encode the program");
+    let relocatable = elf::relocatable(&object).expect("Could not make the object file");
+
+    let path = out_path("encode_relocatable.o");
+    fs::write(&path, relocatable).expect("Could not write the object file");
+
+    let symbols = run_tool(Command::new("readelf").args(["-s", "-r"]).arg(&path));
+    assert!(symbols.lines().any(|line| line.contains("GLOBAL") && line.ends_with(" _start")), "{}", symbols);
+    assert!(symbols.lines().any(|line| line.contains("LOCAL") && line.ends_with(" increment")), "{}", symbols);
+    assert!(symbols.contains("R_X86_64_PC32"), "{}", symbols);
+
+    let linked = out_path("encode_linked");
+    run_tool(Command::new("ld").arg("-o").arg(&linked).arg(&path));
+
+    assert_eq!(run_executable(&linked), Some(42));
+}
+
+#[test]
+fn data_starts_with_its_values() {
+    // the number starts at 41 in the data section, after a zeroed qword in the bss
+    let mut insts = exit_program();
+    insts.remove(3);
+    insts.extend([
+        Inst::Resq("padding".to_string(), 1),
+        Inst::Section(".data".to_string()),
+        Inst::Dq("number".to_string(), 41),
+    ]);
+    insts.retain(|inst| *inst != Inst::Resq("number".to_string(), 1));
+
+    let object = encode::encode(&insts).expect("Could not encode the program");
+    assert_eq!(object.data, 41_i64.to_le_bytes());
+
+    let path = out_path("encode_data");
+    fs::write(&path, elf::executable(&object).expect("Could not make the executable")).expect("Could not write the executable");
+    assert_eq!(run_executable(&path), Some(42));
+
+    let relocatable = out_path("encode_data.o");
+    fs::write(&relocatable, elf::relocatable(&object).expect("Could not make the object file")).expect("Could not write the object file");
+
+    let sections = run_tool(Command::new("readelf").arg("-S").arg(&relocatable));
+    assert!(sections.contains(".data             PROGBITS"), "{}", sections);
+
+    let linked = out_path("encode_data_linked");
+    run_tool(Command::new("ld").arg("-o").arg(&linked).arg(&relocatable));
+    assert_eq!(run_executable(&linked), Some(42));
+}
+
+#[test]
+fn undefined_labels_are_errors() {
+    let insts = [Inst::Label("_start".to_string()), Inst::Jmp("nowhere".to_string())];
+    let object = encode::encode(&insts).expect("Could not encode the program");
+
+    assert_eq!(elf::executable(&object).err(), Some("Undefined label nowhere".to_string()));
+    assert_eq!(elf::relocatable(&object).err(), Some("Undefined label nowhere".to_string()));
+
+    let object = encode::encode(&[Inst::Ret]).expect("Could not encode the program");
+    assert_eq!(elf::executable(&object).err(), Some("There is no _start label to begin at".to_string()));
+}
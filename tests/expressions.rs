@@ -0,0 +1,51 @@
+//! How the expression parser groups operators: precedence, associativity, unary minus
+//! and parentheses, checked through what the expressions work out to
+
+mod common;
+
+use std::process::Command;
+
+use common::{atomic, write_program};
+
+#[test]
+fn operators_group_by_precedence_and_from_the_left() {
+    let cases = [
+        ("precedence", "1 + 2 * 3", 7),
+        ("precedence_first", "2 * 3 + 1", 7),
+        ("precedence_division", "1 + 12 / 4 - 2", 2),
+        ("subtraction", "10 - 3 - 2", 5),
+        ("division", "100 / 10 / 5", 2),
+        ("same_precedence", "8 / 2 * 2", 8),
+        ("minus_product", "-2 * 3", -6),
+        ("minus_minus", "- -1", 1),
+        ("minus_after_operator", "1 - -1", 2),
+        ("minus_parentheses", "-(2 + 3)", -5),
+        ("parentheses", "(1 + 2) * 3", 9),
+        ("nested_parentheses", "2 * (10 - (3 - 1))", 16),
+        ("parentheses_right", "20 / (10 / 5)", 10),
+    ];
+
+    for (name, expression, value) in cases {
+        let path = write_program(&format!("expressions_{}", name), &format!("exit({});\n", expression));
+        let out_path = path.trim_end_matches(".at");
+
+        let compiled = atomic(&["-O0", &path, out_path]);
+        assert!(compiled.status.success(), "{} did not compile:\n{}", expression, String::from_utf8_lossy(&compiled.stderr));
+
+        // exit codes are the lowest byte of the value
+        for run in [Command::new(out_path).output().expect("Could not run the program"), atomic(&["run", "--interpret", &path])] {
+            assert_eq!(run.status.code(), Some(value & 0xff), "value of {}", expression);
+        }
+    }
+}
+
+#[test]
+fn subtraction_is_left_associative_in_the_ast() {
+    let path = write_program("expressions_ast", "exit(10 - 3 - 2);\n");
+    let run = atomic(&["--emit=ast", &path]);
+    let ast: String = String::from_utf8_lossy(&run.stdout).split_whitespace().collect();
+
+    // (10 - 3) - 2, the left side is the other subtraction
+    assert!(ast.contains(r#""expression":{"kind":"Sub","value_1":{"kind":"Sub","value_1":{"kind":"Integer","value":10,"#), "{}", ast);
+    assert_eq!(run.status.code(), Some(0));
+}
@@ -0,0 +1,94 @@
+//! Calls C library functions declared with `extern fn`, from an executable linked with
+//! `cc` and from the jit
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::{atomic, installed, write_program};
+
+fn check_run(run: &Output, how: &str) {
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "7Q", "output {}", how);
+    assert_eq!(run.status.code(), Some(42), "exit code {}", how);
+}
+
+#[test]
+fn executables_call_the_c_library() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/externs.at");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+
+    let mut assemblers = vec!(("builtin", "-O0"), ("builtin", "-O2"), ("gas", "-O1"));
+    if installed("nasm") {
+        assemblers.push(("nasm", "-O1"));
+    }
+
+    for (assembler, level) in assemblers {
+        let how = format!("with {} at {}", assembler, level);
+        let out_path = out_dir.join(format!("externs_{}{}", assembler, level));
+        let out_path = out_path.to_str().unwrap();
+
+        let assembler = format!("--assembler={}", assembler);
+        let compiled = atomic(&[&assembler, level, source.to_str().unwrap(), out_path]);
+        assert!(compiled.status.success(), "did not compile {}:\n{}", how, String::from_utf8_lossy(&compiled.stderr));
+
+        let run = Command::new(out_path).output().expect("Could not run the program");
+        check_run(&run, &how);
+    }
+}
+
+#[test]
+fn nasm_calls_the_c_library_through_the_plt() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/externs.at");
+    let out_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("externs_nasm_listing");
+
+    // the listing is written before nasm runs, so this doesn't need it installed
+    atomic(&["--assembler=nasm", source.to_str().unwrap(), out_path.to_str().unwrap()]);
+    let asm = fs::read_to_string(format!("{}.asm", out_path.display())).expect("Could not read the assembly");
+
+    for function in ["labs", "toupper", "putchar", "exit"] {
+        assert!(asm.contains(&format!("    call {} wrt ..plt\n", function)), "{} isn't called through the plt:\n{}", function, asm);
+    }
+    assert!(asm.contains("    call fn_digit\n"), "{}", asm);
+}
+
+#[test]
+fn jit_calls_the_c_library() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/externs.at");
+
+    check_run(&atomic(&["run", "--jit", source.to_str().unwrap()]), "from the jit");
+}
+
+#[test]
+fn interpreters_reject_extern_calls() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/externs.at");
+
+    for how in ["--interpret", "-O1"] {
+        let run = atomic(&["run", how, source.to_str().unwrap()]);
+
+        assert_eq!(run.status.code(), Some(1), "exit code with {}", how);
+        assert!(String::from_utf8_lossy(&run.stderr).contains("can only be called from native code"), "error with {}", how);
+    }
+}
+
+#[test]
+fn builtins_can_not_be_declared_extern() {
+    for builtin in ["putchar", "exit"] {
+        let source = write_program(&format!("extern_{}", builtin), &format!("extern fn {}(int c) -> int;\nexit(0);\n", builtin));
+        let compiled = atomic(&[&source, source.trim_end_matches(".at")]);
+
+        assert_eq!(compiled.status.code(), Some(1), "exit code with {}", builtin);
+        assert!(String::from_utf8_lossy(&compiled.stderr).contains(&format!("{} is a builtin", builtin)), "error with {}", builtin);
+    }
+}
+
+#[test]
+fn aarch64_rejects_extern_calls() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/externs.at");
+    let out_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("externs_aarch64");
+
+    let compiled = atomic(&["--target=aarch64", source.to_str().unwrap(), out_path.to_str().unwrap()]);
+    assert_eq!(compiled.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&compiled.stderr).contains("can't be called from AArch64 code"));
+}
@@ -0,0 +1,97 @@
+//! Constant folding, checked through the ir printed with `--emit=ir`
+
+mod common;
+
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, Output};
+
+fn emit_ir(name: &str, level: &str, source: &str) -> Output {
+    let path = common::write_program(&format!("fold_{}", name), source);
+
+    common::atomic(&[level, "--emit=ir", &path])
+}
+
+#[test]
+fn constants_and_identities_are_folded() {
+    let run = emit_ir("identities", "-O1", "int x = 5;\nputchar(10 * 10 + 2);\nputchar(x * 0 + 65);\nexit(x * 1 - 0);\n");
+
+    let expected = "global @x
+
+fn main() {
+b0:
+    store @x, 5
+    putchar 102
+    putchar 65
+    t0 = load @x
+    exit t0
+b1:
+    exit 0
+}
+";
+
+    assert_eq!(String::from_utf8_lossy(&run.stdout), expected);
+    assert_eq!(run.status.code(), Some(0));
+}
+
+#[test]
+fn dividing_by_a_constant_zero_is_an_error_at_every_level() {
+    let sources = [
+        ("zero", "int x = 5;\nexit(x / 0);\n"),
+        ("zero_literals", "exit(1 / 0);\n"),
+        ("zero_product", "int x = 5;\nint y = 2;\nexit(x / (y * 0));\n"),
+        ("zero_in_function", "fn f(int a) {\n    return a / (2 - 2);\n}\nexit(0);\n"),
+    ];
+
+    for (name, source) in sources {
+        for level in ["-O0", "-O1", "-O2"] {
+            let run = emit_ir(name, level, source);
+
+            assert_eq!(run.status.code(), Some(1), "exit code of {} at {}", name, level);
+            assert!(String::from_utf8_lossy(&run.stderr).contains("Division by zero, the divisor is always 0"), "error of {} at {}", name, level);
+        }
+    }
+}
+
+#[test]
+fn multiplying_by_zero_keeps_divisions_that_can_trap() {
+    let sources = [
+        ("zero_times_zero_divisor", "int y = 0;\nexit(0 * (7 / y));\n"),
+        ("overflow_times_zero", "int x = -9223372036854775807 - 1;\nexit((x / -1) * 0);\n"),
+    ];
+
+    for (name, source) in sources {
+        let path = common::write_program(&format!("fold_{}", name), source);
+
+        for level in ["-O0", "-O1", "-O2"] {
+            let out_path = format!("{}{}", path.trim_end_matches(".at"), level);
+            let compiled = common::atomic(&[level, &path, &out_path]);
+            assert!(compiled.status.success(), "{} did not compile at {}:\n{}", name, level, String::from_utf8_lossy(&compiled.stderr));
+
+            // SIGFPE
+            let run = Command::new(&out_path).status().expect("Could not run the program");
+            assert_eq!(run.signal(), Some(8), "signal of {} at {}", name, level);
+        }
+    }
+
+    // a divisor that can't trap is dropped with the rest
+    let run = emit_ir("safe_divisor_times_zero", "-O1", "int y = 7;\nexit(0 * (y / 7));\n");
+    assert!(String::from_utf8_lossy(&run.stdout).contains("    exit 0\n"), "{}", String::from_utf8_lossy(&run.stdout));
+}
+
+#[test]
+fn folding_does_not_hide_undeclared_variables() {
+    for level in ["-O0", "-O1"] {
+        let run = emit_ir("undeclared", level, "exit(y * 0);\n");
+
+        assert_eq!(run.status.code(), Some(1), "exit code at {}", level);
+        assert!(String::from_utf8_lossy(&run.stderr).contains("Variable y has not been declared"), "error at {}", level);
+    }
+}
+
+#[test]
+fn overflowing_division_is_left_to_the_program() {
+    let run = emit_ir("overflow", "-O1", "exit((-9223372036854775807 - 1) / -1);\n");
+
+    assert!(String::from_utf8_lossy(&run.stdout).contains("t0 = div -9223372036854775808, -1"));
+    assert_eq!(run.status.code(), Some(0));
+}
@@ -0,0 +1,126 @@
+//! Function parameters, call arguments, calls to functions defined later, shadowing
+//! globals, and the errors for calls that don't match a function
+
+mod common;
+
+use std::process::Command;
+
+use common::{atomic, write_program};
+
+/// Runs the program with the interpreter and as an executable, both have to agree
+fn check_runs(name: &str, source: &str, output: &str, exit_code: i32) {
+    let path = write_program(&format!("functions_{}", name), source);
+
+    let run = atomic(&["run", "--interpret", &path]);
+    assert_eq!(String::from_utf8_lossy(&run.stdout), output, "interpreted output of {}", name);
+    assert_eq!(run.status.code(), Some(exit_code), "interpreted exit code of {}", name);
+
+    let out_path = path.trim_end_matches(".at").to_string();
+    let compiled = atomic(&["-O0", &path, &out_path]);
+    assert!(compiled.status.success(), "{} did not compile:\n{}", name, String::from_utf8_lossy(&compiled.stderr));
+
+    let run = Command::new(&out_path).output().expect("Could not run the program");
+    assert_eq!(String::from_utf8_lossy(&run.stdout), output, "compiled output of {}", name);
+    assert_eq!(run.status.code(), Some(exit_code), "compiled exit code of {}", name);
+}
+
+fn check_error(name: &str, source: &str, message: &str) {
+    let path = write_program(&format!("functions_{}", name), source);
+    let run = atomic(&["run", "--interpret", &path]);
+
+    assert_eq!(run.status.code(), Some(1), "exit code of {}", name);
+    assert!(String::from_utf8_lossy(&run.stderr).contains(message), "error of {}:\n{}", name, String::from_utf8_lossy(&run.stderr));
+}
+
+#[test]
+fn arguments_are_passed_in_order() {
+    check_runs(
+        "order",
+        "fn first(int a, int b, int c) {\n    return a;\n}\n\nfn last(int a, int b, int c) {\n    return c;\n}\n\nputchar(first(65, 66, 67));\nputchar(last(65, 66, 67));\nexit(first(1, 2, 3) * 10 + last(1, 2, 3));\n",
+        "AC",
+        13,
+    );
+}
+
+#[test]
+fn arguments_can_be_expressions_and_calls() {
+    check_runs(
+        "nested",
+        "int base = 40;\n\nfn add(int a, int b) {\n    return a + b;\n}\n\nexit(add(add(base, 1), base / 2 - 1));\n",
+        "",
+        60,
+    );
+}
+
+#[test]
+fn functions_can_be_called_before_they_are_defined() {
+    check_runs(
+        "later",
+        "putchar(outer(2));\n\nfn outer(int n) {\n    return inner(n) + 1;\n}\n\nfn inner(int n) {\n    return 64 + n;\n}\n\nexit(inner(0) - 60);\n",
+        "C",
+        4,
+    );
+}
+
+#[test]
+fn calls_to_unknown_functions_are_errors() {
+    check_error("unknown", "missing(1);\n", "Call to unknown function missing");
+}
+
+#[test]
+fn calls_with_the_wrong_number_of_arguments_are_errors() {
+    check_error(
+        "arity",
+        "fn add(int a, int b) {\n    return a + b;\n}\n\nexit(add(1));\n",
+        "Function add takes 2 argument(s) but 1 were given, it is defined on line 1",
+    );
+}
+
+#[test]
+fn functions_can_only_be_declared_once() {
+    check_error(
+        "twice",
+        "fn f() {\n    return 1;\n}\n\nfn f() {\n    return 2;\n}\n",
+        "Function f has already been declared on line 1",
+    );
+}
+
+#[test]
+fn parameters_are_separated_by_commas() {
+    check_error("no_comma", "fn add(int a int b) {\n    return a + b;\n}\n", "Expected Comma");
+}
+
+#[test]
+fn parameters_and_locals_shadow_globals() {
+    let source = "\
+int x = 5;
+
+fn local(int a) {
+    int x = a * 2 + x;
+    x = x + 1;
+    return x;
+}
+
+fn parameter(int x) {
+    x = x + 1;
+    return x;
+}
+
+putchar(48 + local(1));
+putchar(48 + parameter(2));
+putchar(48 + x);
+x = 4;
+putchar(48 + local(1));
+exit(x);
+";
+
+    // the local's value is worked out before it exists, so it reads the global
+    check_runs("shadow", source, "8357", 4);
+}
+
+#[test]
+fn variables_can_only_be_declared_once_in_a_scope() {
+    check_error("global_twice", "int x = 1;\nint x = 2;\n", "Variable x has already been declared!");
+    check_error("local_twice", "fn f() {\n    int y = 1;\n    int y = 2;\n    return y;\n}\n", "Variable y has already been declared!");
+    check_error("local_and_parameter", "fn f(int y) {\n    int y = 2;\n    return y;\n}\n", "Variable y has already been declared!");
+}
@@ -0,0 +1,125 @@
+.global _start
+.text
+_start:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #112
+    // block 0
+    // store a
+    mov x9, #7
+    adrp x11, global_a
+    add x11, x11, :lo12:global_a
+    str x9, [x11]
+    // load a
+    adrp x11, global_a
+    add x11, x11, :lo12:global_a
+    ldr x9, [x11]
+    str x9, [x29, #-8]
+    ldr x9, [x29, #-8]
+    mov x10, #6
+    mul x9, x9, x10
+    str x9, [x29, #-16]
+    ldr x9, [x29, #-16]
+    mov x10, #2
+    sub x9, x9, x10
+    str x9, [x29, #-24]
+    // store b
+    ldr x9, [x29, #-24]
+    adrp x11, global_b
+    add x11, x11, :lo12:global_b
+    str x9, [x11]
+    // store big
+    movz x9, #52719, lsl #0
+    movk x9, #35243, lsl #16
+    movk x9, #17767, lsl #32
+    movk x9, #291, lsl #48
+    adrp x11, global_big
+    add x11, x11, :lo12:global_big
+    str x9, [x11]
+    // load b
+    adrp x11, global_b
+    add x11, x11, :lo12:global_b
+    ldr x9, [x11]
+    str x9, [x29, #-32]
+    ldr x9, [x29, #-32]
+    mov x10, #4
+    sdiv x9, x9, x10
+    str x9, [x29, #-40]
+    ldr x9, [x29, #-40]
+    mov x10, #60
+    add x9, x9, x10
+    str x9, [x29, #-48]
+    // put char, the syscall reads the byte from the stack
+    ldr x9, [x29, #-48]
+    sub sp, sp, #16
+    strb w9, [sp]
+    mov x0, #1
+    mov x1, sp
+    mov x2, #1
+    mov x8, #64
+    svc #0
+    add sp, sp, #16
+    // load a
+    adrp x11, global_a
+    add x11, x11, :lo12:global_a
+    ldr x9, [x11]
+    str x9, [x29, #-56]
+    ldr x9, [x29, #-56]
+    mov x10, #100
+    sub x9, x9, x10
+    str x9, [x29, #-64]
+    ldr x9, [x29, #-64]
+    neg x9, x9
+    str x9, [x29, #-72]
+    ldr x9, [x29, #-72]
+    mov x10, #25
+    sub x9, x9, x10
+    str x9, [x29, #-80]
+    // put char, the syscall reads the byte from the stack
+    ldr x9, [x29, #-80]
+    sub sp, sp, #16
+    strb w9, [sp]
+    mov x0, #1
+    mov x1, sp
+    mov x2, #1
+    mov x8, #64
+    svc #0
+    add sp, sp, #16
+    // load big
+    adrp x11, global_big
+    add x11, x11, :lo12:global_big
+    ldr x9, [x11]
+    str x9, [x29, #-88]
+    ldr x9, [x29, #-88]
+    movz x10, #4096, lsl #0
+    movk x10, #54437, lsl #16
+    movk x10, #232, lsl #32
+    sdiv x9, x9, x10
+    str x9, [x29, #-96]
+    // store b
+    ldr x9, [x29, #-96]
+    adrp x11, global_b
+    add x11, x11, :lo12:global_b
+    str x9, [x11]
+    // load b
+    adrp x11, global_b
+    add x11, x11, :lo12:global_b
+    ldr x9, [x11]
+    str x9, [x29, #-104]
+    // exiting
+    ldr x0, [x29, #-104]
+    mov x8, #93
+    svc #0
+    // block 1
+    // exiting
+    mov x0, #0
+    mov x8, #93
+    svc #0
+.bss
+.balign 8
+global_a:
+    .zero 8
+global_b:
+    .zero 8
+global_big:
+    .zero 8
@@ -0,0 +1,155 @@
+.global _start
+.text
+_start:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #32
+    // block 0
+    // store base
+    mov x9, #48
+    adrp x11, global_base
+    add x11, x11, :lo12:global_base
+    str x9, [x11]
+    sub sp, sp, #32
+    // argument 0
+    mov x9, #1
+    str x9, [sp, #0]
+    // argument 1
+    mov x9, #2
+    str x9, [sp, #8]
+    // argument 2
+    mov x9, #3
+    str x9, [sp, #16]
+    bl fn_sum
+    // remove the arguments
+    add sp, sp, #32
+    str x0, [x29, #-16]
+    sub sp, sp, #16
+    // argument 0
+    ldr x9, [x29, #-16]
+    str x9, [sp, #0]
+    bl fn_digit
+    // remove the arguments
+    add sp, sp, #16
+    str x0, [x29, #-8]
+    // put char, the syscall reads the byte from the stack
+    ldr x9, [x29, #-8]
+    sub sp, sp, #16
+    strb w9, [sp]
+    mov x0, #1
+    mov x1, sp
+    mov x2, #1
+    mov x8, #64
+    svc #0
+    add sp, sp, #16
+    sub sp, sp, #32
+    // argument 0
+    mov x9, #4
+    str x9, [sp, #0]
+    // argument 1
+    mov x9, #5
+    str x9, [sp, #8]
+    // argument 2
+    mov x9, #6
+    str x9, [sp, #16]
+    bl fn_sum
+    // remove the arguments
+    add sp, sp, #32
+    str x0, [x29, #-24]
+    // exiting
+    ldr x0, [x29, #-24]
+    mov x8, #93
+    svc #0
+    // block 1
+    // exiting
+    mov x0, #0
+    mov x8, #93
+    svc #0
+    // function definition
+fn_digit:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #32
+    // block 0
+    // load base
+    adrp x11, global_base
+    add x11, x11, :lo12:global_base
+    ldr x9, [x11]
+    str x9, [x29, #-8]
+    // load n
+    ldr x9, [x29, #16]
+    str x9, [x29, #-16]
+    ldr x9, [x29, #-8]
+    ldr x10, [x29, #-16]
+    add x9, x9, x10
+    str x9, [x29, #-24]
+    // return
+    ldr x0, [x29, #-24]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    // block 1
+    // return
+    mov x0, #0
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    // function definition
+fn_sum:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #64
+    // block 0
+    // load x
+    ldr x9, [x29, #16]
+    str x9, [x29, #-16]
+    sub sp, sp, #16
+    // argument 0
+    ldr x9, [x29, #-16]
+    str x9, [sp, #0]
+    bl fn_digit
+    // remove the arguments
+    add sp, sp, #16
+    str x0, [x29, #-8]
+    // put char, the syscall reads the byte from the stack
+    ldr x9, [x29, #-8]
+    sub sp, sp, #16
+    strb w9, [sp]
+    mov x0, #1
+    mov x1, sp
+    mov x2, #1
+    mov x8, #64
+    svc #0
+    add sp, sp, #16
+    // load x
+    ldr x9, [x29, #16]
+    str x9, [x29, #-24]
+    // load y
+    ldr x9, [x29, #24]
+    str x9, [x29, #-32]
+    ldr x9, [x29, #-24]
+    ldr x10, [x29, #-32]
+    add x9, x9, x10
+    str x9, [x29, #-40]
+    // load z
+    ldr x9, [x29, #32]
+    str x9, [x29, #-48]
+    ldr x9, [x29, #-40]
+    ldr x10, [x29, #-48]
+    add x9, x9, x10
+    str x9, [x29, #-56]
+    // return
+    ldr x0, [x29, #-56]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    // block 1
+    // return
+    mov x0, #0
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+.bss
+.balign 8
+global_base:
+    .zero 8
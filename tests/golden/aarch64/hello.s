@@ -0,0 +1,97 @@
+.global _start
+.text
+_start:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    // block 0
+    // store hi
+    mov x9, #102
+    adrp x11, global_hi
+    add x11, x11, :lo12:global_hi
+    str x9, [x11]
+    bl fn_hello
+    bl fn_hello
+    bl fn_hello
+    bl fn_hello
+    // put char, the syscall reads the byte from the stack
+    mov x9, #10
+    sub sp, sp, #16
+    strb w9, [sp]
+    mov x0, #1
+    mov x1, sp
+    mov x2, #1
+    mov x8, #64
+    svc #0
+    add sp, sp, #16
+    // exiting
+    mov x0, #0
+    mov x8, #93
+    svc #0
+    // block 1
+    // exiting
+    mov x0, #0
+    mov x8, #93
+    svc #0
+    // function definition
+fn_bye:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    // block 0
+    // load hi
+    adrp x11, global_hi
+    add x11, x11, :lo12:global_hi
+    ldr x9, [x11]
+    str x9, [x29, #-8]
+    // put char, the syscall reads the byte from the stack
+    ldr x9, [x29, #-8]
+    sub sp, sp, #16
+    strb w9, [sp]
+    mov x0, #1
+    mov x1, sp
+    mov x2, #1
+    mov x8, #64
+    svc #0
+    add sp, sp, #16
+    // return
+    mov x0, #0
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    // function definition
+fn_hello:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #32
+    // block 0
+    // store a
+    mov x9, #10
+    str x9, [x29, #-8]
+    // load a
+    ldr x9, [x29, #-8]
+    str x9, [x29, #-16]
+    ldr x9, [x29, #-16]
+    mov x10, #95
+    add x9, x9, x10
+    str x9, [x29, #-24]
+    // put char, the syscall reads the byte from the stack
+    ldr x9, [x29, #-24]
+    sub sp, sp, #16
+    strb w9, [sp]
+    mov x0, #1
+    mov x1, sp
+    mov x2, #1
+    mov x8, #64
+    svc #0
+    add sp, sp, #16
+    bl fn_bye
+    bl fn_bye
+    // return
+    mov x0, #0
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+.bss
+.balign 8
+global_hi:
+    .zero 8
@@ -0,0 +1,101 @@
+.global _start
+.text
+_start:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    // block 0
+    sub sp, sp, #16
+    // argument 0
+    mov x9, #5
+    str x9, [sp, #0]
+    // argument 1
+    mov x9, #0
+    str x9, [sp, #8]
+    bl fn_countdown
+    // remove the arguments
+    add sp, sp, #16
+    str x0, [x29, #-8]
+    // exiting
+    ldr x0, [x29, #-8]
+    mov x8, #93
+    svc #0
+    // block 1
+    // exiting
+    mov x0, #0
+    mov x8, #93
+    svc #0
+    // function definition
+fn_countdown:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #64
+    // block 0
+    // load n
+    ldr x9, [x29, #16]
+    str x9, [x29, #-8]
+    ldr x9, [x29, #-8]
+    mov x10, #48
+    add x9, x9, x10
+    str x9, [x29, #-16]
+    // put char, the syscall reads the byte from the stack
+    ldr x9, [x29, #-16]
+    sub sp, sp, #16
+    strb w9, [sp]
+    mov x0, #1
+    mov x1, sp
+    mov x2, #1
+    mov x8, #64
+    svc #0
+    add sp, sp, #16
+    // load n
+    ldr x9, [x29, #16]
+    str x9, [x29, #-32]
+    ldr x9, [x29, #-32]
+    mov x10, #1
+    sub x9, x9, x10
+    str x9, [x29, #-40]
+    // load acc
+    ldr x9, [x29, #24]
+    str x9, [x29, #-48]
+    // load n
+    ldr x9, [x29, #16]
+    str x9, [x29, #-56]
+    ldr x9, [x29, #-48]
+    ldr x10, [x29, #-56]
+    add x9, x9, x10
+    str x9, [x29, #-64]
+    // tail call step
+    ldr x9, [x29, #-40]
+    str x9, [x29, #16]
+    ldr x9, [x29, #-64]
+    str x9, [x29, #24]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    b fn_step
+    // block 1
+    // return
+    mov x0, #0
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    // function definition
+fn_step:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    // block 0
+    // load acc
+    ldr x9, [x29, #24]
+    str x9, [x29, #-8]
+    // return
+    ldr x0, [x29, #-8]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+    // block 1
+    // return
+    mov x0, #0
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
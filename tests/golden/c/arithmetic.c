@@ -0,0 +1,30 @@
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+static inline int64_t atomic_add(int64_t a, int64_t b) { return (int64_t)((uint64_t)a + (uint64_t)b); }
+static inline int64_t atomic_sub(int64_t a, int64_t b) { return (int64_t)((uint64_t)a - (uint64_t)b); }
+static inline int64_t atomic_mul(int64_t a, int64_t b) { return (int64_t)((uint64_t)a * (uint64_t)b); }
+static inline int64_t atomic_neg(int64_t a) { return (int64_t)(0 - (uint64_t)a); }
+static inline int64_t atomic_divide(int64_t a, int64_t b) {
+    if (b == 0 || (a == INT64_MIN && b == -1)) {
+        raise(SIGFPE);
+    }
+    return a / b;
+}
+
+static int64_t global_a;
+static int64_t global_b;
+static int64_t global_big;
+
+int main(void) {
+    global_a = 7;
+    global_b = atomic_sub(atomic_mul(global_a, 6), 2);
+    global_big = INT64_C(81985529216486895);
+    putchar((unsigned char)atomic_add(atomic_divide(global_b, 4), 60));
+    putchar((unsigned char)atomic_sub(atomic_neg(atomic_sub(global_a, 100)), 25));
+    global_b = atomic_divide(global_big, INT64_C(1000000000000));
+    exit((int)global_b);
+    return 0;
+}
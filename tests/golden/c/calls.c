@@ -0,0 +1,32 @@
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+static inline int64_t atomic_add(int64_t a, int64_t b) { return (int64_t)((uint64_t)a + (uint64_t)b); }
+
+static int64_t global_base;
+
+static int64_t fn_digit(int64_t local_n);
+static int64_t fn_sum(int64_t local_x, int64_t local_y, int64_t local_z);
+
+static int64_t fn_digit(int64_t local_n) {
+    return atomic_add(global_base, local_n);
+    return 0;
+}
+
+static int64_t fn_sum(int64_t local_x, int64_t local_y, int64_t local_z) {
+    int64_t t0 = fn_digit(local_x);
+    putchar((unsigned char)t0);
+    return atomic_add(atomic_add(local_x, local_y), local_z);
+    return 0;
+}
+
+int main(void) {
+    global_base = 48;
+    int64_t t0 = fn_sum(1, 2, 3);
+    int64_t t1 = fn_digit(t0);
+    putchar((unsigned char)t1);
+    int64_t t2 = fn_sum(4, 5, 6);
+    exit((int)t2);
+    return 0;
+}
@@ -0,0 +1,34 @@
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+static inline int64_t atomic_add(int64_t a, int64_t b) { return (int64_t)((uint64_t)a + (uint64_t)b); }
+
+static int64_t global_hi;
+
+static int64_t fn_bye(void);
+static int64_t fn_hello(void);
+
+static int64_t fn_bye(void) {
+    putchar((unsigned char)global_hi);
+    return 0;
+}
+
+static int64_t fn_hello(void) {
+    int64_t local_a = 10;
+    putchar((unsigned char)atomic_add(local_a, 95));
+    fn_bye();
+    fn_bye();
+    return 0;
+}
+
+int main(void) {
+    global_hi = 102;
+    fn_hello();
+    fn_hello();
+    fn_hello();
+    fn_hello();
+    putchar((unsigned char)10);
+    exit((int)0);
+    return 0;
+}
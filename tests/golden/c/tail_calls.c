@@ -0,0 +1,27 @@
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+static inline int64_t atomic_add(int64_t a, int64_t b) { return (int64_t)((uint64_t)a + (uint64_t)b); }
+static inline int64_t atomic_sub(int64_t a, int64_t b) { return (int64_t)((uint64_t)a - (uint64_t)b); }
+
+static int64_t fn_countdown(int64_t local_n, int64_t local_acc);
+static int64_t fn_step(int64_t local_n, int64_t local_acc);
+
+static int64_t fn_countdown(int64_t local_n, int64_t local_acc) {
+    putchar((unsigned char)atomic_add(local_n, 48));
+    int64_t t0 = fn_step(atomic_sub(local_n, 1), atomic_add(local_acc, local_n));
+    return t0;
+    return 0;
+}
+
+static int64_t fn_step(int64_t local_n, int64_t local_acc) {
+    return local_acc;
+    return 0;
+}
+
+int main(void) {
+    int64_t t0 = fn_countdown(5, 0);
+    exit((int)t0);
+    return 0;
+}
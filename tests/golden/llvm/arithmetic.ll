@@ -0,0 +1,35 @@
+@global_a = internal global i64 0
+@global_b = internal global i64 0
+@global_big = internal global i64 0
+
+define i32 @main() {
+    store i64 7, i64* @global_a
+    %t0 = load i64, i64* @global_a
+    %t1 = mul i64 %t0, 6
+    %t2 = sub i64 %t1, 2
+    store i64 %t2, i64* @global_b
+    store i64 81985529216486895, i64* @global_big
+    %t3 = load i64, i64* @global_b
+    %t4 = sdiv i64 %t3, 4
+    %t5 = add i64 %t4, 60
+    %t6 = trunc i64 %t5 to i32
+    call i32 @putchar(i32 %t6)
+    %t7 = load i64, i64* @global_a
+    %t8 = sub i64 %t7, 100
+    %t9 = sub i64 0, %t8
+    %t10 = sub i64 %t9, 25
+    %t11 = trunc i64 %t10 to i32
+    call i32 @putchar(i32 %t11)
+    %t12 = load i64, i64* @global_big
+    %t13 = sdiv i64 %t12, 1000000000000
+    store i64 %t13, i64* @global_b
+    %t14 = load i64, i64* @global_b
+    %t15 = trunc i64 %t14 to i32
+    call void @exit(i32 %t15)
+    unreachable
+dead1:
+    ret i32 0
+}
+
+declare i32 @putchar(i32)
+declare void @exit(i32) noreturn
@@ -0,0 +1,50 @@
+@global_base = internal global i64 0
+
+define internal i64 @fn_digit(i64 %param_n) {
+    %local_n = alloca i64
+    store i64 %param_n, i64* %local_n
+    %t0 = load i64, i64* @global_base
+    %t1 = load i64, i64* %local_n
+    %t2 = add i64 %t0, %t1
+    ret i64 %t2
+dead1:
+    ret i64 0
+}
+
+define internal i64 @fn_sum(i64 %param_x, i64 %param_y, i64 %param_z) {
+    %local_x = alloca i64
+    store i64 %param_x, i64* %local_x
+    %local_y = alloca i64
+    store i64 %param_y, i64* %local_y
+    %local_z = alloca i64
+    store i64 %param_z, i64* %local_z
+    %t0 = load i64, i64* %local_x
+    %t1 = call i64 @fn_digit(i64 %t0)
+    %t2 = trunc i64 %t1 to i32
+    call i32 @putchar(i32 %t2)
+    %t3 = load i64, i64* %local_x
+    %t4 = load i64, i64* %local_y
+    %t5 = add i64 %t3, %t4
+    %t6 = load i64, i64* %local_z
+    %t7 = add i64 %t5, %t6
+    ret i64 %t7
+dead1:
+    ret i64 0
+}
+
+define i32 @main() {
+    store i64 48, i64* @global_base
+    %t0 = call i64 @fn_sum(i64 1, i64 2, i64 3)
+    %t1 = call i64 @fn_digit(i64 %t0)
+    %t2 = trunc i64 %t1 to i32
+    call i32 @putchar(i32 %t2)
+    %t3 = call i64 @fn_sum(i64 4, i64 5, i64 6)
+    %t4 = trunc i64 %t3 to i32
+    call void @exit(i32 %t4)
+    unreachable
+dead1:
+    ret i32 0
+}
+
+declare i32 @putchar(i32)
+declare void @exit(i32) noreturn
@@ -0,0 +1,38 @@
+@global_hi = internal global i64 0
+
+define internal i64 @fn_bye() {
+    %t0 = load i64, i64* @global_hi
+    %t1 = trunc i64 %t0 to i32
+    call i32 @putchar(i32 %t1)
+    ret i64 0
+}
+
+define internal i64 @fn_hello() {
+    %local_a = alloca i64
+    store i64 10, i64* %local_a
+    %t0 = load i64, i64* %local_a
+    %t1 = add i64 %t0, 95
+    %t2 = trunc i64 %t1 to i32
+    call i32 @putchar(i32 %t2)
+    call i64 @fn_bye()
+    call i64 @fn_bye()
+    ret i64 0
+}
+
+define i32 @main() {
+    store i64 102, i64* @global_hi
+    call i64 @fn_hello()
+    call i64 @fn_hello()
+    call i64 @fn_hello()
+    call i64 @fn_hello()
+    %t0 = trunc i64 10 to i32
+    call i32 @putchar(i32 %t0)
+    %t1 = trunc i64 0 to i32
+    call void @exit(i32 %t1)
+    unreachable
+dead1:
+    ret i32 0
+}
+
+declare i32 @putchar(i32)
+declare void @exit(i32) noreturn
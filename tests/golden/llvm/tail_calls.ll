@@ -0,0 +1,42 @@
+define internal i64 @fn_countdown(i64 %param_n, i64 %param_acc) {
+    %local_n = alloca i64
+    store i64 %param_n, i64* %local_n
+    %local_acc = alloca i64
+    store i64 %param_acc, i64* %local_acc
+    %t0 = load i64, i64* %local_n
+    %t1 = add i64 %t0, 48
+    %t2 = trunc i64 %t1 to i32
+    call i32 @putchar(i32 %t2)
+    %t3 = load i64, i64* %local_n
+    %t4 = sub i64 %t3, 1
+    %t5 = load i64, i64* %local_acc
+    %t6 = load i64, i64* %local_n
+    %t7 = add i64 %t5, %t6
+    %t8 = call i64 @fn_step(i64 %t4, i64 %t7)
+    ret i64 %t8
+dead1:
+    ret i64 0
+}
+
+define internal i64 @fn_step(i64 %param_n, i64 %param_acc) {
+    %local_n = alloca i64
+    store i64 %param_n, i64* %local_n
+    %local_acc = alloca i64
+    store i64 %param_acc, i64* %local_acc
+    %t0 = load i64, i64* %local_acc
+    ret i64 %t0
+dead1:
+    ret i64 0
+}
+
+define i32 @main() {
+    %t0 = call i64 @fn_countdown(i64 5, i64 0)
+    %t1 = trunc i64 %t0 to i32
+    call void @exit(i32 %t1)
+    unreachable
+dead1:
+    ret i32 0
+}
+
+declare i32 @putchar(i32)
+declare void @exit(i32) noreturn
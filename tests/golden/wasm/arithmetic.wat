@@ -0,0 +1,47 @@
+(module
+  (import "env" "putchar" (func $putchar (param i32)))
+  (import "env" "exit" (func $exit (param i32)))
+  (memory (export "memory") 16)
+  (global $sp (mut i32) (i32.const 1048576))
+  ;; global a is at 0
+  ;; global b is at 8
+  ;; global big is at 16
+  (func $main (export "main")
+    (local $t0 i64)
+    (local $t1 i64)
+    (local $t2 i64)
+    (local $t3 i64)
+    (local $t4 i64)
+    (local $t5 i64)
+    (local $t6 i64)
+    (local $t7 i64)
+    (local $t8 i64)
+    (local $t9 i64)
+    (local $t10 i64)
+    (local $t11 i64)
+    (local $t12 i64)
+    (i64.store (i32.const 0) (i64.const 7))
+    (local.set $t0 (i64.load (i32.const 0)))
+    (local.set $t1 (i64.mul (local.get $t0) (i64.const 6)))
+    (local.set $t2 (i64.sub (local.get $t1) (i64.const 2)))
+    (i64.store (i32.const 8) (local.get $t2))
+    (i64.store (i32.const 16) (i64.const 81985529216486895))
+    (local.set $t3 (i64.load (i32.const 8)))
+    (local.set $t4 (i64.div_s (local.get $t3) (i64.const 4)))
+    (local.set $t5 (i64.add (local.get $t4) (i64.const 60)))
+    (call $putchar (i32.wrap_i64 (local.get $t5)))
+    (local.set $t6 (i64.load (i32.const 0)))
+    (local.set $t7 (i64.sub (local.get $t6) (i64.const 100)))
+    (local.set $t8 (i64.sub (i64.const 0) (local.get $t7)))
+    (local.set $t9 (i64.sub (local.get $t8) (i64.const 25)))
+    (call $putchar (i32.wrap_i64 (local.get $t9)))
+    (local.set $t10 (i64.load (i32.const 16)))
+    (local.set $t11 (i64.div_s (local.get $t10) (i64.const 1000000000000)))
+    (i64.store (i32.const 8) (local.get $t11))
+    (local.set $t12 (i64.load (i32.const 8)))
+    (call $exit (i32.wrap_i64 (local.get $t12)))
+    (unreachable)
+    (call $exit (i32.wrap_i64 (i64.const 0)))
+    (unreachable)
+  )
+)
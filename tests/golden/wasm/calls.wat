@@ -0,0 +1,64 @@
+(module
+  (import "env" "putchar" (func $putchar (param i32)))
+  (import "env" "exit" (func $exit (param i32)))
+  (memory (export "memory") 16)
+  (global $sp (mut i32) (i32.const 1048576))
+  ;; global base is at 0
+  (func $main (export "main")
+    (local $t0 i64)
+    (local $t1 i64)
+    (local $t2 i64)
+    (i64.store (i32.const 0) (i64.const 48))
+    (local.set $t1 (call $fn_sum (i64.const 1) (i64.const 2) (i64.const 3)))
+    (local.set $t0 (call $fn_digit (local.get $t1)))
+    (call $putchar (i32.wrap_i64 (local.get $t0)))
+    (local.set $t2 (call $fn_sum (i64.const 4) (i64.const 5) (i64.const 6)))
+    (call $exit (i32.wrap_i64 (local.get $t2)))
+    (unreachable)
+    (call $exit (i32.wrap_i64 (i64.const 0)))
+    (unreachable)
+  )
+  (func $fn_digit (param $p0 i64) (result i64)
+    (local $fp i32)
+    (local $t0 i64)
+    (local $t1 i64)
+    (local $t2 i64)
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 8)))
+    (local.set $fp (global.get $sp))
+    (i64.store offset=0 (local.get $fp) (local.get $p0))
+    (local.set $t0 (i64.load (i32.const 0)))
+    (local.set $t1 (i64.load offset=0 (local.get $fp)))
+    (local.set $t2 (i64.add (local.get $t0) (local.get $t1)))
+    (global.set $sp (i32.add (local.get $fp) (i32.const 8)))
+    (return (local.get $t2))
+    (global.set $sp (i32.add (local.get $fp) (i32.const 8)))
+    (return (i64.const 0))
+  )
+  (func $fn_sum (param $p0 i64) (param $p1 i64) (param $p2 i64) (result i64)
+    (local $fp i32)
+    (local $t0 i64)
+    (local $t1 i64)
+    (local $t2 i64)
+    (local $t3 i64)
+    (local $t4 i64)
+    (local $t5 i64)
+    (local $t6 i64)
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 24)))
+    (local.set $fp (global.get $sp))
+    (i64.store offset=0 (local.get $fp) (local.get $p0))
+    (i64.store offset=8 (local.get $fp) (local.get $p1))
+    (i64.store offset=16 (local.get $fp) (local.get $p2))
+    (local.set $t1 (i64.load offset=0 (local.get $fp)))
+    (local.set $t0 (call $fn_digit (local.get $t1)))
+    (call $putchar (i32.wrap_i64 (local.get $t0)))
+    (local.set $t2 (i64.load offset=0 (local.get $fp)))
+    (local.set $t3 (i64.load offset=8 (local.get $fp)))
+    (local.set $t4 (i64.add (local.get $t2) (local.get $t3)))
+    (local.set $t5 (i64.load offset=16 (local.get $fp)))
+    (local.set $t6 (i64.add (local.get $t4) (local.get $t5)))
+    (global.set $sp (i32.add (local.get $fp) (i32.const 24)))
+    (return (local.get $t6))
+    (global.set $sp (i32.add (local.get $fp) (i32.const 24)))
+    (return (i64.const 0))
+  )
+)
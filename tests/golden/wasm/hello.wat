@@ -0,0 +1,40 @@
+(module
+  (import "env" "putchar" (func $putchar (param i32)))
+  (import "env" "exit" (func $exit (param i32)))
+  (memory (export "memory") 16)
+  (global $sp (mut i32) (i32.const 1048576))
+  ;; global hi is at 0
+  (func $main (export "main")
+    (i64.store (i32.const 0) (i64.const 102))
+    (drop (call $fn_hello))
+    (drop (call $fn_hello))
+    (drop (call $fn_hello))
+    (drop (call $fn_hello))
+    (call $putchar (i32.wrap_i64 (i64.const 10)))
+    (call $exit (i32.wrap_i64 (i64.const 0)))
+    (unreachable)
+    (call $exit (i32.wrap_i64 (i64.const 0)))
+    (unreachable)
+  )
+  (func $fn_bye (result i64)
+    (local $t0 i64)
+    (local.set $t0 (i64.load (i32.const 0)))
+    (call $putchar (i32.wrap_i64 (local.get $t0)))
+    (return (i64.const 0))
+  )
+  (func $fn_hello (result i64)
+    (local $fp i32)
+    (local $t0 i64)
+    (local $t1 i64)
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 8)))
+    (local.set $fp (global.get $sp))
+    (i64.store offset=0 (local.get $fp) (i64.const 10))
+    (local.set $t0 (i64.load offset=0 (local.get $fp)))
+    (local.set $t1 (i64.add (local.get $t0) (i64.const 95)))
+    (call $putchar (i32.wrap_i64 (local.get $t1)))
+    (drop (call $fn_bye))
+    (drop (call $fn_bye))
+    (global.set $sp (i32.add (local.get $fp) (i32.const 8)))
+    (return (i64.const 0))
+  )
+)
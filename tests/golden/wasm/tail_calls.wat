@@ -0,0 +1,54 @@
+(module
+  (import "env" "putchar" (func $putchar (param i32)))
+  (import "env" "exit" (func $exit (param i32)))
+  (memory (export "memory") 16)
+  (global $sp (mut i32) (i32.const 1048576))
+  (func $main (export "main")
+    (local $t0 i64)
+    (local.set $t0 (call $fn_countdown (i64.const 5) (i64.const 0)))
+    (call $exit (i32.wrap_i64 (local.get $t0)))
+    (unreachable)
+    (call $exit (i32.wrap_i64 (i64.const 0)))
+    (unreachable)
+  )
+  (func $fn_countdown (param $p0 i64) (param $p1 i64) (result i64)
+    (local $fp i32)
+    (local $t0 i64)
+    (local $t1 i64)
+    (local $t2 i64)
+    (local $t3 i64)
+    (local $t4 i64)
+    (local $t5 i64)
+    (local $t6 i64)
+    (local $t7 i64)
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 16)))
+    (local.set $fp (global.get $sp))
+    (i64.store offset=0 (local.get $fp) (local.get $p0))
+    (i64.store offset=8 (local.get $fp) (local.get $p1))
+    (local.set $t0 (i64.load offset=0 (local.get $fp)))
+    (local.set $t1 (i64.add (local.get $t0) (i64.const 48)))
+    (call $putchar (i32.wrap_i64 (local.get $t1)))
+    (local.set $t3 (i64.load offset=0 (local.get $fp)))
+    (local.set $t4 (i64.sub (local.get $t3) (i64.const 1)))
+    (local.set $t5 (i64.load offset=8 (local.get $fp)))
+    (local.set $t6 (i64.load offset=0 (local.get $fp)))
+    (local.set $t7 (i64.add (local.get $t5) (local.get $t6)))
+    (global.set $sp (i32.add (local.get $fp) (i32.const 16)))
+    (return (call $fn_step (local.get $t4) (local.get $t7)))
+    (global.set $sp (i32.add (local.get $fp) (i32.const 16)))
+    (return (i64.const 0))
+  )
+  (func $fn_step (param $p0 i64) (param $p1 i64) (result i64)
+    (local $fp i32)
+    (local $t0 i64)
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 16)))
+    (local.set $fp (global.get $sp))
+    (i64.store offset=0 (local.get $fp) (local.get $p0))
+    (i64.store offset=8 (local.get $fp) (local.get $p1))
+    (local.set $t0 (i64.load offset=8 (local.get $fp)))
+    (global.set $sp (i32.add (local.get $fp) (i32.const 16)))
+    (return (local.get $t0))
+    (global.set $sp (i32.add (local.get $fp) (i32.const 16)))
+    (return (i64.const 0))
+  )
+)
@@ -0,0 +1,43 @@
+//! Inlining at -O2, checked through the ir printed with `--emit=ir`
+
+mod common;
+
+use std::process::Output;
+
+fn emit_ir(name: &str, source: &str) -> Output {
+    let path = common::write_program(&format!("inline_{}", name), source);
+
+    common::atomic(&["-O2", "--emit=ir", &path])
+}
+
+/// `g` is inlined into main in the first round, which brings a second call to `f` with it
+const CHAIN: &str = "fn f(int x) {\n    return x + 1;\n}\n\nfn g(int y) {\n    return f(y) * 2;\n}\n\nputchar(f(64));\nputchar(g(32));\nexit(0);\n";
+
+#[test]
+fn a_chain_of_small_calls_collapses() {
+    let run = emit_ir("chain", CHAIN);
+    let ir = String::from_utf8_lossy(&run.stdout);
+
+    assert!(!ir.contains("call"), "calls left in:\n{}", ir);
+    assert!(!ir.contains("fn f("), "f was not removed:\n{}", ir);
+    assert!(!ir.contains("fn g("), "g was not removed:\n{}", ir);
+}
+
+#[test]
+fn every_copy_gets_its_own_name() {
+    let run = emit_ir("names", CHAIN);
+    let ir = String::from_utf8_lossy(&run.stdout);
+
+    let stores: Vec<&str> = ir.lines().filter(|line| line.trim_start().starts_with("store ")).map(|line| line.trim()).collect();
+    assert_eq!(stores, ["store %f.0.x, 64", "store %g.0.y, 32", "store %f.1.x, t5"]);
+}
+
+#[test]
+fn recursive_and_noinline_functions_are_kept() {
+    let source = "fn forever(int n) {\n    return forever(n);\n}\n\n#[noinline]\nfn small() {\n    return 1;\n}\n\nexit(small() + forever(0));\n";
+    let run = emit_ir("kept", source);
+    let ir = String::from_utf8_lossy(&run.stdout);
+
+    assert!(ir.contains("call small()"), "small was inlined:\n{}", ir);
+    assert!(ir.contains("fn forever("), "forever was removed:\n{}", ir);
+}
@@ -0,0 +1,42 @@
+//! Runs the golden programs with `run --interpret`
+
+mod common;
+
+use std::path::Path;
+
+#[test]
+fn interpreter_runs_golden_programs() {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+
+    for common::Case { name, options, output, exit_code } in common::CASES {
+        let source = golden_dir.join(format!("{}.at", name));
+        let mut args = vec!("run", "--interpret");
+        args.extend(options);
+        args.push(source.to_str().unwrap());
+        let run = common::atomic(&args);
+
+        assert_eq!(String::from_utf8_lossy(&run.stdout), output, "output of {}", name);
+        assert_eq!(run.status.code(), Some(exit_code), "exit code of {}", name);
+    }
+}
+
+#[test]
+fn every_level_interprets_the_same() {
+    let sources = [
+        ("interpret_zero_divisor", "int y = 0;\nputchar(65);\nexit(0 * (7 / y));\n"),
+        ("interpret_folded", "int x = 5;\nputchar(x * 0 + 10 * 6 + 6);\nexit(x * 1 - 0);\n"),
+    ];
+
+    for (name, source) in sources {
+        let path = common::write_program(name, source);
+
+        let unoptimised = common::atomic(&["run", "--interpret", "-O0", &path]);
+        for level in ["-O1", "-O2"] {
+            let run = common::atomic(&["run", "--interpret", level, &path]);
+
+            assert_eq!(run.stdout, unoptimised.stdout, "output of {} at {}", name, level);
+            assert_eq!(run.stderr, unoptimised.stderr, "errors of {} at {}", name, level);
+            assert_eq!(run.status.code(), unoptimised.status.code(), "exit code of {} at {}", name, level);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Runs the golden programs with `run --jit`, and calls a function from the host
+
+mod common;
+
+use std::path::Path;
+
+#[test]
+fn jit_runs_golden_programs() {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+
+    for common::Case { name, options, output, exit_code } in common::CASES {
+        let source = golden_dir.join(format!("{}.at", name));
+        let mut args = vec!("run", "--jit");
+        args.extend(options);
+        args.push(source.to_str().unwrap());
+        let run = common::atomic(&args);
+
+        assert_eq!(String::from_utf8_lossy(&run.stdout), output, "output of {}", name);
+        assert_eq!(run.status.code(), Some(exit_code), "exit code of {}", name);
+    }
+}
+
+#[test]
+fn jit_calls_a_function_after_the_top_level_code() {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+
+    let source = golden_dir.join("calls.at");
+    let run = common::atomic(&["run", "--jit", "--call=sum", source.to_str().unwrap(), "1", "2", "-3"]);
+
+    // the top level code prints 164 and its exit is ignored, then sum prints 1 and returns 0
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "16410\n");
+    assert_eq!(run.status.code(), Some(0));
+}
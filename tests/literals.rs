@@ -0,0 +1,68 @@
+//! Integer literals in decimal, hex, binary and octal, with underscores, and the ones
+//! that are rejected
+
+mod common;
+
+use std::process::Command;
+
+use common::{atomic, write_program};
+
+#[test]
+fn literals_in_every_base_run() {
+    // 0x1_0000_0041 doesn't fit in 32 bits, so it is moved into a register with its own
+    // instruction, and nothing is folded at -O0
+    let source = "\
+int big = 0x1_0000_0041;
+putchar(big - 4_294_967_296);
+putchar(0b0100_0010);
+putchar(0o103);
+putchar(0XA);
+int minus_one = 0xffff_ffff_ffff_ffff;
+exit(minus_one + 43);
+";
+    let path = write_program("literals_bases", source);
+    let out_path = path.trim_end_matches(".at");
+
+    let compiled = atomic(&["-O0", "--assembler=builtin", &path, out_path]);
+    assert!(compiled.status.success(), "did not compile:\n{}", String::from_utf8_lossy(&compiled.stderr));
+
+    for run in [Command::new(out_path).output().expect("Could not run the program"), atomic(&["run", "--interpret", &path])] {
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "ABC\n");
+        assert_eq!(run.status.code(), Some(42));
+    }
+}
+
+#[test]
+fn literals_above_i64_max_wrap_around() {
+    let path = write_program("literals_wrap", "exit(0xffff_ffff_ffff_ffff);\n");
+    let run = atomic(&["--emit=tokens", &path]);
+    let tokens = String::from_utf8_lossy(&run.stdout);
+
+    assert!(tokens.contains("\"text\": \"0xffff_ffff_ffff_ffff\",\n    \"value\": -1,"), "{}", tokens);
+    assert_eq!(run.status.code(), Some(0));
+}
+
+#[test]
+fn malformed_literals_are_errors() {
+    let cases = [
+        ("too_big", "18446744073709551616", "Integer literal 18446744073709551616 does not fit in 64 bits"),
+        ("too_big_hex", "0x1_0000_0000_0000_0000", "Integer literal 0x1_0000_0000_0000_0000 does not fit in 64 bits"),
+        ("binary_digit", "0b102", "Invalid digit `2` in binary literal 0b102"),
+        ("octal_digit", "0o9", "Invalid digit `9` in octal literal 0o9"),
+        ("hex_digit", "0xfg", "Invalid digit `g` in hex literal 0xfg"),
+        ("no_digits", "0x", "Expected digits after the prefix in 0x"),
+        ("only_underscores", "0b__", "Expected digits after the prefix in 0b__"),
+        ("trailing_underscore", "1_000_", "Underscores in 1_000_ have to be between digits"),
+        ("underscore_after_prefix", "0x_ff", "Underscores in 0x_ff have to be between digits"),
+        // a word can't start with an underscore
+        ("leading_underscore", "_1", "Could not tokenise _"),
+    ];
+
+    for (name, literal, message) in cases {
+        let path = write_program(&format!("literals_{}", name), &format!("exit({});\n", literal));
+        let run = atomic(&["--emit=tokens", &path]);
+
+        assert_eq!(run.status.code(), Some(1), "exit code of {}", literal);
+        assert!(String::from_utf8_lossy(&run.stderr).contains(message), "error of {}:\n{}", literal, String::from_utf8_lossy(&run.stderr));
+    }
+}
@@ -0,0 +1,52 @@
+//! Golden file tests of the LLVM IR backend, and running the golden files compiled with
+//! `llc` or `clang` when one of them is installed
+
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use common::installed;
+
+#[test]
+fn llvm_matches_golden_files() {
+    common::check_golden_files("llvm", "ll");
+}
+
+#[test]
+fn llvm_golden_files_run() {
+    let use_llc = installed("llc");
+    if !use_llc && !installed("clang") {
+        eprintln!("neither llc nor clang is installed, the golden files aren't run");
+        return;
+    }
+
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/llvm");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+
+    for common::Case { name, output, exit_code, .. } in common::CASES {
+        let ll_path = golden_dir.join(format!("{}.ll", name));
+        let out_path = out_dir.join(format!("llvm_run_{}", name));
+
+        // llc makes an object file that cc links with the C library, clang does both
+        let compiled = if use_llc {
+            let object_path = out_dir.join(format!("llvm_run_{}.o", name));
+            let compiled = Command::new("llc")
+                .args(["-filetype=obj", "-relocation-model=pic", "-o"])
+                .arg(&object_path)
+                .arg(&ll_path)
+                .output()
+                .expect("Could not run llc");
+            assert!(compiled.status.success(), "{} did not compile:\n{}", name, String::from_utf8_lossy(&compiled.stderr));
+
+            Command::new("cc").arg(&object_path).arg("-o").arg(&out_path).output().expect("Could not run cc")
+        } else {
+            Command::new("clang").arg(&ll_path).arg("-o").arg(&out_path).output().expect("Could not run clang")
+        };
+        assert!(compiled.status.success(), "{} did not link:\n{}", name, String::from_utf8_lossy(&compiled.stderr));
+
+        let run = Command::new(&out_path).output().expect("Could not run the program");
+        assert_eq!(String::from_utf8_lossy(&run.stdout), output, "output of {}", name);
+        assert_eq!(run.status.code(), Some(exit_code), "exit code of {}", name);
+    }
+}
@@ -0,0 +1,97 @@
+//! Object files made with `--object`, linked into a C program with `cc`
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{atomic, write_program};
+
+const LIBRARY: &str = "int counter = 5;
+int negative = -3;
+int later = counter * 2;
+
+fn add(int a, int b) {
+    return a + b + counter;
+}
+
+fn bump() {
+    counter = counter + 1;
+}
+
+fn get_negative() {
+    return negative;
+}
+
+fn get_later() {
+    return later;
+}
+
+putchar(65);
+later = later + 1;
+";
+
+/// Uses the globals before and after running the top level code with `lib_init`
+const DRIVER: &str = "#include <stdio.h>
+
+long lib_add(long a, long b);
+long lib_get_negative(void);
+long lib_get_later(void);
+void lib_bump(void);
+void lib_init(void);
+
+int main(void) {
+    printf(\"%ld %ld %ld \", lib_add(1, 2), lib_get_negative(), lib_get_later());
+    fflush(stdout);
+
+    lib_init();
+    lib_bump();
+    printf(\" %ld %ld\\n\", lib_add(1, 2), lib_get_later());
+
+    return 0;
+}
+";
+
+#[test]
+fn top_level_code_runs_from_init() {
+    let source = write_program("object_library", LIBRARY);
+    let driver = Path::new(env!("CARGO_TARGET_TMPDIR")).join("object_driver.c");
+    fs::write(&driver, DRIVER).expect("Could not write the driver");
+
+    for (assembler, level) in [("builtin", "-O0"), ("builtin", "-O2"), ("gas", "-O0"), ("gas", "-O2")] {
+        let how = format!("with {} at {}", assembler, level);
+        let out_path = source.replace("library.at", &format!("library_{}{}", assembler, level));
+
+        let assembler = format!("--assembler={}", assembler);
+        let compiled = atomic(&["--object", "--export-prefix=lib_", &assembler, level, &source, &out_path]);
+        assert!(compiled.status.success(), "did not compile {}:\n{}", how, String::from_utf8_lossy(&compiled.stderr));
+
+        let linked = Command::new("cc")
+            .arg(&driver)
+            .arg(format!("{}.o", out_path))
+            .arg("-o")
+            .arg(&out_path)
+            .output()
+            .expect("Could not run cc");
+        assert!(linked.status.success(), "did not link {}:\n{}", how, String::from_utf8_lossy(&linked.stderr));
+
+        // the constants are there before init runs, `later` is worked out by it
+        let run = Command::new(&out_path).output().expect("Could not run the program");
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "8 -3 0 A 9 11\n", "output {}", how);
+        assert_eq!(run.status.code(), Some(0), "exit code {}", how);
+    }
+}
+
+#[test]
+fn functions_can_not_be_called_init() {
+    let source = write_program("object_init", "fn init() {\n    return 1;\n}\n");
+    let out_path = source.trim_end_matches(".at");
+
+    let compiled = atomic(&["--object", &source, out_path]);
+    assert_eq!(compiled.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&compiled.stderr).contains("Function init can't be exported"));
+
+    // anything else can
+    assert!(atomic(&[&source, out_path]).status.success());
+}
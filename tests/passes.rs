@@ -0,0 +1,103 @@
+//! The optimisation levels and the pass manager: which passes run at each level, turning
+//! passes off, and programs running the same at every level
+
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use common::{atomic, write_program};
+
+/// The status `--print-passes` gives each pass, in the order they run
+fn pass_statuses(options: &[&str]) -> Vec<(String, String)> {
+    let mut args = options.to_vec();
+    args.push("--print-passes");
+
+    let run = atomic(&args);
+    assert!(run.status.success(), "{:?} failed:\n{}", options, String::from_utf8_lossy(&run.stderr));
+
+    String::from_utf8_lossy(&run.stdout).lines()
+        .skip(1)
+        .map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            (columns[0].to_string(), columns[2].to_string())
+        })
+        .collect()
+}
+
+fn statuses(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+    expected.iter().map(|(name, status)| (name.to_string(), status.to_string())).collect()
+}
+
+#[test]
+fn each_level_runs_its_passes() {
+    assert_eq!(pass_statuses(&["-O0"]), statuses(&[
+        ("fold", "needs"), ("dce", "needs"), ("inline", "needs"),
+        ("tail-calls", "needs"), ("regalloc", "needs"), ("peephole", "needs"),
+    ]));
+    assert_eq!(pass_statuses(&["-O1"]), statuses(&[
+        ("fold", "runs"), ("dce", "runs"), ("inline", "needs"),
+        ("tail-calls", "needs"), ("regalloc", "runs"), ("peephole", "runs"),
+    ]));
+    assert_eq!(pass_statuses(&["-O2"]), statuses(&[
+        ("fold", "runs"), ("dce", "runs"), ("inline", "runs"),
+        ("tail-calls", "runs"), ("regalloc", "runs"), ("peephole", "runs"),
+    ]));
+}
+
+#[test]
+fn the_level_defaults_to_one_and_the_last_one_wins() {
+    assert_eq!(pass_statuses(&[]), pass_statuses(&["-O1"]));
+    assert_eq!(pass_statuses(&["-O2", "-O0"]), pass_statuses(&["-O0"]));
+}
+
+#[test]
+fn disabled_passes_do_not_run() {
+    let passes = pass_statuses(&["-O2", "--disable-pass=inline", "--disable-pass=peephole"]);
+
+    assert_eq!(passes[2], ("inline".to_string(), "disabled".to_string()));
+    assert_eq!(passes[5], ("peephole".to_string(), "disabled".to_string()));
+    assert_eq!(passes[3], ("tail-calls".to_string(), "runs".to_string()));
+
+    // without fold the constant expression is worked out by the program
+    let source = write_program("passes_constant", "putchar(10 * 10 + 2);\n");
+
+    let folded = atomic(&["-O1", "--emit=ir", &source]);
+    let unfolded = atomic(&["-O1", "--disable-pass=fold", "--emit=ir", &source]);
+
+    assert!(String::from_utf8_lossy(&folded.stdout).contains("putchar 102"));
+    assert!(String::from_utf8_lossy(&unfolded.stdout).contains("mul 10, 10"));
+}
+
+#[test]
+fn unknown_levels_and_passes_are_errors() {
+    let run = atomic(&["-O3", "--print-passes"]);
+    assert_eq!(run.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("Unknown optimisation level -O3, expected 0 to 2"));
+
+    let run = atomic(&["--disable-pass=nope", "--print-passes"]);
+    assert_eq!(run.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("Unknown pass `nope`, the passes are fold, dce, inline, tail-calls, regalloc, peephole"));
+}
+
+#[test]
+fn golden_programs_run_the_same_at_every_level() {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+
+    for common::Case { name, output, exit_code, .. } in common::CASES {
+        let source = golden_dir.join(format!("{}.at", name));
+
+        for level in ["-O0", "-O1", "-O2"] {
+            let out_path = out_dir.join(format!("passes_{}{}", name, level));
+            let out_path = out_path.to_str().unwrap();
+
+            let compiled = atomic(&[level, source.to_str().unwrap(), out_path]);
+            assert!(compiled.status.success(), "{} did not compile at {}:\n{}", name, level, String::from_utf8_lossy(&compiled.stderr));
+
+            let run = Command::new(out_path).output().expect("Could not run the program");
+            assert_eq!(String::from_utf8_lossy(&run.stdout), output, "output of {} at {}", name, level);
+            assert_eq!(run.status.code(), Some(exit_code), "exit code of {} at {}", name, level);
+        }
+    }
+}
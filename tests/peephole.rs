@@ -0,0 +1,91 @@
+//! Compiles programs with and without the peephole pass, through GNU as so the assembly
+//! is kept, and checks what the pass cleaned up and that the programs still run the same
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Compiles with the options and runs the executable, returns the assembly and what the
+/// program printed and exited with
+fn compile_and_run(source: &Path, out_name: &str, options: &[&str]) -> (Vec<String>, String, Option<i32>) {
+    let out_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(out_name);
+    let out_path = out_path.to_str().unwrap();
+
+    let mut args = vec!("--assembler=gas");
+    args.extend(options);
+    args.extend([source.to_str().unwrap(), out_path]);
+    let compiled = common::atomic(&args);
+    assert!(compiled.status.success(), "{} did not compile:\n{}", out_name, String::from_utf8_lossy(&compiled.stderr));
+
+    let asm = fs::read_to_string(format!("{}.s", out_path)).expect("Could not read the assembly");
+    let lines = asm.lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    let run = Command::new(out_path).output().expect("Could not run the program");
+
+    (lines, String::from_utf8_lossy(&run.stdout).to_string(), run.status.code())
+}
+
+/// Adjacent instructions that match the two patterns
+fn count_pairs(lines: &[String], first: fn(&str) -> bool, second: fn(&str) -> bool) -> usize {
+    lines.windows(2).filter(|pair| first(&pair[0]) && second(&pair[1])).count()
+}
+
+fn is_constant_into_rax(line: &str) -> bool {
+    line.strip_prefix("mov rax, ").is_some_and(|value| value.parse::<i32>().is_ok())
+}
+
+#[test]
+fn constants_are_pushed_directly() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/calls.at");
+
+    let (without, _, _) = compile_and_run(&source, "peephole_without", &["-O1", "--disable-pass=peephole"]);
+    let (with, _, _) = compile_and_run(&source, "peephole_with", &["-O1"]);
+
+    assert!(count_pairs(&without, is_constant_into_rax, |line| line == "push rax") > 0);
+    assert_eq!(count_pairs(&with, is_constant_into_rax, |line| line == "push rax"), 0);
+    assert!(with.len() < without.len());
+}
+
+#[test]
+fn pushed_arguments_are_moved_into_registers() {
+    // calls to C functions push the arguments and pop them into the argument registers
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/externs.at");
+    let is_push = |line: &str| line.starts_with("push ");
+    let is_pop = |line: &str| line.starts_with("pop ");
+
+    let (without, without_output, without_code) = compile_and_run(&source, "peephole_externs_without", &["-O1", "--disable-pass=peephole"]);
+    let (with, output, code) = compile_and_run(&source, "peephole_externs_with", &["-O1"]);
+
+    assert!(count_pairs(&without, is_push, is_pop) > 0);
+    assert_eq!(count_pairs(&with, is_push, is_pop), 0);
+
+    assert_eq!((output, code), (without_output, without_code));
+}
+
+#[test]
+fn golden_programs_have_no_pairs_left_and_run_the_same() {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+
+    for common::Case { name, output, exit_code, .. } in common::CASES {
+        let source = golden_dir.join(format!("{}.at", name));
+
+        // without the register allocator the code is a stack machine, full of pushes
+        for regalloc in [&[][..], &["--disable-pass=regalloc"][..]] {
+            let mut options = vec!("-O1");
+            options.extend(regalloc);
+
+            let (lines, stdout, code) = compile_and_run(&source, &format!("peephole_{}", name), &options);
+
+            assert_eq!(count_pairs(&lines, |line| line.starts_with("push "), |line| line.starts_with("pop ")), 0, "push and pop left in {}", name);
+            assert!(!lines.iter().any(|line| line == "add rsp, 0" || line == "sub rsp, 0"), "rsp moved by 0 in {}", name);
+
+            assert_eq!(stdout, output, "output of {} with {:?}", name, options);
+            assert_eq!(code, Some(exit_code), "exit code of {} with {:?}", name, options);
+        }
+    }
+}
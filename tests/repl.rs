@@ -0,0 +1,50 @@
+//! Feeds statements to `repl` on stdin
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn repl(input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_atomic-lang"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Could not run atomic-lang");
+
+    child.stdin.take().unwrap().write_all(input.as_bytes()).expect("Could not write to the repl");
+
+    child.wait_with_output().expect("The repl didn't finish")
+}
+
+#[test]
+fn repl_keeps_definitions_between_inputs() {
+    let run = repl("int x = 65;\nputchar(x);\nfn next(int n) {\n    return n + 1;\n}\nputchar(next(x));\nx = next(\n    x\n);\nputchar(x);\n");
+
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "ABB");
+    assert_eq!(run.status.code(), Some(0));
+}
+
+#[test]
+fn repl_reports_errors_and_carries_on() {
+    let run = repl("putchar(y);\nint y = 66;\nint y = 1;\nputchar(y / 0);\nputchar(y);\nexit(y - 60);\nputchar(y);\n");
+
+    let errors = String::from_utf8_lossy(&run.stderr);
+    assert!(errors.contains("Variable y has not been declared"), "{}", errors);
+    assert!(errors.contains("Variable y has already been declared"), "{}", errors);
+    assert!(errors.contains("Division by zero"), "{}", errors);
+
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "B");
+    assert_eq!(run.status.code(), Some(6));
+}
+
+#[test]
+fn repl_reads_functions_with_empty_lines() {
+    let run = repl("fn f() {\n    int a = 1;\n\n    return a;\n}\nputchar(f() + 64);\nint b = 2\n\nputchar(66);\n");
+
+    // the empty line after the missing semicolon still ends that input
+    assert!(String::from_utf8_lossy(&run.stderr).contains("Expected another token"), "{}", String::from_utf8_lossy(&run.stderr));
+
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "AB");
+    assert_eq!(run.status.code(), Some(0));
+}
@@ -0,0 +1,41 @@
+//! Deep recursion through tail calls, which only works if they don't grow the stack
+
+mod common;
+
+use std::process::Command;
+
+use common::{atomic, write_program};
+
+/// Counts down from ten million through a tail call in every frame. There are no
+/// conditions in the language, so every call makes a system call through the C library,
+/// `getpid` until the count reaches 0 and then `exit_group(42)`
+const COUNTDOWN: &str = "\
+extern fn syscall(int number, int code) -> int;
+
+/// 1 when n is 0, otherwise 0
+fn is_zero(int n) {
+    return 1 / (n * n + 1);
+}
+
+fn count(int n) {
+    syscall(39 + 192 * is_zero(n), 42);
+    return count(n - 1);
+}
+
+count(10000000);
+";
+
+#[test]
+fn ten_million_tail_calls_exit_normally() {
+    let path = write_program("tail_calls_countdown", COUNTDOWN);
+    let out_path = path.trim_end_matches(".at");
+
+    let compiled = atomic(&["-O2", &path, out_path]);
+    assert!(compiled.status.success(), "did not compile:\n{}", String::from_utf8_lossy(&compiled.stderr));
+
+    let native = Command::new(out_path).status().expect("Could not run the program");
+    assert_eq!(native.code(), Some(42), "native");
+
+    let jit = atomic(&["run", "--jit", "-O2", &path]);
+    assert_eq!(jit.status.code(), Some(42), "jit:\n{}", String::from_utf8_lossy(&jit.stderr));
+}
@@ -0,0 +1,97 @@
+//! Golden file tests of the WebAssembly backend. The golden modules are run with a small
+//! interpreter, and the ignored test also assembles them with `wat2wasm` or `wasm-tools`,
+//! which validate them, and runs them with `node`
+
+mod common;
+mod wat_interpreter;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::installed;
+
+/// Runs the module in the first argument, with `putchar` and `exit` for it to import, and
+/// exits with what it exits with
+const HOST: &str = "
+const bytes = require('fs').readFileSync(process.argv[1]);
+
+class Exit {
+    constructor(code) { this.code = code; }
+}
+
+const env = {
+    putchar: (value) => process.stdout.write(Buffer.from([value & 0xff])),
+    exit: (code) => { throw new Exit(code); },
+};
+
+WebAssembly.instantiate(bytes, { env }).then(({ instance }) => {
+    try {
+        instance.exports.main();
+    } catch (error) {
+        if (!(error instanceof Exit)) {
+            throw error;
+        }
+        process.exitCode = error.code & 0xff;
+    }
+});
+";
+
+/// Assembles the text module into a binary one, which fails if the module isn't valid
+fn assemble(wat_path: &Path, wasm_path: &Path) {
+    let mut commands = vec!();
+
+    if installed("wat2wasm") {
+        let mut command = Command::new("wat2wasm");
+        command.arg(wat_path).arg("-o").arg(wasm_path);
+        commands.push(command);
+    } else {
+        let mut parse = Command::new("wasm-tools");
+        parse.arg("parse").arg(wat_path).arg("-o").arg(wasm_path);
+        let mut validate = Command::new("wasm-tools");
+        validate.arg("validate").arg(wasm_path);
+        commands.extend([parse, validate]);
+    }
+
+    for mut command in commands {
+        let output = command.output().expect("Could not run the tool");
+        assert!(output.status.success(), "{} is not valid:\n{}", wat_path.display(), String::from_utf8_lossy(&output.stderr));
+    }
+}
+
+#[test]
+fn wasm_matches_golden_files() {
+    common::check_golden_files("wasm", "wat");
+}
+
+#[test]
+fn wasm_golden_files_run() {
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/wasm");
+
+    for common::Case { name, output, exit_code, .. } in common::CASES {
+        let wat = fs::read_to_string(golden_dir.join(format!("{}.wat", name))).expect("Missing golden file");
+        let run = wat_interpreter::run(&wat).unwrap_or_else(|trap| panic!("{} trapped: {}", name, trap));
+
+        assert_eq!(String::from_utf8_lossy(&run.output), output, "output of {}", name);
+        assert_eq!(run.exit_code & 0xff, exit_code, "exit code of {}", name);
+    }
+}
+
+#[test]
+#[ignore = "needs wat2wasm or wasm-tools, and node"]
+fn wasm_golden_files_are_valid_and_run_in_node() {
+    assert!(installed("wat2wasm") || installed("wasm-tools"), "neither wat2wasm nor wasm-tools is installed");
+    assert!(installed("node"), "node is not installed");
+
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/wasm");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+
+    for common::Case { name, output, exit_code, .. } in common::CASES {
+        let wasm_path = out_dir.join(format!("wasm_run_{}.wasm", name));
+        assemble(&golden_dir.join(format!("{}.wat", name)), &wasm_path);
+
+        let run = Command::new("node").arg("-e").arg(HOST).arg(&wasm_path).output().expect("Could not run node");
+        assert_eq!(String::from_utf8_lossy(&run.stdout), output, "output of {}:\n{}", name, String::from_utf8_lossy(&run.stderr));
+        assert_eq!(run.status.code(), Some(exit_code), "exit code of {}", name);
+    }
+}
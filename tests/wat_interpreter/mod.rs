@@ -0,0 +1,303 @@
+//! A small interpreter for the WebAssembly text the wasm backend writes, so its output
+//! can be run without a wasm runtime. It only knows the folded instructions the backend
+//! uses, and gives the module `putchar` and `exit` like a host would
+
+use std::collections::HashMap;
+
+enum Sexpr {
+    Atom(String),
+    List(Vec<Sexpr>),
+}
+
+impl Sexpr {
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexpr::Atom(atom) => Some(atom),
+            Sexpr::List(_) => None,
+        }
+    }
+
+    fn list(&self) -> Option<&[Sexpr]> {
+        match self {
+            Sexpr::Atom(_) => None,
+            Sexpr::List(list) => Some(list),
+        }
+    }
+
+    /// The first atom of a list, what kind of field or instruction it is
+    fn head(&self) -> Option<&str> {
+        self.list()?.first()?.atom()
+    }
+}
+
+/// What running `main` did
+pub struct Run {
+    pub output: Vec<u8>,
+    pub exit_code: i32,
+}
+
+struct Func<'a> {
+    params: Vec<String>,
+    /// The params followed by the locals
+    locals: Vec<String>,
+    body: &'a [Sexpr],
+}
+
+/// Why a function stopped early
+enum Stop {
+    Return(i64),
+    Exit(i32),
+    Trap(String),
+}
+
+struct Machine<'a> {
+    funcs: HashMap<String, Func<'a>>,
+    imports: HashMap<String, String>,
+    globals: HashMap<String, i64>,
+    memory: Vec<u8>,
+    output: Vec<u8>,
+}
+
+/// Runs the exported `main` of the module. A trap is an error
+pub fn run(wat: &str) -> Result<Run, String> {
+    let tokens = tokenize(wat);
+    let mut position = 0;
+    let module = parse(&tokens, &mut position)?;
+
+    let fields = module.list().ok_or("The module isn't a list")?;
+    if fields.first().and_then(Sexpr::atom) != Some("module") {
+        return Err("Expected a module".to_string());
+    }
+
+    let mut machine = Machine {
+        funcs: HashMap::new(),
+        imports: HashMap::new(),
+        globals: HashMap::new(),
+        memory: vec!(),
+        output: vec!(),
+    };
+    let mut main = None;
+
+    for field in &fields[1..] {
+        let list = field.list().unwrap_or_default();
+
+        match field.head() {
+            Some("import") => {
+                let name = list.get(2).and_then(Sexpr::atom).ok_or("Import without a name")?;
+                let func = list.get(3).and_then(Sexpr::list).and_then(|func| func.get(1)?.atom()).ok_or("Import without a function")?;
+                machine.imports.insert(func.to_string(), name.trim_matches('"').to_string());
+            },
+            Some("memory") => {
+                let pages = list.last().and_then(Sexpr::atom).ok_or("Memory without a size")?;
+                let pages: usize = pages.parse().map_err(|_| "Bad memory size")?;
+                machine.memory = vec!(0; pages * 65536);
+            },
+            Some("global") => {
+                let name = list.get(1).and_then(Sexpr::atom).ok_or("Global without a name")?;
+                let value = list.get(3).and_then(Sexpr::list).and_then(|init| init.get(1)?.atom()).ok_or("Global without a value")?;
+                machine.globals.insert(name.to_string(), value.parse().map_err(|_| "Bad global value")?);
+            },
+            Some("func") => {
+                let name = list.get(1).and_then(Sexpr::atom).ok_or("Function without a name")?;
+                let mut func = Func { params: vec!(), locals: vec!(), body: &[] };
+
+                let mut i = 2;
+                while i < list.len() {
+                    match list[i].head() {
+                        Some("export") => {
+                            if list[i].list().and_then(|export| export.get(1)?.atom()) == Some("\"main\"") {
+                                main = Some(name.to_string());
+                            }
+                        },
+                        Some("param") | Some("local") => {
+                            let local = list[i].list().and_then(|local| local.get(1)?.atom()).ok_or("Unnamed local")?;
+                            if list[i].head() == Some("param") {
+                                func.params.push(local.to_string());
+                            }
+                            func.locals.push(local.to_string());
+                        },
+                        Some("result") => {},
+                        _ => break,
+                    }
+                    i += 1;
+                }
+
+                func.body = &list[i..];
+                machine.funcs.insert(name.to_string(), func);
+            },
+            _ => return Err("Unknown module field".to_string()),
+        }
+    }
+
+    let main = main.ok_or("Nothing is exported as main")?;
+
+    let exit_code = match machine.call(&main, vec!()) {
+        Ok(_) => 0,
+        Err(Stop::Exit(code)) => code,
+        Err(Stop::Return(_)) => unreachable!("Returns are handled by the call"),
+        Err(Stop::Trap(trap)) => return Err(trap),
+    };
+
+    Ok(Run { output: machine.output, exit_code })
+}
+
+impl<'a> Machine<'a> {
+    fn call(&mut self, name: &str, args: Vec<i64>) -> Result<i64, Stop> {
+        if let Some(import) = self.imports.get(name) {
+            match import.as_str() {
+                "putchar" => self.output.push(args[0] as u8),
+                "exit" => return Err(Stop::Exit(args[0] as i32)),
+                _ => return Err(Stop::Trap(format!("Unknown import {}", import))),
+            }
+            return Ok(0);
+        }
+
+        let Some(func) = self.funcs.get(name) else {
+            return Err(Stop::Trap(format!("Unknown function {}", name)));
+        };
+        let body = func.body;
+
+        let mut locals: HashMap<String, i64> = func.locals.iter().map(|local| (local.clone(), 0)).collect();
+        for (param, arg) in func.params.iter().zip(args) {
+            locals.insert(param.clone(), arg);
+        }
+
+        for instr in body {
+            match self.eval(instr, &mut locals) {
+                Ok(_) => {},
+                Err(Stop::Return(value)) => return Ok(value),
+                Err(stop) => return Err(stop),
+            }
+        }
+
+        Ok(0)
+    }
+
+    fn eval(&mut self, instr: &Sexpr, locals: &mut HashMap<String, i64>) -> Result<i64, Stop> {
+        let list = instr.list().ok_or_else(|| Stop::Trap("Expected an instruction".to_string()))?;
+        let op = instr.head().ok_or_else(|| Stop::Trap("Expected an instruction name".to_string()))?;
+
+        let mut immediates = vec!();
+        let mut operands = vec!();
+        for arg in &list[1..] {
+            match arg {
+                Sexpr::Atom(atom) => immediates.push(atom.as_str()),
+                Sexpr::List(_) => operands.push(self.eval(arg, locals)?),
+            }
+        }
+
+        let immediate = |i: usize| -> Result<&str, Stop> {
+            immediates.get(i).copied().ok_or_else(|| Stop::Trap(format!("{} needs an immediate", op)))
+        };
+        let offset = immediates.first()
+            .and_then(|imm| imm.strip_prefix("offset="))
+            .map_or(0, |offset| offset.parse::<usize>().unwrap());
+
+        let value = match op {
+            "i64.const" | "i32.const" => immediate(0)?.parse().map_err(|_| Stop::Trap("Bad constant".to_string()))?,
+            "local.get" => locals[immediate(0)?],
+            "local.set" => {
+                locals.insert(immediate(0)?.to_string(), operands[0]);
+                0
+            },
+            "global.get" => self.globals[immediate(0)?],
+            "global.set" => {
+                self.globals.insert(immediate(0)?.to_string(), operands[0]);
+                0
+            },
+            "i64.load" => {
+                let address = self.address(operands[0], offset)?;
+                i64::from_le_bytes(self.memory[address..address + 8].try_into().unwrap())
+            },
+            "i64.store" => {
+                let address = self.address(operands[0], offset)?;
+                self.memory[address..address + 8].copy_from_slice(&operands[1].to_le_bytes());
+                0
+            },
+            "i64.add" => operands[0].wrapping_add(operands[1]),
+            "i64.sub" => operands[0].wrapping_sub(operands[1]),
+            "i64.mul" => operands[0].wrapping_mul(operands[1]),
+            "i64.div_s" => operands[0].checked_div(operands[1]).ok_or_else(|| Stop::Trap("Division trapped".to_string()))?,
+            "i32.add" => (operands[0] as i32).wrapping_add(operands[1] as i32) as i64,
+            "i32.sub" => (operands[0] as i32).wrapping_sub(operands[1] as i32) as i64,
+            "i32.wrap_i64" => operands[0] as i32 as i64,
+            "call" => self.call(immediate(0)?, operands)?,
+            "drop" => 0,
+            "return" => return Err(Stop::Return(operands.first().copied().unwrap_or(0))),
+            "unreachable" => return Err(Stop::Trap("Reached unreachable".to_string())),
+            _ => return Err(Stop::Trap(format!("Unknown instruction {}", op))),
+        };
+
+        Ok(value)
+    }
+
+    fn address(&self, base: i64, offset: usize) -> Result<usize, Stop> {
+        let address = base as u32 as usize + offset;
+
+        if address + 8 > self.memory.len() {
+            return Err(Stop::Trap(format!("Out of bounds memory access at {}", address)));
+        }
+
+        Ok(address)
+    }
+}
+
+fn tokenize(wat: &str) -> Vec<String> {
+    let mut tokens = vec!();
+    let mut chars = wat.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '(' | ')' => {
+                tokens.push(c.to_string());
+                chars.next();
+            },
+            ';' => {
+                // a line comment
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            },
+            '"' => {
+                let mut string = String::from(chars.next().unwrap());
+                for c in chars.by_ref() {
+                    string.push(c);
+                    if c == '"' {
+                        break;
+                    }
+                }
+                tokens.push(string);
+            },
+            c if c.is_whitespace() => {
+                chars.next();
+            },
+            _ => {
+                let mut atom = String::new();
+                while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != '(' && c != ')') {
+                    atom.push(c);
+                }
+                tokens.push(atom);
+            },
+        }
+    }
+
+    tokens
+}
+
+fn parse(tokens: &[String], position: &mut usize) -> Result<Sexpr, String> {
+    let token = tokens.get(*position).ok_or("Unexpected end of the module")?;
+    *position += 1;
+
+    if token == ")" {
+        return Err("Unexpected )".to_string());
+    }
+    if token != "(" {
+        return Ok(Sexpr::Atom(token.clone()));
+    }
+
+    let mut list = vec!();
+    while tokens.get(*position).ok_or("Unclosed (")? != ")" {
+        list.push(parse(tokens, position)?);
+    }
+    *position += 1;
+
+    Ok(Sexpr::List(list))
+}